@@ -0,0 +1,170 @@
+use crate::config::{
+    module::{Module, ModuleKind},
+    overlay_loads::OverlayLoads,
+    relocation::{RelocationKind, RelocationModule},
+    section::SectionKind,
+    symbol::SymbolMaps,
+};
+
+use super::{data::RelocationResult, overlay_groups::OverlayGroups};
+
+/// Narrows down the candidates of references which lead to the same address in multiple overlays. Candidates which the kind
+/// of reference can't lead to are discarded first, then overlays which can't be loaded at the same time as the referencing
+/// overlay. If there are still multiple candidates, Thumb
+/// pointers prefer code over data, then overlays loaded by the referencing module are preferred, and lastly overlays with a
+/// symbol at the referenced address.
+pub fn resolve_ambiguous_references(
+    modules: &[Module],
+    module_index: usize,
+    overlay_groups: &OverlayGroups,
+    overlay_loads: &OverlayLoads,
+    symbol_maps: &SymbolMaps,
+    result: &mut RelocationResult,
+) {
+    let source_kind = modules[module_index].kind();
+
+    for symbol in &mut result.external_symbols {
+        if symbol.candidates.len() < 2 {
+            continue;
+        }
+        let indices = symbol.candidates.iter().map(|candidate| candidate.module_index).collect::<Vec<_>>();
+        // External symbols come from pointers in data
+        let indices = filter_by_kind(modules, &indices, RelocationKind::Load, symbol.address);
+        let indices =
+            narrow_candidates(modules, source_kind, &indices, symbol.address, overlay_groups, overlay_loads, symbol_maps);
+        symbol.candidates.retain(|candidate| indices.contains(&candidate.module_index));
+    }
+
+    for relocation in &mut result.relocations {
+        let RelocationModule::Overlays { ids } = relocation.module() else { continue };
+        let indices = ids
+            .iter()
+            .filter_map(|&id| modules.iter().position(|module| module.kind() == ModuleKind::Overlay(id)))
+            .collect::<Vec<_>>();
+        let indices = filter_by_kind(modules, &indices, relocation.kind(), relocation.to_address());
+        let indices = narrow_candidates(
+            modules,
+            source_kind,
+            &indices,
+            relocation.to_address(),
+            overlay_groups,
+            overlay_loads,
+            symbol_maps,
+        );
+        let ids = indices
+            .into_iter()
+            .filter_map(|index| if let ModuleKind::Overlay(id) = modules[index].kind() { Some(id) } else { None })
+            .collect::<Vec<_>>();
+        match ids.len() {
+            0 => {}
+            1 => relocation.set_module(RelocationModule::Overlay { id: ids[0] }),
+            _ => relocation.set_module(RelocationModule::Overlays { ids }),
+        }
+    }
+}
+
+/// Keeps the candidates which a relocation of the given kind can lead to. Calls and branches lead to code, in the mode that
+/// the instruction switches to, and loads of even pointers prefer data over code. If no candidate fits, all are kept.
+fn filter_by_kind(modules: &[Module], candidates: &[usize], kind: RelocationKind, pointer: u32) -> Vec<usize> {
+    let address = pointer & !1;
+    let callee_thumb = match kind {
+        RelocationKind::ArmCall | RelocationKind::ThumbCallArm | RelocationKind::ArmBranch => Some(false),
+        RelocationKind::ThumbCall | RelocationKind::ArmCallThumb | RelocationKind::ThumbBranch => Some(true),
+        _ => None,
+    };
+    let is_code = |index: usize| {
+        modules[index]
+            .sections()
+            .get_by_contained_address(address)
+            .is_some_and(|(_, section)| section.kind() == SectionKind::Code)
+    };
+
+    let filtered = if let Some(thumb) = callee_thumb {
+        candidates
+            .iter()
+            .copied()
+            .filter(|&index| is_code(index))
+            .filter(|&index| {
+                // Functions which weren't found by the analysis are given the benefit of the doubt
+                let function = modules[index]
+                    .sections()
+                    .get_by_contained_address(address)
+                    .and_then(|(_, section)| section.functions().get(&address));
+                function.map_or(true, |function| function.is_thumb() == thumb)
+            })
+            .collect::<Vec<_>>()
+    } else if kind == RelocationKind::Load && (pointer & 1) == 0 {
+        candidates.iter().copied().filter(|&index| !is_code(index)).collect::<Vec<_>>()
+    } else {
+        return candidates.to_vec();
+    };
+
+    if filtered.is_empty() {
+        candidates.to_vec()
+    } else {
+        filtered
+    }
+}
+
+/// Returns the module indices which remain after narrowing. A step which would discard every candidate is skipped.
+fn narrow_candidates(
+    modules: &[Module],
+    source_kind: ModuleKind,
+    candidates: &[usize],
+    pointer: u32,
+    overlay_groups: &OverlayGroups,
+    overlay_loads: &OverlayLoads,
+    symbol_maps: &SymbolMaps,
+) -> Vec<usize> {
+    let mut narrowed = candidates.to_vec();
+    let address = pointer & !1;
+
+    let retain = |narrowed: &mut Vec<usize>, predicate: &dyn Fn(&Module) -> bool| {
+        let remaining = narrowed.iter().copied().filter(|&index| predicate(&modules[index])).collect::<Vec<_>>();
+        if !remaining.is_empty() {
+            *narrowed = remaining;
+        }
+    };
+
+    if let ModuleKind::Overlay(source_id) = source_kind {
+        // Main and autoloads can reference any overlay, but an overlay can only reference overlays loaded alongside it
+        retain(&mut narrowed, &|module| match module.kind() {
+            ModuleKind::Overlay(id) => overlay_groups.can_coexist(source_id, id),
+            _ => true,
+        });
+    }
+
+    if narrowed.len() > 1 && (pointer & 1) != 0 {
+        // Odd pointers are far more likely to be Thumb functions than unaligned data
+        retain(&mut narrowed, &|module| {
+            module.sections().get_by_contained_address(address).is_some_and(|(_, section)| section.kind() == SectionKind::Code)
+        });
+    }
+
+    if narrowed.len() > 1 {
+        // An overlay which the referencing module loads by itself is likely to be loaded when the reference is used
+        retain(&mut narrowed, &|module| match module.kind() {
+            ModuleKind::Overlay(id) => overlay_loads.loads(source_kind, id),
+            _ => false,
+        });
+    }
+
+    if narrowed.len() > 1 {
+        retain(&mut narrowed, &|module| {
+            symbol_maps.get(module.kind()).is_some_and(|symbol_map| {
+                symbol_map.for_address(address).is_some_and(|mut symbols| symbols.any(|(_, symbol)| !symbol.ambiguous))
+            })
+        });
+    }
+
+    if narrowed.len() < candidates.len() {
+        log::debug!(
+            "Narrowed down reference from {} to {:#010x} from {} to {} candidates",
+            source_kind,
+            pointer,
+            candidates.len(),
+            narrowed.len()
+        );
+    }
+    narrowed
+}
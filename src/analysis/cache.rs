@@ -0,0 +1,84 @@
+use std::{
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use fxhash::FxHasher64;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{hints::Hints, symbol::SymbolMap},
+    util::io::{create_dir_all, create_file, open_file},
+};
+
+use super::functions::Function;
+
+/// Version of the function analysis. Bump this whenever a change to the analysis would parse any function differently, so
+/// that caches from older versions are discarded.
+pub const ANALYSIS_CACHE_VERSION: u32 = 3;
+
+/// Functions parsed from a module, saved to disk so that commands can skip reparsing them if the module hasn't changed.
+#[derive(Serialize, Deserialize)]
+pub struct AnalysisCache {
+    /// Version of the analysis, see [`ANALYSIS_CACHE_VERSION`]. Caches from before it existed have version 0.
+    #[serde(default)]
+    analysis_version: u32,
+    /// Hash of the module code and function symbols
+    key: u64,
+    functions: Vec<Function>,
+}
+
+impl AnalysisCache {
+    /// Returns the path to the cache file of a module in the given directory.
+    pub fn path<P: AsRef<Path>>(dir: P, module_name: &str) -> PathBuf {
+        dir.as_ref().join(format!("{module_name}.yaml"))
+    }
+
+    /// Computes the cache key of a module. Function analysis only depends on the module code, the module bounds, the hints
+    /// and the function symbols, so changing other symbols will not invalidate the cache.
+    pub fn key(code: &[u8], base_address: u32, end_address: u32, symbol_map: &SymbolMap, hints: &Hints) -> u64 {
+        let mut hasher = FxHasher64::default();
+        code.hash(&mut hasher);
+        base_address.hash(&mut hasher);
+        end_address.hash(&mut hasher);
+        hints.hash(&mut hasher);
+        for (_, symbol) in symbol_map.functions() {
+            symbol.to_string().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Loads the cached functions if the cache file exists and matches the key.
+    pub fn load<P: AsRef<Path>>(path: P, key: u64) -> Option<Vec<Function>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return None;
+        }
+
+        let cache: AnalysisCache = match Self::read(path) {
+            Ok(cache) => cache,
+            Err(e) => {
+                log::warn!("Failed to read analysis cache '{}', reanalyzing functions: {e}", path.display());
+                return None;
+            }
+        };
+        if cache.analysis_version != ANALYSIS_CACHE_VERSION || cache.key != key {
+            log::debug!("Analysis cache '{}' is outdated", path.display());
+            return None;
+        }
+        Some(cache.functions)
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        Ok(serde_yml::from_reader(open_file(path)?)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(path: P, key: u64, functions: &[Function]) -> Result<()> {
+        let path = path.as_ref();
+        create_dir_all(path.parent().unwrap())?;
+        let cache = AnalysisCache { analysis_version: ANALYSIS_CACHE_VERSION, key, functions: functions.to_vec() };
+        serde_yml::to_writer(create_file(path)?, &cache)?;
+        Ok(())
+    }
+}
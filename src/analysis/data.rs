@@ -1,14 +1,18 @@
+use std::iter;
+
 use anyhow::{bail, Result};
 use bon::builder;
 
 use crate::config::{
+    hints::Hints,
     module::{Module, ModuleKind},
+    program::AnalysisOptions,
     relocation::{Relocation, RelocationModule, Relocations},
     section::{Section, SectionKind, Sections},
-    symbol::{SymBss, SymData, SymbolMap, SymbolMaps},
+    symbol::{SymBss, SymData, SymVeneer, SymbolMap, SymbolMaps},
 };
 
-use super::functions::Function;
+use super::functions::{CalledFunction, Function};
 
 #[builder]
 pub fn find_local_data_from_pools(
@@ -20,8 +24,12 @@ pub fn find_local_data_from_pools(
     name_prefix: &str,
     module_code: &[u8],
     base_address: u32,
+    hints: &Hints,
 ) -> Result<()> {
     for pool_constant in function.iter_pool_constants(module_code, base_address) {
+        if hints.is_not_pointer(pool_constant.address) {
+            continue;
+        }
         let pointer = pool_constant.value;
         let Some((_, section)) = sections.get_by_contained_address(pointer) else {
             // Not a pointer, or points to a different module
@@ -46,20 +54,50 @@ pub fn find_local_data_from_pools(
     Ok(())
 }
 
-pub fn find_local_data_from_section(
+/// Finds addresses computed with `add rX, pc, #imm` which lead outside the function, and must therefore be relocated.
+/// There is no equivalent search for `abs16` and `abs8` relocations, as no address of a module fits in 16 bits.
+pub fn find_local_data_from_pc_relative_adds(
+    function: &Function,
     sections: &Sections,
-    section: &Section,
-    code: &[u8],
     module_kind: ModuleKind,
     symbol_map: &mut SymbolMap,
     relocations: &mut Relocations,
     name_prefix: &str,
 ) -> Result<()> {
-    find_pointers(sections, section, code, module_kind, symbol_map, relocations, name_prefix)?;
+    for (&address, &destination) in function.data_loads() {
+        if destination >= function.start_address() && destination < function.end_address() {
+            // Moves along with the function
+            continue;
+        }
+        let Some((_, section)) = sections.get_by_contained_address(destination) else {
+            continue;
+        };
+
+        let name = format!("{}{:08x}", name_prefix, destination);
+        match section.kind() {
+            SectionKind::Code => {
+                if symbol_map.get_function(destination)?.is_none() {
+                    log::warn!(
+                        "PC-relative address from 0x{address:08x} in {module_kind} to 0x{destination:08x} leads to no function"
+                    );
+                    continue;
+                }
+            }
+            SectionKind::Data | SectionKind::Exception | SectionKind::ExceptionIndex => {
+                symbol_map.add_data(Some(name), destination, SymData::Any)?;
+            }
+            SectionKind::Bss => {
+                symbol_map.add_bss(Some(name), destination, SymBss { size: None })?;
+            }
+        }
+        relocations.add(Relocation::new_pc_relative(address, destination, module_kind.try_into()?, function.is_thumb()))?;
+    }
+
     Ok(())
 }
 
-fn find_pointers(
+#[builder]
+pub fn find_local_data_from_section(
     sections: &Sections,
     section: &Section,
     code: &[u8],
@@ -67,8 +105,12 @@ fn find_pointers(
     symbol_map: &mut SymbolMap,
     relocations: &mut Relocations,
     name_prefix: &str,
+    hints: &Hints,
 ) -> Result<()> {
     for word in section.iter_words(code) {
+        if hints.is_not_pointer(word.address) {
+            continue;
+        }
         let pointer = word.value;
         let Some((_, section)) = sections.get_by_contained_address(pointer) else {
             continue;
@@ -95,7 +137,7 @@ fn add_symbol_from_pointer(
                 relocations.add_load(address, pointer, 0, module_kind.try_into()?)?;
             }
         }
-        SectionKind::Data => {
+        SectionKind::Data | SectionKind::Exception | SectionKind::ExceptionIndex => {
             symbol_map.add_data(Some(name), pointer, SymData::Any)?;
             relocations.add_load(address, pointer, 0, module_kind.try_into()?)?;
         }
@@ -112,9 +154,10 @@ pub fn analyze_external_references(
     modules: &[Module],
     module_index: usize,
     symbol_maps: &mut SymbolMaps,
+    options: &AnalysisOptions,
 ) -> Result<RelocationResult> {
     let mut result = RelocationResult::new();
-    find_relocations_in_functions(modules, module_index, symbol_maps, &mut result)?;
+    find_relocations_in_functions(modules, module_index, symbol_maps, options, &mut result)?;
     find_external_references_in_sections(modules, module_index, &mut result)?;
     Ok(result)
 }
@@ -122,7 +165,7 @@ pub fn analyze_external_references(
 fn find_external_references_in_sections(modules: &[Module], module_index: usize, result: &mut RelocationResult) -> Result<()> {
     for section in modules[module_index].sections().iter() {
         match section.kind() {
-            SectionKind::Data => {}
+            SectionKind::Data | SectionKind::Exception | SectionKind::ExceptionIndex => {}
             SectionKind::Code | SectionKind::Bss => continue,
         }
 
@@ -138,11 +181,12 @@ fn find_relocations_in_functions(
     modules: &[Module],
     module_index: usize,
     symbol_maps: &mut SymbolMaps,
+    options: &AnalysisOptions,
     result: &mut RelocationResult,
 ) -> Result<()> {
     for section in modules[module_index].sections().iter() {
         for function in section.functions().values() {
-            add_function_calls_as_relocations(modules, module_index, function, symbol_maps, result)?;
+            add_function_calls_as_relocations(modules, module_index, function, symbol_maps, options, result)?;
             find_external_data_from_pools(modules, module_index, function, result)?;
         }
     }
@@ -154,14 +198,33 @@ fn add_function_calls_as_relocations(
     module_index: usize,
     function: &Function,
     symbol_maps: &mut SymbolMaps,
+    options: &AnalysisOptions,
     result: &mut RelocationResult,
 ) -> Result<()> {
     for (&address, &called_function) in function.function_calls() {
-        if called_function.ins.is_conditional() {
-            // Dumb mwld linker bug removes the condition code from relocated call instructions
+        if called_function.conditional && (!options.conditional_calls || function.is_thumb()) {
+            // Dumb mwld linker bug removes the condition code from relocated call and branch instructions. ARM instructions
+            // can opt in to be relocated anyway, and `rom config` will restore the condition code after linking.
             continue;
         }
 
+        // Veneers are generated again by the linker, so calls to them are relocated to their targets instead
+        let called_function = match find_veneer(modules, module_index, symbol_maps, called_function.address)? {
+            Some(veneer) if called_function.branch && veneer.target_thumb() != function.is_thumb() => {
+                log::warn!(
+                    "Branch from 0x{:08x} in {} to veneer at 0x{:08x} changes instruction mode, it can't be relocated to the target",
+                    address,
+                    modules[module_index].kind(),
+                    called_function.address
+                );
+                called_function
+            }
+            Some(veneer) => {
+                CalledFunction { address: veneer.target_address(), thumb: veneer.target_thumb(), ..called_function }
+            }
+            None => called_function,
+        };
+
         let local_module = &modules[module_index];
         let is_local = local_module.sections().get_by_contained_address(called_function.address).is_some();
 
@@ -204,17 +267,34 @@ fn add_function_calls_as_relocations(
             );
         }
 
-        result.relocations.push(Relocation::new_call(
-            address,
-            called_function.address,
-            module,
-            function.is_thumb(),
-            called_function.thumb,
-        ));
+        let relocation = if called_function.branch {
+            Relocation::new_branch(address, called_function.address, module, function.is_thumb())
+        } else {
+            Relocation::new_call(address, called_function.address, module, function.is_thumb(), called_function.thumb)
+        };
+        result.relocations.push(relocation);
     }
     Ok(())
 }
 
+/// Returns the veneer at the given address, either in the local module or in a module which is always loaded.
+fn find_veneer(modules: &[Module], module_index: usize, symbol_maps: &SymbolMaps, address: u32) -> Result<Option<SymVeneer>> {
+    let local_kind = modules[module_index].kind();
+    let kinds = iter::once(local_kind).chain(
+        modules
+            .iter()
+            .map(|module| module.kind())
+            .filter(|&kind| kind != local_kind && !matches!(kind, ModuleKind::Overlay(_))),
+    );
+    for kind in kinds {
+        let Some(symbol_map) = symbol_maps.get(kind) else { continue };
+        if let Some((veneer, _)) = symbol_map.get_veneer(address)? {
+            return Ok(Some(veneer));
+        }
+    }
+    Ok(None)
+}
+
 fn find_external_data_from_pools<'a>(
     modules: &[Module<'a>],
     module_index: usize,
@@ -236,6 +316,9 @@ fn find_external_data(
     result: &mut RelocationResult,
 ) -> Result<()> {
     let local_module = &modules[module_index];
+    if local_module.hints().is_not_pointer(address) {
+        return Ok(());
+    }
     let is_local = local_module.sections().get_by_contained_address(pointer).is_some();
     if is_local {
         return Ok(());
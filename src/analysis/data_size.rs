@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use unarm::{
+    args::{Argument, OffsetImm, OffsetReg, Reg, Register},
+    Ins, ParsedIns,
+};
+
+use crate::config::{
+    module::{Module, ModuleKind},
+    section::SectionKind,
+    symbol::{SymBss, SymData, SymbolKind, SymbolMaps},
+};
+
+use super::functions::Function;
+
+/// Infers the sizes of data and BSS symbols which have no size, from how the code accesses them. A size is only inferred
+/// if every reference to the symbol is a pointer load which the code then only dereferences with immediate offsets, and
+/// if the rest of the space up to the next symbol can only be alignment padding.
+///
+/// Overlays share address ranges, so accesses are tracked per pool constant in the module that loads it, and each
+/// symbol's references come from the relocations which lead to its module.
+pub fn infer_data_sizes(modules: &[Module], symbol_maps: &mut SymbolMaps) -> Result<()> {
+    let mut accesses = DataAccesses::default();
+    for module in modules {
+        for section in module.sections().iter() {
+            for function in section.functions().values() {
+                accesses.analyze_function(function, module.kind(), module.code(), module.base_address());
+            }
+        }
+    }
+
+    let mut references = BTreeMap::<(ModuleKind, u32), Vec<PoolConstant>>::new();
+    for module in modules {
+        for relocation in module.relocations().iter() {
+            let Some(first_module) = relocation.module().first_module() else { continue };
+            // An ambiguous reference counts for every overlay it may lead to
+            let targets = std::iter::once(first_module).chain(relocation.module().other_modules().into_iter().flatten());
+            for target in targets {
+                references
+                    .entry((target, relocation.to_address()))
+                    .or_default()
+                    .push((module.kind(), relocation.from_address()));
+            }
+        }
+    }
+
+    let mut num_inferred = 0;
+    for module in modules {
+        let symbol_map = symbol_maps.get_mut(module.kind());
+        for section in module.sections().iter() {
+            let is_bss = section.kind() == SectionKind::Bss;
+            let candidates = symbol_map
+                .iter_by_address(section.address_range())
+                .filter(|symbol| symbol.size.is_none())
+                .filter(|symbol| {
+                    matches!(symbol.kind, SymbolKind::Data(SymData::Any) | SymbolKind::Bss(SymBss { size: None }))
+                })
+                .map(|symbol| symbol.addr)
+                .collect::<Vec<_>>();
+
+            for address in candidates {
+                let next_address = symbol_map
+                    .iter_by_address(address + 1..section.end_address())
+                    .next()
+                    .map(|symbol| symbol.addr)
+                    .unwrap_or(section.end_address());
+
+                let Some(size) = accesses.size(references.get(&(module.kind(), address))) else { continue };
+                if size > next_address - address {
+                    log::debug!("Data at {address:#010x} in {} is accessed beyond the next symbol", module.kind());
+                    continue;
+                }
+                let padding = (address + size)..next_address;
+                if padding.end % 4 != 0 || padding.len() >= 4 {
+                    continue;
+                }
+                if !is_bss {
+                    let start = (padding.start - module.base_address()) as usize;
+                    let end = (padding.end - module.base_address()) as usize;
+                    if !module.code().get(start..end).is_some_and(|bytes| bytes.iter().all(|&byte| byte == 0)) {
+                        continue;
+                    }
+                }
+
+                let Some((index, _)) = symbol_map.by_address(address)? else { continue };
+                symbol_map.set_size(index, Some(size));
+                num_inferred += 1;
+            }
+        }
+    }
+    log::debug!("Inferred the size of {num_inferred} data symbols");
+
+    Ok(())
+}
+
+/// A pool constant holding a pointer, by the module it's in and its address.
+type PoolConstant = (ModuleKind, u32);
+
+#[derive(Default)]
+struct DataAccesses {
+    /// Maps a pointer, by the pool constant it was loaded from, to how far past it the code reads or writes
+    ends: BTreeMap<PoolConstant, u32>,
+    /// Pointers which are used for anything other than dereferencing with an immediate offset
+    escaped: BTreeSet<PoolConstant>,
+    /// Pool constants whose loads were followed
+    analyzed_pools: BTreeSet<PoolConstant>,
+}
+
+impl DataAccesses {
+    /// Returns the size of a symbol from the accesses through all of its references, if they were all followed.
+    fn size(&self, references: Option<&Vec<PoolConstant>>) -> Option<u32> {
+        let references = references?;
+        if references.iter().any(|pool| self.escaped.contains(pool) || !self.analyzed_pools.contains(pool)) {
+            return None;
+        }
+        references.iter().filter_map(|pool| self.ends.get(pool).copied()).max()
+    }
+
+    fn access(&mut self, pointer: PoolConstant, end: u32) {
+        let current = self.ends.entry(pointer).or_default();
+        *current = (*current).max(end);
+    }
+
+    fn escape(&mut self, pointer: Option<PoolConstant>) {
+        if let Some(pointer) = pointer {
+            self.escaped.insert(pointer);
+        }
+    }
+
+    fn analyze_function(&mut self, function: &Function, module_kind: ModuleKind, module_code: &[u8], base_address: u32) {
+        let mut pointers = RegisterPointers::default();
+        let labels = function.labels().copied().collect::<BTreeSet<_>>();
+
+        for (address, ins, parsed_ins) in function.parser(module_code, base_address) {
+            if Self::is_data(function, address) {
+                pointers.clear();
+                continue;
+            }
+            if labels.contains(&address) {
+                // Control flow merges here, so any pointer might be used by code we didn't follow
+                for (_, pointer) in pointers.take_all() {
+                    self.escape(Some(pointer));
+                }
+            }
+            if let Some(pool_address) = Function::is_pool_load(ins, &parsed_ins, address, function.is_thumb()) {
+                let Argument::Reg(Reg { reg: dest, .. }) = parsed_ins.args[0] else { continue };
+                let previous = pointers.take(dest);
+                if ins.is_conditional() {
+                    self.escape(previous);
+                }
+                let start = (pool_address - base_address) as usize;
+                if module_code.get(start..start + 4).is_some() {
+                    let pool = (module_kind, pool_address);
+                    self.analyzed_pools.insert(pool);
+                    pointers.set(dest, pool);
+                }
+                continue;
+            }
+            self.handle_ins(&mut pointers, function, ins, &parsed_ins, address);
+        }
+        for (_, pointer) in pointers.take_all() {
+            // Still live when the function ends, e.g. at a tail call
+            self.escape(Some(pointer));
+        }
+    }
+
+    fn is_data(function: &Function, address: u32) -> bool {
+        function.pool_constants().range(..=address).next_back().is_some_and(|&pool_constant| address < pool_constant + 4)
+            || function.get_inline_table_at(address).is_some()
+            || function
+                .jump_tables()
+                .any(|table| !table.code && address >= table.address && address < table.address + table.size)
+    }
+
+    fn handle_ins(
+        &mut self,
+        pointers: &mut RegisterPointers,
+        function: &Function,
+        ins: Ins,
+        parsed_ins: &ParsedIns,
+        address: u32,
+    ) {
+        let args = &parsed_ins.args;
+        let mnemonic = ins.mnemonic();
+
+        if let Some(width) = Self::access_width(mnemonic) {
+            if let (Argument::Reg(value), Argument::Reg(base @ Reg { deref: true, .. })) = (args[0], args[1]) {
+                let offset = match args[2] {
+                    Argument::None if !base.writeback => Some(0),
+                    Argument::OffsetImm(OffsetImm { post_indexed: false, value }) if !base.writeback => {
+                        u32::try_from(value).ok()
+                    }
+                    _ => None,
+                };
+                if let Argument::OffsetReg(OffsetReg { reg, .. }) = args[2] {
+                    // The pointer may be the offset, e.g. `ldr r0, [r1, r2]` where r1 is an index
+                    self.escape(pointers.get(reg));
+                }
+                match (pointers.get(base.reg), offset) {
+                    (Some(pointer), Some(offset)) => self.access(pointer, offset + width),
+                    (pointer, _) => self.escape(pointer),
+                }
+                if base.writeback {
+                    pointers.take(base.reg);
+                }
+                if mnemonic.starts_with("str") {
+                    self.escape(pointers.get(value.reg));
+                } else {
+                    let previous = pointers.take(value.reg);
+                    if ins.is_conditional() {
+                        self.escape(previous);
+                    }
+                }
+                return;
+            }
+        }
+
+        if Self::is_return(mnemonic, parsed_ins) {
+            // A pointer in r0 is returned to the caller, the other registers are dead
+            self.escape(pointers.take(Register::R0));
+            if !ins.is_conditional() {
+                pointers.clear();
+            }
+            return;
+        }
+        if let ("b", Some(offset)) = (mnemonic, parsed_ins.branch_destination()) {
+            let destination = (address as i32 + offset) as u32;
+            if destination >= function.start_address() && destination < function.end_address() {
+                // The pointers may be used after the branch destination
+                for (_, pointer) in pointers.take_all() {
+                    self.escape(Some(pointer));
+                }
+            } else {
+                // Tail call
+                self.clobber_call_registers(pointers);
+            }
+            if !ins.is_conditional() {
+                pointers.clear();
+            }
+            return;
+        }
+        if matches!(mnemonic, "bl" | "blx" | "bx") {
+            self.clobber_call_registers(pointers);
+            return;
+        }
+
+        let overwrites_dest = matches!(mnemonic, "mov" | "mvn") && !ins.is_conditional();
+        for (i, arg) in args.iter().enumerate() {
+            match *arg {
+                Argument::Reg(Reg { reg, .. }) if i == 0 && overwrites_dest => {
+                    pointers.take(reg);
+                }
+                Argument::Reg(Reg { reg, .. }) | Argument::OffsetReg(OffsetReg { reg, .. }) => {
+                    // Any other use of a pointer could compute an address we can't follow
+                    self.escape(pointers.take(reg));
+                }
+                Argument::RegList(regs) => {
+                    for (_, pointer) in pointers.take_where(|reg| regs.contains(reg)) {
+                        if !mnemonic.starts_with("ldm") && mnemonic != "pop" {
+                            self.escape(Some(pointer));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Pointers in argument registers are passed to the called function, and caller-saved registers are clobbered.
+    fn clobber_call_registers(&mut self, pointers: &mut RegisterPointers) {
+        for reg in [Register::R0, Register::R1, Register::R2, Register::R3] {
+            self.escape(pointers.take(reg));
+        }
+        pointers.take(Register::R12);
+        pointers.take(Register::Lr);
+    }
+
+    fn is_return(mnemonic: &str, parsed_ins: &ParsedIns) -> bool {
+        let args = &parsed_ins.args;
+        match (mnemonic, args[0], args[1]) {
+            ("bx", Argument::Reg(Reg { reg: Register::Lr, .. }), _) => true,
+            ("mov", Argument::Reg(Reg { reg: Register::Pc, .. }), Argument::Reg(Reg { reg: Register::Lr, .. })) => true,
+            ("pop", Argument::RegList(regs), _) => regs.contains(Register::Pc),
+            (mnemonic, _, Argument::RegList(regs)) if mnemonic.starts_with("ldm") => regs.contains(Register::Pc),
+            _ => false,
+        }
+    }
+
+    fn access_width(mnemonic: &str) -> Option<u32> {
+        match mnemonic {
+            "ldr" | "str" => Some(4),
+            "ldrh" | "strh" | "ldrsh" => Some(2),
+            "ldrb" | "strb" | "ldrsb" => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// Registers holding a pointer loaded from a pool constant, tracked linearly through a function.
+#[derive(Default)]
+struct RegisterPointers {
+    pointers: Vec<(Register, PoolConstant)>,
+}
+
+impl RegisterPointers {
+    fn get(&self, reg: Register) -> Option<PoolConstant> {
+        self.pointers.iter().find(|(r, _)| *r == reg).map(|&(_, pointer)| pointer)
+    }
+
+    fn set(&mut self, reg: Register, pointer: PoolConstant) {
+        self.pointers.retain(|(r, _)| *r != reg);
+        self.pointers.push((reg, pointer));
+    }
+
+    fn take(&mut self, reg: Register) -> Option<PoolConstant> {
+        let pointer = self.get(reg);
+        self.pointers.retain(|(r, _)| *r != reg);
+        pointer
+    }
+
+    fn take_where<F: Fn(Register) -> bool>(&mut self, predicate: F) -> Vec<(Register, PoolConstant)> {
+        let (taken, kept) = self.pointers.drain(..).partition(|&(reg, _)| predicate(reg));
+        self.pointers = kept;
+        taken
+    }
+
+    fn take_all(&mut self) -> Vec<(Register, PoolConstant)> {
+        std::mem::take(&mut self.pointers)
+    }
+
+    fn clear(&mut self) {
+        self.pointers.clear();
+    }
+}
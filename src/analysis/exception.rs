@@ -0,0 +1,123 @@
+use std::ops::Range;
+
+use crate::util::bytes::FromSlice;
+
+/// Size of one entry in the exception index (`.exceptix`)
+pub const INDEX_ENTRY_SIZE: u32 = 12;
+
+/// The exception tables emitted by mwcc for C++ code with exceptions enabled. The linker places them right before `.ctor`:
+///
+/// ```text
+/// .exception  ; exception action tables, one per function
+/// .exceptix   ; sorted index of functions with an exception table, one entry per function
+/// .ctor
+/// ```
+pub struct ExceptionTables {
+    /// Address range of `.exception`
+    pub tables: Range<u32>,
+    /// Address range of `.exceptix`
+    pub index: Range<u32>,
+    pub entries: Vec<ExceptionIndexEntry>,
+}
+
+pub struct ExceptionIndexEntry {
+    /// Address of this entry in `.exceptix`
+    pub address: u32,
+    /// Function start, with the lowest bit set if it's a Thumb function
+    pub function: u32,
+    /// Function size shifted left by one, with the lowest bit set if the exception table is stored directly in the entry
+    pub size_and_flags: u32,
+    /// Address of the function's exception table in `.exception`
+    pub table: u32,
+}
+
+impl ExceptionTables {
+    /// Searches backwards from `end` for the exception index, and finds the exception tables it points to. `end` is
+    /// normally the start of `.ctor`. The functions must not overlap each other and must come before the tables, which
+    /// are stored in the same order as the index and end where the index starts.
+    pub fn find(code: &[u8], base_address: u32, end: u32) -> Option<Self> {
+        let mut entries = vec![];
+        let mut index_start = end;
+        while index_start >= base_address + INDEX_ENTRY_SIZE {
+            let address = index_start - INDEX_ENTRY_SIZE;
+            let Some(entry) = ExceptionIndexEntry::parse(code, base_address, address) else {
+                break;
+            };
+            if entries.last().is_some_and(|next: &ExceptionIndexEntry| next.function_address() <= entry.function_address()) {
+                // The index is sorted by function address
+                break;
+            }
+            entries.push(entry);
+            index_start = address;
+        }
+        if entries.is_empty() {
+            return None;
+        }
+        entries.reverse();
+
+        let tables_start = entries[0].table;
+        if entries.windows(2).any(|pair| pair[0].table > pair[1].table) || entries.last().unwrap().table >= index_start {
+            return None;
+        }
+        let mut functions_end = base_address;
+        for entry in &entries {
+            if entry.function_address() < functions_end {
+                return None;
+            }
+            functions_end = entry.function_range().end;
+        }
+        if functions_end > tables_start {
+            return None;
+        }
+
+        Some(Self { tables: tables_start..index_start, index: index_start..end, entries })
+    }
+
+    /// Returns the address range of each function's exception table, in the same order as the index entries.
+    pub fn table_ranges(&self) -> Vec<(&ExceptionIndexEntry, Range<u32>)> {
+        let mut table_starts = self.entries.iter().map(|entry| entry.table).collect::<Vec<_>>();
+        table_starts.sort_unstable();
+        table_starts.dedup();
+
+        self.entries
+            .iter()
+            .map(|entry| {
+                let next = table_starts.iter().find(|&&start| start > entry.table).copied().unwrap_or(self.tables.end);
+                (entry, entry.table..next)
+            })
+            .collect()
+    }
+}
+
+impl ExceptionIndexEntry {
+    fn parse(code: &[u8], base_address: u32, address: u32) -> Option<Self> {
+        let offset = (address - base_address) as usize;
+        let words = code.get(offset..offset + INDEX_ENTRY_SIZE as usize)?;
+        let function = u32::from_le_slice(&words[0..]);
+        let size_and_flags = u32::from_le_slice(&words[4..]);
+        let table = u32::from_le_slice(&words[8..]);
+
+        let thumb = (function & 1) != 0;
+        if !thumb && (function & 3) != 0 {
+            return None;
+        }
+        let function_end = (function & !1).checked_add(size_and_flags >> 1);
+        if function < base_address || (size_and_flags >> 1) == 0 || function_end.map_or(true, |end| end > address) {
+            return None;
+        }
+        if (table & 3) != 0 || table < base_address || table >= address {
+            return None;
+        }
+
+        Some(Self { address, function, size_and_flags, table })
+    }
+
+    pub fn function_address(&self) -> u32 {
+        self.function & !1
+    }
+
+    pub fn function_range(&self) -> Range<u32> {
+        let start = self.function_address();
+        start..start + (self.size_and_flags >> 1)
+    }
+}
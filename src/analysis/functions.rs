@@ -5,6 +5,7 @@ use std::{
 
 use anyhow::{bail, Result};
 use bon::bon;
+use serde::{Deserialize, Serialize};
 use unarm::{
     args::{Argument, Reg, Register},
     arm, thumb, ArmVersion, DisplayOptions, Endian, Ins, ParseFlags, ParseMode, ParsedIns, Parser, RegNames,
@@ -12,15 +13,19 @@ use unarm::{
 
 use crate::{
     analysis::function_start::is_valid_function_start,
-    config::symbol::{SymbolLookup, SymbolMap},
-    util::bytes::FromSlice,
+    config::{
+        hints::Hints,
+        symbol::{SymbolLookup, SymbolMap},
+    },
+    util::{bytes::FromSlice, demangle::demangle},
 };
 
 use super::{
     function_branch::FunctionBranchState,
-    inline_table::{InlineTable, InlineTableState},
+    inline_table::{InlineTable, InlineTableKind, InlineTableState},
     jump_table::{JumpTable, JumpTableState},
     secure_area::SecureAreaState,
+    veneer::Veneer,
 };
 
 // All keys in the types below are instruction addresses
@@ -31,7 +36,7 @@ pub type InlineTables = BTreeMap<u32, InlineTable>;
 pub type FunctionCalls = BTreeMap<u32, CalledFunction>;
 pub type DataLoads = BTreeMap<u32, u32>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Function {
     name: String,
     start_address: u32,
@@ -43,6 +48,8 @@ pub struct Function {
     jump_tables: JumpTables,
     inline_tables: InlineTables,
     function_calls: FunctionCalls,
+    /// Addresses computed relative to the PC with `add rX, pc, #imm`
+    data_loads: DataLoads,
 }
 
 #[bon]
@@ -130,7 +137,7 @@ impl Function {
         Some((address as i32 + dest).try_into().unwrap())
     }
 
-    fn is_pool_load(ins: Ins, parsed_ins: &ParsedIns, address: u32, thumb: bool) -> Option<u32> {
+    pub fn is_pool_load(ins: Ins, parsed_ins: &ParsedIns, address: u32, thumb: bool) -> Option<u32> {
         if ins.mnemonic() != "ldr" {
             return None;
         }
@@ -153,17 +160,35 @@ impl Function {
         }
     }
 
+    fn is_pc_relative_add(parsed_ins: &ParsedIns, address: u32, thumb: bool) -> Option<u32> {
+        let args = &parsed_ins.args;
+        match (parsed_ins.mnemonic, args[0], args[1], args[2], args[3]) {
+            // add *, pc, #*
+            (
+                "add",
+                Argument::Reg(Reg { reg: dest, .. }),
+                Argument::Reg(Reg { reg: Register::Pc, deref: false, .. }),
+                Argument::UImm(offset),
+                Argument::None,
+            ) if dest != Register::Pc => {
+                let pc = if thumb { (address + 4) & !3 } else { address + 8 };
+                Some(pc + offset)
+            }
+            _ => None,
+        }
+    }
+
     fn is_function_call(ins: Ins, parsed_ins: &ParsedIns, address: u32, thumb: bool) -> Option<CalledFunction> {
         let args = &parsed_ins.args;
         match (ins.mnemonic(), args[0], args[1]) {
             ("bl", Argument::BranchDest(offset), Argument::None) => {
                 let destination = (address as i32 + offset) as u32;
-                Some(CalledFunction { ins, address: destination, thumb })
+                Some(CalledFunction { conditional: ins.is_conditional(), address: destination, thumb, branch: false })
             }
             ("blx", Argument::BranchDest(offset), Argument::None) => {
                 let destination = (address as i32 + offset) as u32;
                 let destination = if thumb { destination & !3 } else { destination };
-                Some(CalledFunction { ins, address: destination, thumb: !thumb })
+                Some(CalledFunction { conditional: ins.is_conditional(), address: destination, thumb: !thumb, branch: false })
             }
             _ => None,
         }
@@ -178,12 +203,24 @@ impl Function {
         known_end_address: Option<u32>,
         module_start_address: u32,
         module_end_address: u32,
+        hints: Option<&'a Hints>,
+        function_starts: Option<&'a BTreeSet<u32>>,
+        first_instruction_offset: Option<u32>,
     ) -> Result<ParseFunctionResult> {
-        let mut context =
-            ParseFunctionContext::new(start_address, thumb, known_end_address, module_start_address, module_end_address);
+        let mut context = ParseFunctionContext::new(
+            start_address,
+            thumb,
+            known_end_address,
+            module_start_address,
+            module_end_address,
+            hints,
+            function_starts,
+        );
+        context.own_function_start = start_address + first_instruction_offset.unwrap_or(0);
 
         let Some((address, ins, parsed_ins)) = parser.next() else { return Ok(ParseFunctionResult::NoEpilogue) };
-        if !is_valid_function_start(address, ins, &parsed_ins) {
+        let forced_start = hints.map_or(false, |hints| hints.is_function_start(start_address));
+        if !forced_start && !is_valid_function_start(address, ins, &parsed_ins) {
             return Ok(ParseFunctionResult::InvalidStart { address, ins, parsed_ins });
         }
 
@@ -234,8 +271,15 @@ impl Function {
         known_end_address: Option<u32>,
         module_start_address: u32,
         module_end_address: u32,
+        hints: Option<&Hints>,
+        function_starts: Option<&BTreeSet<u32>>,
     ) -> Result<ParseFunctionResult> {
-        let thumb = options.thumb.unwrap_or(Function::is_thumb_function(start_address, module_code));
+        let function_hint = hints.and_then(|hints| hints.function(start_address));
+        let known_end_address = known_end_address.or(function_hint.and_then(|hint| hint.end));
+        let thumb = options
+            .thumb
+            .or(function_hint.and_then(|hint| hint.thumb))
+            .unwrap_or(Function::is_thumb_function(start_address, module_code));
         let parse_mode = if thumb { ParseMode::Thumb } else { ParseMode::Arm };
         let offset = first_instruction_offset.unwrap_or(0);
         let start = (start_address - base_address + offset) as usize;
@@ -256,6 +300,9 @@ impl Function {
             .maybe_known_end_address(known_end_address)
             .module_start_address(module_start_address)
             .module_end_address(module_end_address)
+            .maybe_hints(hints)
+            .maybe_function_starts(function_starts)
+            .maybe_first_instruction_offset(first_instruction_offset)
             .call()
     }
 
@@ -268,6 +315,7 @@ impl Function {
         options: Option<ParseFunctionOptions>,
         module_start_address: u32,
         module_end_address: u32,
+        hints: Option<&Hints>,
     ) -> Result<ParseFunctionResult> {
         Self::run_function_parser_loop()
             .name(name)
@@ -277,6 +325,7 @@ impl Function {
             .base_address(base_address)
             .module_start_address(module_start_address)
             .module_end_address(module_end_address)
+            .maybe_hints(hints)
             .call()
     }
 
@@ -290,6 +339,9 @@ impl Function {
         options: ParseFunctionOptions,
         module_start_address: u32,
         module_end_address: u32,
+        hints: Option<&Hints>,
+        /// Start addresses of all known functions, branches to these are treated as tail calls
+        function_starts: Option<&BTreeSet<u32>>,
     ) -> Result<ParseFunctionResult> {
         Self::run_function_parser_loop()
             .name(name)
@@ -301,6 +353,8 @@ impl Function {
             .base_address(start_address)
             .module_start_address(module_start_address)
             .module_end_address(module_end_address)
+            .maybe_hints(hints)
+            .maybe_function_starts(function_starts)
             .call()
     }
 
@@ -313,6 +367,100 @@ impl Function {
         options: FindFunctionsOptions,
         module_start_address: u32,
         module_end_address: u32,
+        hints: Option<&Hints>,
+    ) -> Result<BTreeMap<u32, Function>> {
+        let mut function_starts: BTreeSet<u32> =
+            symbol_map.functions().map(|(function, symbol)| symbol.addr + function.offset).collect();
+        let initial_symbol_map = symbol_map.clone();
+
+        let functions = Self::find_functions_pass()
+            .module_code(module_code)
+            .base_addr(base_addr)
+            .default_name_prefix(default_name_prefix)
+            .symbol_map(symbol_map)
+            .options(&options)
+            .module_start_address(module_start_address)
+            .module_end_address(module_end_address)
+            .maybe_hints(hints)
+            .function_starts(&function_starts)
+            .call()?;
+
+        // A branch to a function which hadn't been found yet was mistaken for a local branch, so search again now that all
+        // function starts are known
+        let found_starts = Self::found_function_starts(&functions, module_code, base_addr);
+        let needs_second_pass = functions.values().any(|function| {
+            function.labels.iter().any(|&label| label != function.first_instruction_address && found_starts.contains(&label))
+                || found_starts.range(function.first_instruction_address + 1..function.end_address).next().is_some()
+        });
+        if !needs_second_pass {
+            return Ok(functions);
+        }
+
+        log::debug!("Searching for functions again with {} known function starts", found_starts.len());
+        *symbol_map = initial_symbol_map;
+        function_starts.extend(found_starts);
+        Self::find_functions_pass()
+            .module_code(module_code)
+            .base_addr(base_addr)
+            .default_name_prefix(default_name_prefix)
+            .symbol_map(symbol_map)
+            .options(&options)
+            .module_start_address(module_start_address)
+            .module_end_address(module_end_address)
+            .maybe_hints(hints)
+            .function_starts(&function_starts)
+            .call()
+    }
+
+    /// Returns the start addresses of found functions, functions called by them and function pointers in their pools.
+    fn found_function_starts(functions: &BTreeMap<u32, Function>, module_code: &[u8], base_addr: u32) -> BTreeSet<u32> {
+        let end_addr = base_addr + module_code.len() as u32;
+        let mut starts = BTreeSet::new();
+        for function in functions.values() {
+            starts.insert(function.first_instruction_address);
+            starts.extend(
+                function
+                    .function_calls
+                    .values()
+                    .map(|called_function| called_function.address)
+                    .filter(|&address| address >= base_addr && address < end_addr),
+            );
+            for pool_constant in function.iter_pool_constants(module_code, base_addr) {
+                let address = pool_constant.value & !1;
+                if address < base_addr || address >= end_addr {
+                    continue;
+                }
+                let thumb = (pool_constant.value & 1) != 0;
+                if !thumb && (address & 3) != 0 {
+                    continue;
+                }
+                let mut parser = Parser::new(
+                    if thumb { ParseMode::Thumb } else { ParseMode::Arm },
+                    address,
+                    Endian::Little,
+                    ParseFlags { ual: false, version: ArmVersion::V5Te },
+                    &module_code[(address - base_addr) as usize..],
+                );
+                let Some((address, ins, parsed_ins)) = parser.next() else { continue };
+                if is_valid_function_start(address, ins, &parsed_ins) {
+                    starts.insert(address);
+                }
+            }
+        }
+        starts
+    }
+
+    #[builder]
+    fn find_functions_pass(
+        module_code: &[u8],
+        base_addr: u32,
+        default_name_prefix: &str,
+        symbol_map: &mut SymbolMap,
+        options: &FindFunctionsOptions,
+        module_start_address: u32,
+        module_end_address: u32,
+        hints: Option<&Hints>,
+        function_starts: &BTreeSet<u32>,
     ) -> Result<BTreeMap<u32, Function>> {
         let mut functions = BTreeMap::new();
 
@@ -329,7 +477,20 @@ impl Function {
         let mut address = start_address;
 
         while !function_code.is_empty() && address <= last_function_address {
-            let thumb = Function::is_thumb_function(address, function_code);
+            let function_hint = hints.and_then(|hints| hints.function(address));
+            let veneer = if function_hint.is_none() { Veneer::parse(function_code, address) } else { None };
+            if let Some(veneer) = veneer {
+                if symbol_map.get_function(address)?.is_none() {
+                    // Generated by the linker, so it's not added as a function
+                    symbol_map.add_veneer(address, veneer.into())?;
+                    address += veneer.size;
+                    function_code = &module_code[(address - base_addr) as usize..];
+                    continue;
+                }
+            }
+            let thumb = function_hint
+                .and_then(|hint| hint.thumb)
+                .unwrap_or_else(|| Function::is_thumb_function(address, function_code));
 
             let parse_mode = if thumb { ParseMode::Thumb } else { ParseMode::Arm };
             let parser = Parser::new(
@@ -351,8 +512,11 @@ impl Function {
                 .start_address(address)
                 .thumb(thumb)
                 .parser(parser)
+                .maybe_known_end_address(function_hint.and_then(|hint| hint.end))
                 .module_start_address(module_start_address)
                 .module_end_address(module_end_address)
+                .maybe_hints(hints)
+                .function_starts(function_starts)
                 .call()?;
             let function = match function_result {
                 ParseFunctionResult::Found(function) => function,
@@ -441,6 +605,11 @@ impl Function {
                     if pointer_value >= last_function_address {
                         continue;
                     }
+                    if let Some(hints) = hints {
+                        if hints.is_not_pointer(pool_constant.address) || hints.is_function_start(pointer_value) {
+                            continue;
+                        }
+                    }
                     if pointer_value >= start_address {
                         let offset = (pointer_value - base_addr) as usize;
                         if offset < module_code.len() {
@@ -518,6 +687,7 @@ impl Function {
                     jump_tables: JumpTables::new(),
                     inline_tables: InlineTables::new(),
                     function_calls: FunctionCalls::new(),
+                    data_loads: DataLoads::new(),
                 };
                 symbol_map.add_function(&function);
                 functions.insert(function.start_address, function);
@@ -603,6 +773,10 @@ impl Function {
         &self.function_calls
     }
 
+    pub fn data_loads(&self) -> &DataLoads {
+        &self.data_loads
+    }
+
     pub fn write_assembly<W: io::Write>(
         &self,
         w: &mut W,
@@ -620,6 +794,9 @@ impl Function {
         );
 
         // declare self
+        if let Some(demangled) = demangle(&self.name) {
+            writeln!(w, "    ; {demangled}")?;
+        }
         writeln!(w, "    .global {}", self.name)?;
         if self.thumb {
             writeln!(w, "    thumb_func_start {}", self.name)?;
@@ -644,7 +821,7 @@ impl Function {
 
             // write data
             if let Some((data, sym)) = symbols.symbol_map.get_data(address)? {
-                let Some(size) = data.size() else {
+                let Some(size) = data.size(symbols.types) else {
                     log::error!("Inline tables must have a known size");
                     bail!("Inline tables must have a known size");
                 };
@@ -742,7 +919,7 @@ impl Function {
     }
 }
 
-struct ParseFunctionContext {
+struct ParseFunctionContext<'a> {
     start_address: u32,
     thumb: bool,
     end_address: Option<u32>,
@@ -752,6 +929,7 @@ struct ParseFunctionContext {
     jump_tables: JumpTables,
     inline_tables: InlineTables,
     function_calls: FunctionCalls,
+    data_loads: DataLoads,
 
     module_start_address: u32,
     module_end_address: u32,
@@ -762,21 +940,30 @@ struct ParseFunctionContext {
     last_pool_address: Option<u32>,
     /// State machine for detecting jump tables and adding them as symbols
     jump_table_state: JumpTableState,
-    /// State machine for detecting branches (B, not BL) to other functions
+    /// State machine for detecting branches (B, not BL) to functions which aren't known yet
     function_branch_state: FunctionBranchState,
     /// State machine for detecting inline data tables within the function
     inline_table_state: InlineTableState,
 
     prev_ins: Option<Ins>,
+
+    /// User-supplied hints which take precedence over the heuristics above
+    hints: Option<&'a Hints>,
+    /// Start addresses of known functions, used to detect branches (B, not BL) to other functions
+    function_starts: Option<&'a BTreeSet<u32>>,
+    /// This function's own entry in `function_starts`, which is its first instruction after any pre-code pool
+    own_function_start: u32,
 }
 
-impl ParseFunctionContext {
+impl<'a> ParseFunctionContext<'a> {
     pub fn new(
         start_address: u32,
         thumb: bool,
         known_end_address: Option<u32>,
         module_start_address: u32,
         module_end_address: u32,
+        hints: Option<&'a Hints>,
+        function_starts: Option<&'a BTreeSet<u32>>,
     ) -> Self {
         Self {
             start_address,
@@ -788,6 +975,7 @@ impl ParseFunctionContext {
             jump_tables: JumpTables::new(),
             inline_tables: InlineTables::new(),
             function_calls: FunctionCalls::new(),
+            data_loads: DataLoads::new(),
 
             module_start_address,
             module_end_address,
@@ -803,6 +991,10 @@ impl ParseFunctionContext {
             inline_table_state: Default::default(),
 
             prev_ins: None,
+
+            hints,
+            function_starts,
+            own_function_start: start_address,
         }
     }
 
@@ -832,7 +1024,19 @@ impl ParseFunctionContext {
             return ParseFunctionState::Continue;
         }
 
+        if let Some(state) = self.handle_hints(parser, address) {
+            return state;
+        }
+
         self.jump_table_state = self.jump_table_state.handle(address, ins, &parsed_ins, &mut self.jump_tables);
+        if let Some(hints) = self.hints {
+            for jump_table in self.jump_tables.values_mut() {
+                if let Some(size) = hints.jump_table_size(jump_table.address) {
+                    jump_table.size = size;
+                    self.last_conditional_destination = self.last_conditional_destination.max(Some(jump_table.address + size));
+                }
+            }
+        }
         self.last_conditional_destination = self.last_conditional_destination.max(self.jump_table_state.table_end_address());
         if let Some(label) = self.jump_table_state.get_label(address, ins) {
             self.labels.insert(label);
@@ -848,9 +1052,18 @@ impl ParseFunctionContext {
             return ParseFunctionState::IllegalIns { address, ins, parsed_ins: parsed_ins.clone() };
         }
 
+        self.function_branch_state = self.function_branch_state.handle(ins, parsed_ins);
+        let tail_call = self.tail_call_destination(address, ins, parsed_ins);
+        if let Some(destination) = tail_call {
+            self.function_calls.insert(
+                address,
+                CalledFunction { conditional: ins.is_conditional(), address: destination, thumb: self.thumb, branch: true },
+            );
+        }
+
         if Some(address) >= self.last_conditional_destination {
-            if Function::is_return(ins, &parsed_ins) {
-                // We're not inside a conditional code block, so this is the final return instruction
+            if Function::is_return(ins, &parsed_ins) || (tail_call.is_some() && !ins.is_conditional()) {
+                // We're not inside a conditional code block, so this is the final return instruction or tail call
                 self.end_address = Some(address + parser.mode.instruction_size(address) as u32);
                 return ParseFunctionState::Done;
             }
@@ -865,13 +1078,8 @@ impl ParseFunctionContext {
             return ParseFunctionState::Done;
         }
 
-        self.function_branch_state = self.function_branch_state.handle(ins, &parsed_ins);
-        if let Some(destination) = Function::is_branch(ins, &parsed_ins, address) {
-            let in_current_module = destination >= self.module_start_address && destination < self.module_end_address;
-            if !in_current_module || self.function_branch_state.is_function_branch() {
-                // Tail call or function branch
-                self.function_calls.insert(address, CalledFunction { ins, address: destination, thumb: self.thumb });
-            } else {
+        if tail_call.is_none() {
+            if let Some(destination) = Function::is_branch(ins, &parsed_ins, address) {
                 if let Some(state) = self.handle_label(destination, address, parser, ins_size) {
                     return state;
                 }
@@ -892,9 +1100,67 @@ impl ParseFunctionContext {
             self.function_calls.insert(address, called_function);
         }
 
+        if let Some(destination) = Function::is_pc_relative_add(parsed_ins, address, self.thumb) {
+            self.data_loads.insert(address, destination);
+        }
+
         ParseFunctionState::Continue
     }
 
+    /// Returns the destination of a branch (B, not BL) to another function. This happens when the destination is outside
+    /// the current module, before the current function, at a known function start, or when a hint says so. Branches to
+    /// functions which are never called or pointed to are only known from the instructions leading up to them.
+    fn tail_call_destination(&self, address: u32, ins: Ins, parsed_ins: &ParsedIns) -> Option<u32> {
+        let destination = Function::is_branch(ins, parsed_ins, address)?;
+        let in_current_module = destination >= self.module_start_address && destination < self.module_end_address;
+        let before_function = destination < self.start_address;
+        let known_function = self.function_starts.map_or(false, |starts| starts.contains(&destination));
+        let tail_call_hint = self.hints.map_or(false, |hints| hints.is_tail_call(address));
+        let function_branch = self.function_branch_state.is_function_branch();
+        (!in_current_module || before_function || known_function || tail_call_hint || function_branch).then_some(destination)
+    }
+
+    /// Applies hints and known function boundaries at the given address. Returns a new state if the instruction should not
+    /// be handled any further.
+    fn handle_hints(&mut self, parser: &mut Parser, address: u32) -> Option<ParseFunctionState> {
+        let hint_end_address = self.hints.and_then(|hints| hints.function(self.start_address)).and_then(|hint| hint.end);
+        if let Some(hint_end_address) = hint_end_address {
+            if address >= hint_end_address {
+                // Never parse past the end from a hint, in case the heuristics fail to find the final return instruction
+                self.end_address = Some(hint_end_address);
+                return Some(ParseFunctionState::Done);
+            }
+        }
+        if address > self.start_address
+            && address != self.own_function_start
+            && self.function_starts.map_or(false, |starts| starts.contains(&address))
+        {
+            // Another function starts here
+            self.end_address = Some(address);
+            return Some(ParseFunctionState::Done);
+        }
+
+        let hints = self.hints?;
+        if address > self.start_address && hints.is_function_start(address) {
+            self.end_address = Some(address);
+            return Some(ParseFunctionState::Done);
+        }
+        if let Some(size) = hints.inline_table_size(address) {
+            self.inline_tables.insert(address, InlineTable { address, size, kind: InlineTableKind::Byte });
+            parser.seek_forward(address + size);
+            return Some(ParseFunctionState::Continue);
+        }
+        if let Some(size) = hints.jump_table_size(address) {
+            if !self.thumb && !self.jump_tables.contains_key(&address) {
+                // Jump table was not detected by the heuristics. The entries are branch instructions, so they can be parsed
+                // normally. Thumb jump tables contain offsets which need labels, so they must be detected to be resized.
+                self.jump_tables.insert(address, JumpTable { address, size, code: true });
+                self.last_conditional_destination = self.last_conditional_destination.max(Some(address + size));
+            }
+        }
+        None
+    }
+
     pub fn handle_ins(&mut self, parser: &mut Parser, address: u32, ins: Ins, parsed_ins: &ParsedIns) -> ParseFunctionState {
         let state = self.handle_ins_inner(parser, address, ins, parsed_ins);
         self.prev_ins = Some(ins);
@@ -975,6 +1241,7 @@ impl ParseFunctionContext {
             jump_tables: self.jump_tables,
             inline_tables: self.inline_tables,
             function_calls: self.function_calls,
+            data_loads: self.data_loads,
         }))
     }
 }
@@ -1022,11 +1289,13 @@ pub struct FindFunctionsOptions {
     pub use_data_as_upper_bound: bool,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct CalledFunction {
-    pub ins: Ins,
+    pub conditional: bool,
     pub address: u32,
     pub thumb: bool,
+    /// True if this is a tail call (B), false if it's a function call (BL/BLX)
+    pub branch: bool,
 }
 
 pub struct PoolConstant {
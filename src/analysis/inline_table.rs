@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use unarm::{
     args::{Argument, Reg, Register, Shift, ShiftImm},
     ParsedIns,
@@ -66,7 +67,7 @@ impl InlineTableState {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct InlineTable {
     pub address: u32,
     pub size: u32,
@@ -86,7 +87,7 @@ impl Into<SymData> for InlineTable {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum InlineTableKind {
     Byte,
 }
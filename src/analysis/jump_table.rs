@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use unarm::{
     args::{Argument, OffsetImm, Reg, Register, Shift, ShiftImm},
     Ins, ParsedIns,
@@ -5,7 +6,7 @@ use unarm::{
 
 use super::functions::JumpTables;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JumpTable {
     pub address: u32,
     pub size: u32,
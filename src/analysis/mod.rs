@@ -1,5 +1,9 @@
+pub mod ambiguity;
+pub mod cache;
 pub mod ctor;
 pub mod data;
+pub mod data_size;
+pub mod exception;
 pub mod function_branch;
 pub mod function_start;
 pub mod functions;
@@ -7,4 +11,6 @@ pub mod inline_table;
 pub mod jump_table;
 pub mod main;
 pub mod overlay_groups;
+pub mod overlay_loads;
 pub mod secure_area;
+pub mod veneer;
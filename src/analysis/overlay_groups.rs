@@ -1,8 +1,11 @@
+use std::{collections::BTreeMap, ops::Range};
+
 use anyhow::{bail, Result};
 use ds_rom::rom::Overlay;
 
 pub struct OverlayGroups {
     groups: Vec<OverlayGroup>,
+    ranges: BTreeMap<OverlayIndex, Range<u32>>,
 }
 
 pub type OverlayIndex = u16;
@@ -82,10 +85,21 @@ impl OverlayGroups {
             }
         }
 
-        Ok(Self { groups })
+        let ranges = overlays.iter().map(|overlay| (overlay.id(), overlay.base_address()..overlay.end_address())).collect();
+
+        Ok(Self { groups, ranges })
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &OverlayGroup> {
         self.groups.iter()
     }
+
+    /// Returns true if the two overlays can be loaded at the same time, i.e. they don't occupy the same memory.
+    pub fn can_coexist(&self, a: OverlayIndex, b: OverlayIndex) -> bool {
+        if a == b {
+            return true;
+        }
+        let (Some(a), Some(b)) = (self.ranges.get(&a), self.ranges.get(&b)) else { return true };
+        a.end <= b.start || b.end <= a.start
+    }
 }
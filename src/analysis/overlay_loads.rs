@@ -0,0 +1,286 @@
+use anyhow::{bail, Result};
+use unarm::{
+    args::{Argument, OffsetImm, Reg, Register},
+    Ins, ParsedIns,
+};
+
+use crate::{
+    config::{
+        module::{Module, ModuleKind},
+        overlay_loads::{OverlayLoad, OverlayLoads},
+    },
+    util::{bytes::FromSlice, parse::parse_u32},
+};
+
+use super::functions::Function;
+
+/// How to find the overlay loader function, see [`crate::config::config::Config::overlay_loader`].
+pub enum OverlayLoader {
+    Address(u32),
+    /// The bytes at the start of the function, with `None` for bytes that may have any value
+    Signature(Vec<Option<u8>>),
+}
+
+impl OverlayLoader {
+    /// Parses an address, a `signature:` followed by hex bytes with `??` as wildcards, or a symbol name. Symbol names are
+    /// resolved by `find_symbol`, since the analysis doesn't know the names in the project's symbols files.
+    pub fn parse<F>(text: &str, find_symbol: F) -> Result<Self>
+    where
+        F: FnOnce(&str) -> Result<Option<u32>>,
+    {
+        if let Ok(address) = parse_u32(text) {
+            return Ok(Self::Address(address));
+        }
+        if let Some(signature) = text.strip_prefix("signature:") {
+            return Self::parse_signature(signature);
+        }
+        match find_symbol(text)? {
+            Some(address) => Ok(Self::Address(address)),
+            None => {
+                log::error!("Overlay loader '{text}' was not found in symbols.txt, use its address or signature instead");
+                bail!("Overlay loader '{text}' was not found in symbols.txt, use its address or signature instead");
+            }
+        }
+    }
+
+    fn parse_signature(text: &str) -> Result<Self> {
+        let digits = text.chars().filter(|c| !c.is_whitespace()).collect::<Vec<_>>();
+        if digits.is_empty() || digits.len() % 2 != 0 {
+            log::error!("Overlay loader signature '{text}' must be a non-empty list of hex bytes");
+            bail!("Overlay loader signature '{text}' must be a non-empty list of hex bytes");
+        }
+
+        let mut bytes = vec![];
+        for pair in digits.chunks(2) {
+            let pair = pair.iter().collect::<String>();
+            if pair == "??" {
+                bytes.push(None);
+                continue;
+            }
+            let Ok(byte) = u8::from_str_radix(&pair, 16) else {
+                log::error!("Invalid byte '{pair}' in overlay loader signature, expected two hex digits or '??'");
+                bail!("Invalid byte '{pair}' in overlay loader signature, expected two hex digits or '??'");
+            };
+            bytes.push(Some(byte));
+        }
+        Ok(Self::Signature(bytes))
+    }
+
+    /// Returns the address of the overlay loader. A signature must match the start of exactly one function.
+    pub fn find(&self, modules: &[Module]) -> Result<u32> {
+        let signature = match self {
+            Self::Address(address) => return Ok(*address),
+            Self::Signature(signature) => signature,
+        };
+
+        let mut matches = vec![];
+        for module in modules {
+            for function in module.sections().functions() {
+                let code = function.code(module.code(), module.base_address());
+                if code.len() < signature.len() {
+                    continue;
+                }
+                if signature.iter().zip(code).all(|(expected, &byte)| expected.is_none_or(|expected| expected == byte)) {
+                    matches.push((module.kind(), function.start_address()));
+                }
+            }
+        }
+
+        match matches.as_slice() {
+            [] => {
+                log::error!("No function matches the overlay loader signature");
+                bail!("No function matches the overlay loader signature");
+            }
+            [(module, address)] => {
+                log::debug!("Found overlay loader at {address:#010x} in {module}");
+                Ok(*address)
+            }
+            _ => {
+                let matches = matches
+                    .iter()
+                    .map(|(module, address)| format!("{address:#010x} in {module}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                log::error!("Several functions match the overlay loader signature: {matches}");
+                bail!("Several functions match the overlay loader signature: {matches}");
+            }
+        }
+    }
+}
+
+/// Finds calls to the overlay loader at `loader`, such as `FS_LoadOverlay`, and the overlay ID passed to it in r1.
+pub fn find_overlay_loads(modules: &[Module], loader: u32) -> OverlayLoads {
+    let overlay_ids = modules
+        .iter()
+        .filter_map(|module| if let ModuleKind::Overlay(id) = module.kind() { Some(id) } else { None })
+        .collect::<Vec<_>>();
+
+    let mut overlay_loads = OverlayLoads::new();
+    for module in modules {
+        for section in module.sections().iter() {
+            for function in section.functions().values() {
+                let calls = function
+                    .function_calls()
+                    .iter()
+                    .filter(|(_, called_function)| called_function.address == loader)
+                    .map(|(&address, _)| address)
+                    .collect::<Vec<_>>();
+                if calls.is_empty() {
+                    continue;
+                }
+
+                let values = RegisterValues::at_calls(function, module.code(), module.base_address(), &calls);
+                for (from, overlay_id) in values {
+                    let Some(overlay_id) = overlay_id.and_then(|id| u16::try_from(id).ok()) else {
+                        log::debug!(
+                            "Overlay ID of overlay load from {:#010x} in {} could not be determined",
+                            from,
+                            module.kind()
+                        );
+                        continue;
+                    };
+                    if !overlay_ids.contains(&overlay_id) {
+                        log::warn!(
+                            "Overlay load from {:#010x} in {} loads overlay {} which doesn't exist",
+                            from,
+                            module.kind(),
+                            overlay_id
+                        );
+                        continue;
+                    }
+                    overlay_loads.add(OverlayLoad { module: module.kind(), from, overlay: overlay_id });
+                }
+            }
+        }
+    }
+    overlay_loads
+}
+
+/// Registers with known constant values, tracked linearly through a function.
+#[derive(Default)]
+struct RegisterValues {
+    values: Vec<(Register, u32)>,
+}
+
+impl RegisterValues {
+    /// Returns the value of r1 at each of the given call instructions, if it's a known constant.
+    fn at_calls(function: &Function, module_code: &[u8], base_address: u32, calls: &[u32]) -> Vec<(u32, Option<u32>)> {
+        let mut result = vec![];
+        let mut registers = Self::default();
+        let labels = function.labels().copied().collect::<Vec<_>>();
+
+        for (address, ins, parsed_ins) in function.parser(module_code, base_address) {
+            if Self::is_pool_constant(function, address) {
+                registers.clear();
+                continue;
+            }
+            if labels.contains(&address) {
+                // Control flow merges here, so the values are unknown
+                registers.clear();
+            }
+            if calls.contains(&address) {
+                result.push((address, registers.get(Register::R1)));
+            }
+            registers.handle_ins(ins, &parsed_ins, address, function.is_thumb(), module_code, base_address);
+        }
+        result
+    }
+
+    fn is_pool_constant(function: &Function, address: u32) -> bool {
+        function.pool_constants().range(..=address).next_back().is_some_and(|&pool_constant| address < pool_constant + 4)
+    }
+
+    fn get(&self, reg: Register) -> Option<u32> {
+        self.values.iter().find(|(r, _)| *r == reg).map(|&(_, value)| value)
+    }
+
+    fn set(&mut self, reg: Register, value: Option<u32>) {
+        self.values.retain(|(r, _)| *r != reg);
+        if let Some(value) = value {
+            self.values.push((reg, value));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    fn handle_ins(
+        &mut self,
+        ins: Ins,
+        parsed_ins: &ParsedIns,
+        address: u32,
+        thumb: bool,
+        module_code: &[u8],
+        base_address: u32,
+    ) {
+        let args = &parsed_ins.args;
+        let mnemonic = parsed_ins.mnemonic;
+
+        if mnemonic.starts_with("bl") {
+            // Caller-saved registers are clobbered by calls
+            for reg in [Register::R0, Register::R1, Register::R2, Register::R3, Register::R12] {
+                self.set(reg, None);
+            }
+            return;
+        }
+        if let Argument::RegList(regs) = args[1] {
+            if mnemonic.starts_with("ldm") {
+                self.values.retain(|(reg, _)| !regs.contains(*reg));
+            }
+        }
+        if let ("pop", Argument::RegList(regs)) = (mnemonic, args[0]) {
+            self.values.retain(|(reg, _)| !regs.contains(*reg));
+            return;
+        }
+
+        let Argument::Reg(Reg { reg: dest, deref: false, .. }) = args[0] else { return };
+        if mnemonic.starts_with("str")
+            || mnemonic.starts_with("stm")
+            || mnemonic.starts_with("cmp")
+            || mnemonic.starts_with("cmn")
+            || mnemonic.starts_with("tst")
+            || mnemonic.starts_with("teq")
+            || mnemonic == "bx"
+        {
+            return;
+        }
+
+        let value = if ins.is_conditional() {
+            None
+        } else {
+            match (mnemonic, args[1], args[2], args[3]) {
+                ("mov" | "movs", Argument::UImm(value), Argument::None, _) => Some(value),
+                ("mov" | "movs", Argument::Reg(Reg { reg: src, deref: false, .. }), Argument::None, _) => self.get(src),
+                ("mvn" | "mvns", Argument::UImm(value), Argument::None, _) => Some(!value),
+                ("add" | "adds", Argument::UImm(value), Argument::None, _) => self.get(dest).map(|v| v.wrapping_add(value)),
+                ("sub" | "subs", Argument::UImm(value), Argument::None, _) => self.get(dest).map(|v| v.wrapping_sub(value)),
+                ("add" | "adds", Argument::Reg(Reg { reg: src, deref: false, .. }), Argument::UImm(value), Argument::None)
+                    if src != Register::Pc =>
+                {
+                    self.get(src).map(|v| v.wrapping_add(value))
+                }
+                ("sub" | "subs", Argument::Reg(Reg { reg: src, deref: false, .. }), Argument::UImm(value), Argument::None)
+                    if src != Register::Pc =>
+                {
+                    self.get(src).map(|v| v.wrapping_sub(value))
+                }
+                (
+                    "ldr",
+                    Argument::Reg(Reg { reg: Register::Pc, deref: true, .. }),
+                    Argument::OffsetImm(OffsetImm { post_indexed: false, value: offset }),
+                    Argument::None,
+                ) => {
+                    let load_address = ((address as i32 + offset) as u32 & !3) + if thumb { 4 } else { 8 };
+                    load_address
+                        .checked_sub(base_address)
+                        .and_then(|start| module_code.get(start as usize..))
+                        .filter(|bytes| bytes.len() >= 4)
+                        .map(u32::from_le_slice)
+                }
+                _ => None,
+            }
+        };
+        self.set(dest, value);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::util::bytes::FromSlice;
+
+/// `ldr ip, [pc]`
+const ARM_LDR_IP: u32 = 0xe59fc000;
+/// `bx ip`
+const ARM_BX_IP: u32 = 0xe12fff1c;
+/// `ldr pc, [pc, #-4]`
+const ARM_LDR_PC: u32 = 0xe51ff004;
+/// `bx pc`
+const THUMB_BX_PC: u16 = 0x4778;
+/// `nop`, encoded as `mov r8, r8`
+const THUMB_NOP: u16 = 0x46c0;
+
+/// A veneer or interworking stub generated by the linker. These are not part of any source file, so they are excluded from
+/// delinked objects and generated again when linking.
+#[derive(Clone, Copy, Debug)]
+pub struct Veneer {
+    pub size: u32,
+    pub thumb: bool,
+    /// Destination of the veneer, with the lowest bit set if it's a Thumb function
+    pub target: u32,
+}
+
+impl Veneer {
+    /// Matches the veneer patterns known to be generated by mwld:
+    ///
+    /// ```text
+    /// ; ARM long branch
+    /// ldr ip, [pc]
+    /// bx ip
+    /// .word target
+    ///
+    /// ; ARM long branch without interworking
+    /// ldr pc, [pc, #-4]
+    /// .word target
+    ///
+    /// ; Thumb to ARM stub, followed by a branch in one of the forms above or `b target`
+    /// bx pc
+    /// nop
+    /// ```
+    pub fn parse(code: &[u8], address: u32) -> Option<Self> {
+        if address & 3 != 0 {
+            return None;
+        }
+
+        if let Some((size, target)) = Self::parse_arm(code, address) {
+            return Some(Self { size, thumb: false, target });
+        }
+
+        if code.len() < 4 {
+            return None;
+        }
+        if u16::from_le_slice(&code[0..]) != THUMB_BX_PC || u16::from_le_slice(&code[2..]) != THUMB_NOP {
+            return None;
+        }
+        let arm_code = &code[4..];
+        if let Some((size, target)) = Self::parse_arm(arm_code, address + 4) {
+            return Some(Self { size: size + 4, thumb: true, target });
+        }
+        if arm_code.len() >= 4 {
+            let ins = u32::from_le_slice(arm_code);
+            if ins & 0xff000000 == 0xea000000 {
+                // b target
+                let offset = ((ins << 8) as i32) >> 6;
+                let target = ((address + 4 + 8) as i32 + offset) as u32;
+                return Some(Self { size: 8, thumb: true, target });
+            }
+        }
+        None
+    }
+
+    fn parse_arm(code: &[u8], address: u32) -> Option<(u32, u32)> {
+        let word = |index: usize| code.get(index * 4..).filter(|bytes| bytes.len() >= 4).map(u32::from_le_slice);
+
+        match (word(0), word(1), word(2)) {
+            (Some(ARM_LDR_IP), Some(ARM_BX_IP), Some(target)) if Self::is_valid_target(target, address) => Some((12, target)),
+            (Some(ARM_LDR_PC), Some(target), _) if Self::is_valid_target(target, address) => Some((8, target)),
+            _ => None,
+        }
+    }
+
+    fn is_valid_target(target: u32, address: u32) -> bool {
+        // ARM targets must be word aligned, and a veneer never leads to itself
+        let aligned = (target & 1) != 0 || (target & 3) == 0;
+        aligned && (target & !1) != address
+    }
+
+    pub fn target_address(&self) -> u32 {
+        self.target & !1
+    }
+
+    pub fn target_thumb(&self) -> bool {
+        (self.target & 1) != 0
+    }
+}
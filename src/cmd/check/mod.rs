@@ -1,6 +1,10 @@
 mod modules;
+mod relocs;
+mod symbols;
 
 pub use modules::*;
+pub use relocs::*;
+pub use symbols::*;
 
 use anyhow::Result;
 use argp::FromArgs;
@@ -17,6 +21,8 @@ impl CheckArgs {
     pub fn run(&self) -> Result<()> {
         match &self.command {
             CheckCommand::Modules(modules) => modules.run(),
+            CheckCommand::Relocs(relocs) => relocs.run(),
+            CheckCommand::Symbols(symbols) => symbols.run(),
         }
     }
 }
@@ -25,4 +31,6 @@ impl CheckArgs {
 #[argp(subcommand)]
 enum CheckCommand {
     Modules(CheckModules),
+    Relocs(CheckRelocs),
+    Symbols(CheckSymbols),
 }
@@ -11,7 +11,7 @@ use crate::{
         config::{Config, ConfigModule},
         module::ModuleKind,
     },
-    util::io::{open_file, read_file},
+    util::io::read_file,
 };
 
 /// Verifies that built modules are matching the base ROM.
@@ -35,7 +35,7 @@ enum CheckResult {
 
 impl CheckModules {
     pub fn run(&self) -> Result<()> {
-        let config: Config = serde_yml::from_reader(open_file(&self.config_path)?)?;
+        let config = Config::from_file(&self.config_path)?;
         let config_path = self.config_path.parent().unwrap();
 
         let mut success = true;
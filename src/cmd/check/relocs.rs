@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::config::{
+    config::{Config, ConfigModule},
+    module::ModuleKind,
+    relocation::{RelocationOverride, RelocationOverrides, Relocations},
+};
+
+/// Verifies that relocation overrides still match the generated relocations.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "relocs")]
+pub struct CheckRelocs {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Return failing exit code if an override doesn't match.
+    #[argp(switch, short = 'f')]
+    pub fail: bool,
+}
+
+impl CheckRelocs {
+    pub fn run(&self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)?;
+        let config_path = self.config_path.parent().unwrap();
+
+        let mut success = true;
+
+        success &= self.check_module(&config.main_module, ModuleKind::Arm9, config_path)?;
+        for autoload in &config.autoloads {
+            success &= self.check_module(&autoload.module, ModuleKind::Autoload(autoload.kind), config_path)?;
+        }
+        for overlay in &config.overlays {
+            success &= self.check_module(&overlay.module, ModuleKind::Overlay(overlay.id), config_path)?;
+        }
+
+        if self.fail && !success {
+            bail!("Some relocation override(s) didn't match.");
+        }
+
+        Ok(())
+    }
+
+    fn check_module(&self, module: &ConfigModule, module_kind: ModuleKind, config_path: &Path) -> Result<bool> {
+        let relocations_path = config_path.join(&module.relocations);
+        let overrides_path = RelocationOverrides::path(&relocations_path);
+        if !overrides_path.exists() {
+            return Ok(true);
+        }
+
+        let relocations = Relocations::from_file_without_overrides(&relocations_path)?;
+        let overrides = RelocationOverrides::from_file(&overrides_path)?;
+
+        let mut success = true;
+        for relocation_override in overrides.iter() {
+            let existing = relocations.get(relocation_override.from_address());
+            match relocation_override {
+                RelocationOverride::Add(relocation) if existing == Some(relocation) => {
+                    log::warn!("Check {module_kind}: '{relocation_override}' is identical to a generated relocation");
+                    success = false;
+                }
+                RelocationOverride::Remove { .. } | RelocationOverride::SetModule { .. } if existing.is_none() => {
+                    log::warn!("Check {module_kind}: '{relocation_override}' doesn't match any relocation");
+                    success = false;
+                }
+                _ => {}
+            }
+        }
+
+        if success {
+            log::info!("Check {module_kind}: OK");
+        }
+        Ok(success)
+    }
+}
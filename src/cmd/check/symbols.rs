@@ -0,0 +1,106 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+use object::{Object, ObjectSymbol};
+
+use crate::{
+    config::{
+        config::{Config, ConfigModule},
+        module::ModuleKind,
+        symbol::SymbolMap,
+        types::Types,
+    },
+    util::io::read_file,
+};
+
+/// Verifies that the symbols in the linked ELF have the sizes given in symbols.txt.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "symbols")]
+pub struct CheckSymbols {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Path to linked ELF file.
+    #[argp(option, short = 'e')]
+    pub elf: PathBuf,
+
+    /// Return failing exit code if a symbol's size doesn't match.
+    #[argp(switch, short = 'f')]
+    pub fail: bool,
+}
+
+/// Address and size of each linked symbol, by name.
+type LinkedSymbols = HashMap<String, Vec<(u32, u32)>>;
+
+impl CheckSymbols {
+    pub fn run(&self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)?;
+        let config_path = self.config_path.parent().unwrap();
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+
+        let file = read_file(&self.elf)?;
+        let object = object::File::parse(&*file)?;
+        let mut linked_symbols = LinkedSymbols::new();
+        for symbol in object.symbols() {
+            let Ok(name) = symbol.name() else { continue };
+            // Thumb functions have the lowest bit set
+            let address = symbol.address() as u32 & !1;
+            linked_symbols.entry(name.to_string()).or_default().push((address, symbol.size() as u32));
+        }
+
+        let mut success = true;
+
+        success &= self.check_module(&config.main_module, ModuleKind::Arm9, config_path, &types, &linked_symbols)?;
+        for autoload in &config.autoloads {
+            let module_kind = ModuleKind::Autoload(autoload.kind);
+            success &= self.check_module(&autoload.module, module_kind, config_path, &types, &linked_symbols)?;
+        }
+        for overlay in &config.overlays {
+            let module_kind = ModuleKind::Overlay(overlay.id);
+            success &= self.check_module(&overlay.module, module_kind, config_path, &types, &linked_symbols)?;
+        }
+
+        if self.fail && !success {
+            bail!("Some symbol(s) didn't have the expected size.");
+        }
+
+        Ok(())
+    }
+
+    fn check_module(
+        &self,
+        module: &ConfigModule,
+        module_kind: ModuleKind,
+        config_path: &Path,
+        types: &Types,
+        linked_symbols: &LinkedSymbols,
+    ) -> Result<bool> {
+        let symbol_map = SymbolMap::from_file(config_path.join(&module.symbols), types)?;
+
+        let mut success = true;
+        for symbol in symbol_map.iter_by_address(0..u32::MAX) {
+            // Symbols without a size of their own are sized by the next symbol, so only the `size:` attribute and sized
+            // kinds can be checked
+            let Some(expected) = symbol.size.or(symbol.kind.known_size(types)) else { continue };
+            let Some(linked) = linked_symbols.get(&symbol.name) else { continue };
+            let Some(&(_, size)) = linked.iter().find(|&&(address, _)| address == symbol.addr) else { continue };
+            if size != expected {
+                log::warn!(
+                    "Check {module_kind}: symbol '{}' has size {size:#x} in the ELF but {expected:#x} in symbols.txt",
+                    symbol.name
+                );
+                success = false;
+            }
+        }
+
+        if success {
+            log::info!("Check {module_kind}: OK");
+        }
+        Ok(success)
+    }
+}
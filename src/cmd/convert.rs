@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::config::{delinks::Delinks, module::ModuleKind, relocation::Relocations, symbol::SymbolMap, types::Types};
+
+/// Converts a symbols, relocs or delinks file between the text format and JSON. Files ending in `.json` are JSON.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "convert")]
+pub struct Convert {
+    /// File to convert.
+    #[argp(positional)]
+    pub input: PathBuf,
+
+    /// Path to the converted file.
+    #[argp(positional)]
+    pub output: PathBuf,
+
+    /// Kind of file: symbols, relocs or delinks. Inferred from the input file name if omitted.
+    #[argp(option, short = 'k')]
+    pub kind: Option<String>,
+
+    /// Path to types.txt, needed if data symbols use struct types.
+    #[argp(option, short = 't')]
+    pub types: Option<PathBuf>,
+}
+
+impl Convert {
+    pub fn run(&self) -> Result<()> {
+        let kind = match &self.kind {
+            Some(kind) => kind.as_str(),
+            None => Self::infer_kind(&self.input)?,
+        };
+
+        match kind {
+            "symbols" => {
+                let types = Types::from_optional_file(self.types.as_ref())?;
+                SymbolMap::from_file(&self.input, &types)?.to_file(&self.output)?;
+            }
+            // Overrides are kept in their own file, so that they still apply when the relocations are regenerated
+            "relocs" => Relocations::from_file_without_overrides(&self.input)?.to_file(&self.output)?,
+            "delinks" => {
+                // Gap files are left out of the output, so the module kind used for naming them doesn't matter
+                let delinks = Delinks::from_file(&self.input, ModuleKind::Arm9)?;
+                Delinks::to_file(&self.output, &delinks.sections, &delinks.files)?;
+            }
+            _ => {
+                log::error!("Unknown file kind '{kind}', must be one of: symbols, relocs, delinks");
+                bail!("Unknown file kind '{kind}', must be one of: symbols, relocs, delinks");
+            }
+        }
+
+        log::info!("Converted {} to {}", self.input.display(), self.output.display());
+        Ok(())
+    }
+
+    fn infer_kind(path: &Path) -> Result<&'static str> {
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("");
+        if stem.starts_with("symbols") {
+            Ok("symbols")
+        } else if stem.starts_with("relocs") {
+            Ok("relocs")
+        } else if stem.starts_with("delinks") {
+            Ok("delinks")
+        } else {
+            log::error!("Can't tell the kind of '{}' from its name, use --kind", path.display());
+            bail!("Can't tell the kind of '{}' from its name, use --kind", path.display());
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use argp::FromArgs;
+
+use crate::{
+    config::{
+        config::Config,
+        delinks::{DelinkFile, Delinks},
+        module::ModuleKind,
+        relocation::Relocations,
+        symbol::{SymData, SymFunction, Symbol, SymbolKind, SymbolMaps},
+        types::{FieldKind, Types},
+    },
+    util::io::create_file_and_dirs,
+};
+
+/// Generates C context headers with declarations of the symbols referenced by each source file, for decomp.me scratches
+/// and m2c.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "ctx")]
+pub struct Ctx {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+}
+
+impl Ctx {
+    pub fn run(&self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)?;
+        let config_path = self.config_path.parent().unwrap();
+
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        let symbol_maps = SymbolMaps::from_config(config_path, &config, &types)?;
+        let build_path = config_path.join(&config.build_path);
+
+        let mut modules = vec![(&config.main_module, ModuleKind::Arm9)];
+        modules.extend(config.autoloads.iter().map(|autoload| (&autoload.module, ModuleKind::Autoload(autoload.kind))));
+        modules.extend(config.overlays.iter().map(|overlay| (&overlay.module, ModuleKind::Overlay(overlay.id))));
+
+        let mut num_files = 0;
+        for (module, module_kind) in modules {
+            let delinks = Delinks::from_file(config_path.join(&module.delinks), module_kind)?;
+            let relocations = Relocations::from_file(config_path.join(&module.relocations))?;
+
+            for file in delinks.files.iter().filter(|file| !file.gap()) {
+                let symbols = Self::referenced_symbols(file, module_kind, &relocations, &symbol_maps)?;
+                Self::write_ctx(Self::path(&build_path, file), file, &symbols, &types)?;
+                num_files += 1;
+            }
+        }
+
+        log::info!("Generated {num_files} context files");
+        Ok(())
+    }
+
+    /// Path to the context file of a source file, e.g. `src/main.c` becomes `<build path>/src/main.ctx.c`.
+    pub fn path(build_path: &Path, file: &DelinkFile) -> PathBuf {
+        let (file_path, extension) = file.split_file_ext();
+        if extension.is_empty() {
+            build_path.join(format!("{file_path}.ctx"))
+        } else {
+            build_path.join(format!("{file_path}.ctx.{extension}"))
+        }
+    }
+
+    /// Returns every symbol that the file refers to through relocations, sorted by module and address. Symbols defined
+    /// in the file itself are left out, since the source file declares those.
+    fn referenced_symbols<'a>(
+        file: &DelinkFile,
+        file_module: ModuleKind,
+        relocations: &Relocations,
+        symbol_maps: &'a SymbolMaps,
+    ) -> Result<Vec<&'a Symbol>> {
+        let mut symbols = BTreeMap::new();
+        for section in file.sections.iter() {
+            for (_, relocation) in relocations.iter_range(section.address_range()) {
+                let Some(module_kind) = relocation.module().first_module() else { continue };
+                let Some(symbol_map) = symbol_maps.get(module_kind) else { continue };
+
+                let to = relocation.to_address();
+                if module_kind == file_module && file.sections.iter().any(|section| section.address_range().contains(&to)) {
+                    continue;
+                }
+                let symbol = if let Some((_, symbol)) = symbol_map.by_address(to)? {
+                    symbol
+                } else if let Some((_, symbol)) = symbol_map.get_function(to)? {
+                    symbol
+                } else {
+                    log::warn!(
+                        "No symbol found for relocation from {:#010x} to {to:#010x} in {module_kind}",
+                        relocation.from_address()
+                    );
+                    continue;
+                };
+                symbols.insert((module_kind, symbol.addr), symbol);
+            }
+        }
+        Ok(symbols.into_values().collect())
+    }
+
+    fn write_ctx(path: PathBuf, file: &DelinkFile, symbols: &[&Symbol], types: &Types) -> Result<()> {
+        let mut writer = BufWriter::new(create_file_and_dirs(path)?);
+
+        writeln!(writer, "// Generated by dsd, do not edit")?;
+        writeln!(writer)?;
+        writeln!(writer, "typedef unsigned char u8;")?;
+        writeln!(writer, "typedef unsigned short u16;")?;
+        writeln!(writer, "typedef unsigned long u32;")?;
+        writeln!(writer)?;
+
+        let mut written_structs = BTreeSet::new();
+        for symbol in symbols {
+            if let SymbolKind::Data(SymData::Struct { name, .. }) = &symbol.kind {
+                Self::write_struct(&mut writer, name.as_str(), types, &mut written_structs)?;
+            }
+        }
+
+        let (_, extension) = file.split_file_ext();
+        let cpp = matches!(extension, "cpp" | "cp" | "cc" | "cxx");
+        if cpp {
+            // Declarations must use the symbol names as they are, since C++ would mangle them
+            writeln!(writer, "extern \"C\" {{")?;
+            writeln!(writer)?;
+        }
+        for symbol in symbols {
+            Self::write_declaration(&mut writer, symbol, types, cpp)?;
+        }
+        if cpp {
+            writeln!(writer)?;
+            writeln!(writer, "}}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a struct definition from `types.txt`, after the structs it contains.
+    fn write_struct<W: Write>(w: &mut W, name: &str, types: &Types, written: &mut BTreeSet<String>) -> Result<()> {
+        if written.contains(name) {
+            return Ok(());
+        }
+        let Some(struct_type) = types.get(name) else {
+            return Ok(());
+        };
+        written.insert(name.to_string());
+
+        for field in &struct_type.fields {
+            if let FieldKind::Struct(nested) = &field.kind {
+                Self::write_struct(w, nested, types, written)?;
+            }
+        }
+
+        writeln!(w, "struct {name} {{")?;
+        for field in &struct_type.fields {
+            let c_type = match &field.kind {
+                FieldKind::Byte => "u8".to_string(),
+                FieldKind::Short => "u16".to_string(),
+                FieldKind::Word => "u32".to_string(),
+                FieldKind::Pointer => "void *".to_string(),
+                FieldKind::Struct(nested) => format!("struct {nested}"),
+            };
+            let separator = if c_type.ends_with('*') { "" } else { " " };
+            if field.count == 1 {
+                writeln!(w, "    {c_type}{separator}{};", field.name)?;
+            } else {
+                writeln!(w, "    {c_type}{separator}{}[{}];", field.name, field.count)?;
+            }
+        }
+        writeln!(w, "}};")?;
+        writeln!(w)?;
+        Ok(())
+    }
+
+    fn write_declaration<W: Write>(w: &mut W, symbol: &Symbol, types: &Types, cpp: bool) -> Result<()> {
+        let name = &symbol.name;
+        match &symbol.kind {
+            // An empty parameter list means no parameters in C++, but unknown parameters in C
+            SymbolKind::Function(SymFunction { mode, .. }) if cpp => writeln!(w, "void {name}(...); // {mode}")?,
+            SymbolKind::Function(SymFunction { mode, .. }) => writeln!(w, "void {name}(); // {mode}")?,
+            SymbolKind::Data(data) => {
+                let element_type = match data {
+                    SymData::Any | SymData::Byte { .. } => "u8".to_string(),
+                    SymData::Short { .. } => "u16".to_string(),
+                    SymData::Word { .. } => "u32".to_string(),
+                    SymData::Struct { name, .. } if types.get(name.as_str()).is_some() => format!("struct {name}"),
+                    SymData::Struct { .. } => "u8".to_string(),
+                };
+                let count = match data {
+                    SymData::Any => symbol.size,
+                    SymData::Struct { name, .. } if types.get(name.as_str()).is_none() => None,
+                    _ => data.count(),
+                };
+                match count {
+                    Some(1) => writeln!(w, "extern {element_type} {name};")?,
+                    Some(count) => writeln!(w, "extern {element_type} {name}[{count}];")?,
+                    None => writeln!(w, "extern {element_type} {name}[];")?,
+                }
+            }
+            SymbolKind::Bss(bss) => match symbol.size.or(bss.size) {
+                Some(size) => writeln!(w, "extern u8 {name}[{size:#x}];")?,
+                None => writeln!(w, "extern u8 {name}[];")?,
+            },
+            SymbolKind::Label(_) | SymbolKind::PoolConstant | SymbolKind::JumpTable(_) | SymbolKind::Veneer(_) => {}
+        }
+        Ok(())
+    }
+}
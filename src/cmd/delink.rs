@@ -1,5 +1,6 @@
 use std::{
     collections::BTreeMap,
+    hash::{Hash, Hasher},
     io::BufWriter,
     path::{Path, PathBuf},
 };
@@ -7,17 +8,22 @@ use std::{
 use anyhow::{bail, Context, Result};
 use argp::FromArgs;
 use ds_rom::rom::{Rom, RomLoadOptions};
+use fxhash::FxHasher64;
 use object::{Architecture, BinaryFormat, Endianness, RelocationFlags};
-use serde::Serialize;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    analysis::cache::AnalysisCache,
     config::{
         config::{Config, ConfigAutoload, ConfigModule, ConfigOverlay},
         delinks::{DelinkFile, Delinks},
+        hints::Hints,
         module::{Module, ModuleKind},
         relocation::Relocations,
         section::SectionKind,
-        symbol::SymbolMaps,
+        symbol::{SymScope, SymbolMaps},
+        types::Types,
     },
     util::io::{create_dir_all, create_file, open_file},
 };
@@ -35,27 +41,89 @@ pub struct Delink {
 struct DelinkResult {
     num_files: usize,
     num_gaps: usize,
+    #[serde(skip)]
+    num_up_to_date: usize,
+}
+
+/// Paths and types used while delinking modules.
+struct DelinkInputs {
+    /// Output directory for delinked objects
+    elf_path: PathBuf,
+    /// Directory for cached function analysis
+    analysis_path: PathBuf,
+    /// Struct types, for the sizes of data symbols
+    types: Types,
+}
+
+/// Hashes of the inputs used to delink each object, to avoid rewriting objects that haven't changed.
+#[derive(Default, Serialize, Deserialize)]
+struct DelinkCache {
+    /// Version of dsd that delinked the objects, the cache is discarded when this changes
+    version: String,
+    /// Maps object path to the hash of its delinker inputs
+    objects: BTreeMap<String, u64>,
+    #[serde(skip)]
+    previous_objects: BTreeMap<String, u64>,
+}
+
+impl DelinkCache {
+    fn load(path: &Path) -> Result<Self> {
+        let version = env!("CARGO_PKG_VERSION").to_string();
+        if !path.exists() {
+            return Ok(Self { version, ..Default::default() });
+        }
+
+        let cache: DelinkCache = match serde_yml::from_reader(open_file(path)?) {
+            Ok(cache) => cache,
+            Err(e) => {
+                log::warn!("Failed to read delink cache '{}', delinking all objects: {e}", path.display());
+                return Ok(Self { version, ..Default::default() });
+            }
+        };
+        if cache.version != version {
+            return Ok(Self { version, ..Default::default() });
+        }
+        Ok(Self { version, objects: BTreeMap::new(), previous_objects: cache.objects })
+    }
+
+    fn is_up_to_date(&self, object_path: &str, hash: u64) -> bool {
+        self.previous_objects.get(object_path) == Some(&hash)
+    }
 }
 
 impl Delink {
     pub fn run(&self) -> Result<()> {
-        let config: Config = serde_yml::from_reader(open_file(&self.config_path)?)?;
+        let config = Config::from_file(&self.config_path)?;
         let config_path = self.config_path.parent().unwrap();
 
-        let mut symbol_maps = SymbolMaps::from_config(config_path, &config)?;
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        let mut symbol_maps = SymbolMaps::from_config(config_path, &config, &types)?;
         let rom = Rom::load(
             config_path.join(&config.rom_config),
             RomLoadOptions { key: None, compress: false, encrypt: false, load_files: false },
         )?;
 
-        let elf_path = config_path.join(config.delinks_path);
+        let inputs = DelinkInputs {
+            elf_path: config_path.join(&config.delinks_path),
+            analysis_path: config_path.join(&config.build_path).join("analysis"),
+            types,
+        };
         let mut result = DelinkResult::default();
+        let cache_path = inputs.elf_path.join("delink_cache.yaml");
+        let mut cache = DelinkCache::load(&cache_path)?;
+
+        self.delink_arm9(&config.main_module, &rom, &inputs, &mut symbol_maps, &mut cache, &mut result)?;
+        self.delink_autoloads(&config.autoloads, &rom, &inputs, &mut symbol_maps, &mut cache, &mut result)?;
+        self.delink_overlays(&config.overlays, &rom, &inputs, &mut symbol_maps, &mut cache, &mut result)?;
 
-        self.delink_arm9(&config.main_module, &rom, &elf_path, &mut symbol_maps, &mut result)?;
-        self.delink_autoloads(&config.autoloads, &rom, &elf_path, &mut symbol_maps, &mut result)?;
-        self.delink_overlays(&config.overlays, &rom, &elf_path, &mut symbol_maps, &mut result)?;
+        log::info!(
+            "Delinked {} objects, {} were up to date",
+            cache.objects.len() - result.num_up_to_date,
+            result.num_up_to_date
+        );
 
-        serde_yml::to_writer(create_file(elf_path.join("delink.yaml"))?, &result)?;
+        serde_yml::to_writer(create_file(&cache_path)?, &cache)?;
+        serde_yml::to_writer(create_file(inputs.elf_path.join("delink.yaml"))?, &result)?;
 
         Ok(())
     }
@@ -64,30 +132,27 @@ impl Delink {
         &self,
         config: &ConfigModule,
         rom: &Rom,
-        elf_path: &Path,
+        inputs: &DelinkInputs,
         symbol_maps: &mut SymbolMaps,
+        cache: &mut DelinkCache,
         result: &mut DelinkResult,
     ) -> Result<()> {
         let config_path = self.config_path.parent().unwrap();
 
         let module_kind = ModuleKind::Arm9;
-        let delinks = Delinks::from_file(config_path.join(&config.delinks), module_kind)?;
-        let symbol_map = symbol_maps.get_mut(module_kind);
+        let mut delinks = Delinks::from_file(config_path.join(&config.delinks), module_kind)?;
         let relocations = Relocations::from_file(config_path.join(&config.relocations))?;
+        delinks.assign_exception_tables(&relocations)?;
+        let symbol_map = symbol_maps.get_mut(module_kind);
+        delinks.exclude_veneers(symbol_map)?;
+        let hints = Hints::from_optional_file(config.hints.as_ref().map(|hints| config_path.join(hints)))?;
 
         let code = rom.arm9().code()?;
-        let module = Module::new_arm9(config.name.clone(), symbol_map, relocations, delinks.sections, &code)?;
+        let cache_path = AnalysisCache::path(&inputs.analysis_path, &config.name);
+        let module =
+            Module::new_arm9(config.name.clone(), symbol_map, relocations, hints, delinks.sections, &code, Some(&cache_path))?;
 
-        for file in &delinks.files {
-            let (file_path, _) = file.split_file_ext();
-            Self::create_elf_file(&module, file, elf_path.join(format!("{file_path}.o")), &symbol_maps)?;
-
-            if file.gap() {
-                result.num_gaps += 1;
-            } else {
-                result.num_files += 1;
-            }
-        }
+        Self::delink_files(&module, &delinks.files, inputs, symbol_maps, cache, result)?;
 
         Ok(())
     }
@@ -96,8 +161,9 @@ impl Delink {
         &self,
         autoloads: &[ConfigAutoload],
         rom: &Rom,
-        elf_path: &Path,
+        inputs: &DelinkInputs,
         symbol_maps: &mut SymbolMaps,
+        cache: &mut DelinkCache,
         result: &mut DelinkResult,
     ) -> Result<()> {
         let rom_autoloads = rom.arm9().autoloads()?;
@@ -105,34 +171,31 @@ impl Delink {
             let config_path = self.config_path.parent().unwrap();
 
             let module_kind = ModuleKind::Autoload(autoload.kind);
-            let delinks = Delinks::from_file(config_path.join(&autoload.module.delinks), module_kind)?;
-            let symbol_map = symbol_maps.get_mut(module_kind);
+            let mut delinks = Delinks::from_file(config_path.join(&autoload.module.delinks), module_kind)?;
             let relocations = Relocations::from_file(config_path.join(&autoload.module.relocations))?;
+            delinks.assign_exception_tables(&relocations)?;
+            let symbol_map = symbol_maps.get_mut(module_kind);
+            delinks.exclude_veneers(symbol_map)?;
+            let hints = Hints::from_optional_file(autoload.module.hints.as_ref().map(|hints| config_path.join(hints)))?;
 
             let code = rom_autoloads
                 .iter()
                 .find(|a| a.kind() == autoload.kind)
                 .with_context(|| format!("Autoload {} not present in ROM", autoload.kind))?
                 .code();
+            let cache_path = AnalysisCache::path(&inputs.analysis_path, &autoload.module.name);
             let module = Module::new_autoload(
                 autoload.module.name.clone(),
                 symbol_map,
                 relocations,
+                hints,
                 delinks.sections,
                 autoload.kind,
                 &code,
+                Some(&cache_path),
             )?;
 
-            for file in &delinks.files {
-                let (file_path, _) = file.split_file_ext();
-                Self::create_elf_file(&module, file, elf_path.join(format!("{file_path}.o")), &symbol_maps)?;
-
-                if file.gap() {
-                    result.num_gaps += 1;
-                } else {
-                    result.num_files += 1;
-                }
-            }
+            Self::delink_files(&module, &delinks.files, inputs, symbol_maps, cache, result)?;
         }
 
         Ok(())
@@ -142,54 +205,128 @@ impl Delink {
         &self,
         overlays: &[ConfigOverlay],
         rom: &Rom,
-        elf_path: &Path,
+        inputs: &DelinkInputs,
         symbol_maps: &mut SymbolMaps,
+        cache: &mut DelinkCache,
         result: &mut DelinkResult,
     ) -> Result<()> {
         let config_path = self.config_path.parent().unwrap();
 
         for overlay in overlays {
             let module_kind = ModuleKind::Overlay(overlay.id);
-            let delinks = Delinks::from_file(config_path.join(&overlay.module.delinks), module_kind)?;
-            let symbol_map = symbol_maps.get_mut(module_kind);
+            let mut delinks = Delinks::from_file(config_path.join(&overlay.module.delinks), module_kind)?;
             let relocations = Relocations::from_file(config_path.join(&overlay.module.relocations))?;
+            delinks.assign_exception_tables(&relocations)?;
+            let symbol_map = symbol_maps.get_mut(module_kind);
+            delinks.exclude_veneers(symbol_map)?;
+            let hints = Hints::from_optional_file(overlay.module.hints.as_ref().map(|hints| config_path.join(hints)))?;
 
             let code = rom.arm9_overlays()[overlay.id as usize].code();
+            let cache_path = AnalysisCache::path(&inputs.analysis_path, &overlay.module.name);
             let module = Module::new_overlay(
                 overlay.module.name.clone(),
                 symbol_map,
                 relocations,
+                hints,
                 delinks.sections,
                 overlay.id,
                 &code,
+                Some(&cache_path),
             )?;
 
-            for file in &delinks.files {
+            Self::delink_files(&module, &delinks.files, inputs, symbol_maps, cache, result)?;
+        }
+
+        Ok(())
+    }
+
+    fn delink_files(
+        module: &Module,
+        files: &[DelinkFile],
+        inputs: &DelinkInputs,
+        symbol_maps: &SymbolMaps,
+        cache: &mut DelinkCache,
+        result: &mut DelinkResult,
+    ) -> Result<()> {
+        // Objects are independent of each other, collecting keeps the results in delinks order
+        let delinked = files
+            .par_iter()
+            .map(|file| {
                 let (file_path, _) = file.split_file_ext();
-                Self::create_elf_file(&module, file, elf_path.join(format!("{file_path}.o")), &symbol_maps)?;
+                let object_path = format!("{file_path}.o");
+                let path = inputs.elf_path.join(&object_path);
 
-                if file.gap() {
-                    result.num_gaps += 1;
-                } else {
-                    result.num_files += 1;
+                let hash = Self::hash_inputs(symbol_maps, module, file, &inputs.types)?;
+                let up_to_date = cache.is_up_to_date(&object_path, hash) && path.exists();
+                if !up_to_date {
+                    Self::create_elf_file(module, file, path, symbol_maps, &inputs.types)?;
                 }
+                Ok((object_path, hash, up_to_date))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for ((object_path, hash, up_to_date), file) in delinked.into_iter().zip(files) {
+            cache.objects.insert(object_path, hash);
+            if up_to_date {
+                result.num_up_to_date += 1;
+            }
+            if file.gap() {
+                result.num_gaps += 1;
+            } else {
+                result.num_files += 1;
             }
         }
-
         Ok(())
     }
 
+    /// Hashes everything that [`Self::delink`] reads to create the object: section code, symbols, relocations and
+    /// external symbols.
+    fn hash_inputs(symbol_maps: &SymbolMaps, module: &Module, delink_file: &DelinkFile, types: &Types) -> Result<u64> {
+        let symbol_map = symbol_maps.get(module.kind()).unwrap();
+        let mut hasher = FxHasher64::default();
+
+        for file_section in delink_file.sections.iter() {
+            file_section.to_string().hash(&mut hasher);
+            file_section.relocatable_code(module)?.hash(&mut hasher);
+
+            let mut symbols = symbol_map.iter_by_address(file_section.address_range()).peekable();
+            while let Some(symbol) = symbols.next() {
+                let max_address = symbols.peek().map(|s| s.addr).unwrap_or(file_section.end_address());
+                symbol.to_string().hash(&mut hasher);
+                symbol.size(max_address, types).hash(&mut hasher);
+            }
+
+            for (_, relocation) in module.relocations().iter_range(file_section.address_range()) {
+                relocation.to_string().hash(&mut hasher);
+
+                // External symbols are referenced by name and must not be local, so renaming them or changing their scope
+                // must invalidate this object
+                let dest_addr = relocation.to_address();
+                let Some(reloc_module) = relocation.module().first_module() else { continue };
+                let Some(external_symbol_map) = symbol_maps.get(reloc_module) else { continue };
+                if let Some((_, symbol)) = external_symbol_map.by_address(dest_addr)? {
+                    symbol.to_string().hash(&mut hasher);
+                } else if let Some((_, symbol)) = external_symbol_map.get_function(dest_addr)? {
+                    symbol.to_string().hash(&mut hasher);
+                }
+            }
+        }
+
+        Ok(hasher.finish())
+    }
+
     fn create_elf_file<P: AsRef<Path>>(
         module: &Module,
         delink_file: &DelinkFile,
         path: P,
         symbol_maps: &SymbolMaps,
+        types: &Types,
     ) -> Result<()> {
         let path = path.as_ref();
 
         create_dir_all(path.parent().unwrap())?;
 
-        let object = Self::delink(symbol_maps, module, delink_file)?;
+        let object = Self::delink(symbol_maps, module, delink_file, types)?;
         let file = create_file(path)?;
         let writer = BufWriter::new(file);
         object.write_stream(writer).unwrap();
@@ -197,7 +334,12 @@ impl Delink {
         Ok(())
     }
 
-    fn delink<'a>(symbol_maps: &SymbolMaps, module: &Module, delink_file: &DelinkFile) -> Result<object::write::Object<'a>> {
+    fn delink<'a>(
+        symbol_maps: &SymbolMaps,
+        module: &Module,
+        delink_file: &DelinkFile,
+        types: &Types,
+    ) -> Result<object::write::Object<'a>> {
         let symbol_map = symbol_maps.get(module.kind()).unwrap();
         let mut object = object::write::Object::new(BinaryFormat::Elf, Architecture::Arm, Endianness::Little);
         object.elf_is_rela = Some(true);
@@ -216,6 +358,7 @@ impl Delink {
                 SectionKind::Code => object::SectionKind::Text,
                 SectionKind::Data => object::SectionKind::Data, // TODO: use ReadOnlyData if .rodata?
                 SectionKind::Bss => object::SectionKind::UninitializedData,
+                SectionKind::Exception | SectionKind::ExceptionIndex => object::SectionKind::ReadOnlyData,
             };
 
             // Create section
@@ -245,7 +388,7 @@ impl Delink {
                 // Get symbol data
                 let max_address = symbols.peek().map(|s| s.addr).unwrap_or(file_section.end_address());
                 let kind = symbol.kind.into_obj_symbol_kind();
-                let scope = symbol.kind.into_obj_symbol_scope();
+                let (scope, weak) = symbol.obj_scope();
                 let value = (symbol.addr - file_section.start_address()) as u64;
 
                 // Create symbol
@@ -253,10 +396,10 @@ impl Delink {
                 let symbol_id = object.add_symbol(object::write::Symbol {
                     name: symbol.name.clone().into_bytes(),
                     value,
-                    size: symbol.size(max_address) as u64,
+                    size: symbol.size(max_address, types) as u64,
                     kind,
                     scope,
-                    weak: false,
+                    weak,
                     section: symbol_section,
                     flags: object::SymbolFlags::None,
                 });
@@ -299,7 +442,16 @@ impl Delink {
                     *obj_symbol_id
                 } else {
                     // Get external symbol data
-                    let external_symbol_map = symbol_maps.get(reloc_module).unwrap();
+                    let Some(external_symbol_map) = symbol_maps.get(reloc_module) else {
+                        log::error!(
+                            "Relocation from 0x{:08x} in {} points to {}, which is not in the config",
+                            relocation.from_address(),
+                            module.kind(),
+                            reloc_module
+                        );
+                        error = true;
+                        continue;
+                    };
                     let symbol = if let Some((_, symbol)) = external_symbol_map.by_address(dest_addr)? {
                         symbol
                     } else if let Some((_, symbol)) = external_symbol_map.get_function(dest_addr)? {
@@ -315,6 +467,16 @@ impl Delink {
                         error = true;
                         continue;
                     };
+                    if symbol.scope == Some(SymScope::Local) {
+                        log::error!(
+                            "Relocation from 0x{:08x} in {} refers to local symbol '{}' in another file",
+                            relocation.from_address(),
+                            module.kind(),
+                            symbol.name
+                        );
+                        error = true;
+                        continue;
+                    }
 
                     // Add external symbol to section
                     let kind = relocation.kind().into_obj_symbol_kind();
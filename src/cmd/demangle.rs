@@ -0,0 +1,84 @@
+use std::{
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::util::{
+    demangle::demangle,
+    io::{create_file, open_file},
+};
+
+/// Demangles mwcc C++ symbol names.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "demangle")]
+pub struct Demangle {
+    /// Names to demangle.
+    #[argp(positional)]
+    pub names: Vec<String>,
+
+    /// Demangle every name in this file, such as a symbols.txt or assembly file.
+    #[argp(option, short = 'f')]
+    pub file: Option<PathBuf>,
+
+    /// Output path for the demangled file, logs it if omitted.
+    #[argp(option, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+impl Demangle {
+    pub fn run(&self) -> Result<()> {
+        if self.names.is_empty() && self.file.is_none() {
+            log::error!("Expected names to demangle or a file");
+            bail!("Expected names to demangle or a file");
+        }
+
+        for name in &self.names {
+            match demangle(name) {
+                Some(demangled) => log::info!("{demangled}"),
+                None => log::info!("{name}"),
+            }
+        }
+
+        if let Some(file) = &self.file {
+            let reader = BufReader::new(open_file(file)?);
+            let mut writer = self.output.as_ref().map(create_file).transpose()?.map(BufWriter::new);
+            for line in reader.lines() {
+                let line = Self::demangle_line(&line?);
+                match &mut writer {
+                    Some(writer) => writeln!(writer, "{line}")?,
+                    None => log::info!("{line}"),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces each mangled name in the line, names are separated by whitespace or punctuation which is not part of the
+    /// mangling scheme.
+    fn demangle_line(line: &str) -> String {
+        let is_name_char = |c: char| c.is_ascii_alphanumeric() || "_<>,@$".contains(c);
+
+        let mut result = String::with_capacity(line.len());
+        let mut rest = line;
+        while !rest.is_empty() {
+            let name_length = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+            if name_length == 0 {
+                let c = rest.chars().next().unwrap();
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+            let (name, after) = rest.split_at(name_length);
+            match demangle(name) {
+                Some(demangled) => result.push_str(&demangled),
+                None => result.push_str(name),
+            }
+            rest = after;
+        }
+        result
+    }
+}
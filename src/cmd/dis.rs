@@ -9,15 +9,18 @@ use argp::FromArgs;
 use ds_rom::rom::{raw::AutoloadKind, Rom, RomLoadOptions};
 
 use crate::{
+    analysis::cache::AnalysisCache,
     config::{
         config::{Config, ConfigAutoload, ConfigModule, ConfigOverlay},
         delinks::{DelinkFile, Delinks},
+        hints::Hints,
         module::{Module, ModuleKind},
         relocation::Relocations,
         section::Section,
         symbol::{Symbol, SymbolKind, SymbolLookup, SymbolMaps},
+        types::Types,
     },
-    util::io::{create_file, open_file, read_file},
+    util::io::{create_file, read_file},
 };
 
 /// Disassembles an extracted ROM.
@@ -35,7 +38,7 @@ pub struct Disassemble {
 
 impl Disassemble {
     pub fn run(&self) -> Result<()> {
-        let config: Config = serde_yml::from_reader(open_file(&self.config_path)?)?;
+        let config = Config::from_file(&self.config_path)?;
         let config_path = self.config_path.parent().unwrap();
 
         let rom_paths_path = config_path.join(&config.rom_config);
@@ -43,14 +46,16 @@ impl Disassemble {
             Rom::load(&rom_paths_path, RomLoadOptions { key: None, compress: false, encrypt: false, load_files: false })?;
         let extract_path = rom_paths_path.parent().unwrap();
 
-        let mut symbol_maps = SymbolMaps::from_config(config_path, &config)?;
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        let mut symbol_maps = SymbolMaps::from_config(config_path, &config, &types)?;
+        let analysis_path = config_path.join(&config.build_path).join("analysis");
 
-        self.disassemble_arm9(&config.main_module, &mut symbol_maps, &rom, &extract_path)?;
-        self.disassemble_autoloads(&config.autoloads, &mut symbol_maps, &rom, &extract_path)?;
+        self.disassemble_arm9(&config.main_module, &mut symbol_maps, &rom, &extract_path, &analysis_path, &types)?;
+        self.disassemble_autoloads(&config.autoloads, &mut symbol_maps, &rom, &extract_path, &analysis_path, &types)?;
         if let Some(arm9_overlays) = &rom.config().arm9_overlays {
             let overlays_path = extract_path.join(&arm9_overlays);
             let overlays_path = overlays_path.parent().unwrap();
-            self.disassemble_overlays(&config.overlays, &mut symbol_maps, overlays_path)?;
+            self.disassemble_overlays(&config.overlays, &mut symbol_maps, overlays_path, &analysis_path, &types)?;
         }
 
         Ok(())
@@ -62,16 +67,23 @@ impl Disassemble {
         symbol_maps: &mut SymbolMaps,
         rom: &Rom,
         extract_path: &Path,
+        analysis_path: &Path,
+        types: &Types,
     ) -> Result<()> {
         let config_path = self.config_path.parent().unwrap();
 
         let module_kind = ModuleKind::Arm9;
-        let delinks = Delinks::from_file(config_path.join(&config.delinks), module_kind)?;
-        let symbol_map = symbol_maps.get_mut(module_kind);
+        let mut delinks = Delinks::from_file(config_path.join(&config.delinks), module_kind)?;
         let relocations = Relocations::from_file(config_path.join(&config.relocations))?;
+        delinks.assign_exception_tables(&relocations)?;
+        let symbol_map = symbol_maps.get_mut(module_kind);
+        delinks.exclude_veneers(symbol_map)?;
+        let hints = Hints::from_optional_file(config.hints.as_ref().map(|hints| config_path.join(hints)))?;
 
         let code = read_file(extract_path.join(&rom.config().arm9_bin))?;
-        let module = Module::new_arm9(config.name.clone(), symbol_map, relocations, delinks.sections, &code)?;
+        let cache_path = AnalysisCache::path(analysis_path, &config.name);
+        let module =
+            Module::new_arm9(config.name.clone(), symbol_map, relocations, hints, delinks.sections, &code, Some(&cache_path))?;
 
         for file in &delinks.files {
             let (file_path, _) = file.split_file_ext();
@@ -80,6 +92,7 @@ impl Disassemble {
                 file,
                 self.asm_path.join(format!("{}/{file_path}.s", config.name)),
                 &symbol_maps,
+                types,
             )?;
         }
 
@@ -92,14 +105,19 @@ impl Disassemble {
         symbol_maps: &mut SymbolMaps,
         rom: &Rom,
         extract_path: &Path,
+        analysis_path: &Path,
+        types: &Types,
     ) -> Result<()> {
         for autoload in autoloads {
             let config_path = self.config_path.parent().unwrap();
 
             let module_kind = ModuleKind::Autoload(autoload.kind);
-            let delinks = Delinks::from_file(config_path.join(&autoload.module.delinks), module_kind)?;
-            let symbol_map = symbol_maps.get_mut(module_kind);
+            let mut delinks = Delinks::from_file(config_path.join(&autoload.module.delinks), module_kind)?;
             let relocations = Relocations::from_file(config_path.join(&autoload.module.relocations))?;
+            delinks.assign_exception_tables(&relocations)?;
+            let symbol_map = symbol_maps.get_mut(module_kind);
+            delinks.exclude_veneers(symbol_map)?;
+            let hints = Hints::from_optional_file(autoload.module.hints.as_ref().map(|hints| config_path.join(hints)))?;
 
             let autoload_path = match autoload.kind {
                 AutoloadKind::Itcm => &rom.config().itcm.bin,
@@ -108,13 +126,16 @@ impl Disassemble {
             };
 
             let code = read_file(extract_path.join(autoload_path))?;
+            let cache_path = AnalysisCache::path(analysis_path, &autoload.module.name);
             let module = Module::new_autoload(
                 autoload.module.name.clone(),
                 symbol_map,
                 relocations,
+                hints,
                 delinks.sections,
                 autoload.kind,
                 &code,
+                Some(&cache_path),
             )?;
 
             for file in &delinks.files {
@@ -124,6 +145,7 @@ impl Disassemble {
                     file,
                     self.asm_path.join(format!("{}/{file_path}.s", autoload.module.name)),
                     &symbol_maps,
+                    types,
                 )?;
             }
         }
@@ -136,23 +158,31 @@ impl Disassemble {
         overlays: &[ConfigOverlay],
         symbol_maps: &mut SymbolMaps,
         overlays_path: &Path,
+        analysis_path: &Path,
+        types: &Types,
     ) -> Result<()> {
         let config_path = self.config_path.parent().unwrap();
 
         for overlay in overlays {
             let module_kind = ModuleKind::Overlay(overlay.id);
-            let delinks = Delinks::from_file(config_path.join(&overlay.module.delinks), module_kind)?;
-            let symbol_map = symbol_maps.get_mut(module_kind);
+            let mut delinks = Delinks::from_file(config_path.join(&overlay.module.delinks), module_kind)?;
             let relocations = Relocations::from_file(config_path.join(&overlay.module.relocations))?;
+            delinks.assign_exception_tables(&relocations)?;
+            let symbol_map = symbol_maps.get_mut(module_kind);
+            delinks.exclude_veneers(symbol_map)?;
+            let hints = Hints::from_optional_file(overlay.module.hints.as_ref().map(|hints| config_path.join(hints)))?;
 
             let code = read_file(overlays_path.join(format!("ov{:03}.bin", overlay.id)))?;
+            let cache_path = AnalysisCache::path(analysis_path, &overlay.module.name);
             let module = Module::new_overlay(
                 overlay.module.name.clone(),
                 symbol_map,
                 relocations,
+                hints,
                 delinks.sections,
                 overlay.id,
                 &code,
+                Some(&cache_path),
             )?;
 
             for file in &delinks.files {
@@ -162,6 +192,7 @@ impl Disassemble {
                     file,
                     self.asm_path.join(format!("{}/{file_path}.s", overlay.module.name)),
                     &symbol_maps,
+                    types,
                 )?;
             }
         }
@@ -174,6 +205,7 @@ impl Disassemble {
         delink_file: &DelinkFile,
         path: P,
         symbol_maps: &SymbolMaps,
+        types: &Types,
     ) -> Result<()> {
         let path = path.as_ref();
 
@@ -181,7 +213,7 @@ impl Disassemble {
         let asm_file = create_file(&path)?;
         let mut writer = BufWriter::new(asm_file);
 
-        Self::disassemble(module, delink_file, &mut writer, symbol_maps)?;
+        Self::disassemble(module, delink_file, &mut writer, symbol_maps, types)?;
 
         Ok(())
     }
@@ -191,6 +223,7 @@ impl Disassemble {
         delink_file: &DelinkFile,
         writer: &mut BufWriter<File>,
         symbol_maps: &SymbolMaps,
+        types: &Types,
     ) -> Result<()> {
         writeln!(writer, "    .include \"macros/function.inc\"")?;
         writeln!(writer)?;
@@ -208,12 +241,12 @@ impl Disassemble {
             let mut offset = 0; // offset within section
 
             let symbol_lookup =
-                SymbolLookup { module_kind: module.kind(), symbol_map, symbol_maps, relocations: module.relocations() };
+                SymbolLookup { module_kind: module.kind(), symbol_map, symbol_maps, relocations: module.relocations(), types };
 
             let mut symbol_iter = symbol_map.iter_by_address(section.address_range()).peekable();
             while let Some(symbol) = symbol_iter.next() {
                 debug_assert!(symbol.addr >= section.start_address() && symbol.addr < section.end_address());
-                match symbol.kind {
+                match &symbol.kind {
                     SymbolKind::Function(sym_function) => {
                         let function = module.get_function(symbol.addr - sym_function.offset).unwrap();
 
@@ -229,11 +262,13 @@ impl Disassemble {
                     SymbolKind::Data(data) => {
                         let start = (symbol.addr - section.start_address()) as usize;
 
-                        let size =
-                            data.size().unwrap_or_else(|| Self::size_to_next_symbol(section, symbol, symbol_iter.peek()));
+                        let max_size = Self::size_to_next_symbol(section, symbol, symbol_iter.peek());
+                        let data_size = data.size(types).unwrap_or(max_size);
+                        let size = Self::explicit_size(symbol, max_size).unwrap_or(data_size);
 
                         let end = start + size as usize;
-                        let bytes = &code.unwrap()[start..end];
+                        let data_end = start + data_size.min(size) as usize;
+                        let bytes = &code.unwrap()[start..data_end];
                         write!(writer, "{}:", symbol.name)?;
 
                         if symbol.ambiguous {
@@ -242,10 +277,15 @@ impl Disassemble {
                         writeln!(writer)?;
 
                         data.write_assembly(writer, symbol, bytes, &symbol_lookup)?;
+                        if data_end < end {
+                            // Padding covered by the explicit size
+                            Self::dump_bytes(code.unwrap(), data_end as u32, end as u32, writer)?;
+                        }
                         offset = end as u32;
                     }
                     SymbolKind::Bss(bss) => {
-                        let size = bss.size.unwrap_or_else(|| Self::size_to_next_symbol(section, symbol, symbol_iter.peek()));
+                        let max_size = Self::size_to_next_symbol(section, symbol, symbol_iter.peek());
+                        let size = Self::explicit_size(symbol, max_size).or(bss.size).unwrap_or(max_size);
                         writeln!(writer, "{}: .space {:#x}", symbol.name, size)?;
                         offset += size;
                     }
@@ -275,6 +315,16 @@ impl Disassemble {
         }
     }
 
+    /// Returns the `size` attribute of the symbol, limited to the space before the next symbol.
+    fn explicit_size(symbol: &Symbol, max_size: u32) -> Option<u32> {
+        let size = symbol.size?;
+        if size > max_size {
+            log::warn!("Symbol '{}' has size {size:#x} but the next symbol is {max_size:#x} bytes after it", symbol.name);
+            return Some(max_size);
+        }
+        Some(size)
+    }
+
     fn dump_bytes(code: &[u8], mut offset: u32, end_offset: u32, writer: &mut BufWriter<File>) -> Result<()> {
         while offset < end_offset {
             write!(writer, "    .byte ")?;
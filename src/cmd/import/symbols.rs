@@ -6,8 +6,8 @@ use ds_rom::rom::raw::AutoloadKind;
 use object::{Object, ObjectSection, ObjectSymbol};
 
 use crate::{
-    config::{config::Config, module::ModuleKind, symbol::SymbolMaps},
-    util::io::{open_file, read_file},
+    config::{config::Config, module::ModuleKind, symbol::SymbolMaps, types::Types},
+    util::io::read_file,
 };
 
 /// Imports symbol names from a previously built ELF file.
@@ -33,10 +33,11 @@ pub struct ImportSymbols {
 
 impl ImportSymbols {
     pub fn run(&self) -> Result<()> {
-        let config: Config = serde_yml::from_reader(open_file(&self.config_path)?)?;
+        let config = Config::from_file(&self.config_path)?;
         let config_path = self.config_path.parent().unwrap();
 
-        let mut symbol_maps = SymbolMaps::from_config(config_path, &config)?;
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        let mut symbol_maps = SymbolMaps::from_config(config_path, &config, &types)?;
 
         let file = read_file(&self.elf_path)?;
         let object = object::File::parse(&*file)?;
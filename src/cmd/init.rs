@@ -7,11 +7,14 @@ use path_slash::PathBufExt;
 use pathdiff::diff_paths;
 
 use crate::{
+    analysis::overlay_loads::OverlayLoader,
     config::{
-        config::{Config, ConfigAutoload, ConfigModule, ConfigOverlay},
+        config::{Config, ConfigAutoload, ConfigModule, ConfigOverlay, CONFIG_VERSION},
         delinks::Delinks,
+        hints::Hints,
         module::{Module, ModuleKind},
-        program::Program,
+        program::{AnalysisOptions, Program},
+        relocation::Relocations,
         symbol::SymbolMaps,
     },
     util::io::{create_dir_all, create_file, open_file},
@@ -36,6 +39,14 @@ pub struct Init {
     /// Path to build directory.
     #[argp(option, short = 'b')]
     pub build_path: PathBuf,
+
+    /// Relocate conditional calls, and restore their condition codes after linking.
+    #[argp(switch)]
+    pub conditional_calls: bool,
+
+    /// Overlay loader function to find overlay loads with, by address or `signature:` followed by its first bytes in hex.
+    #[argp(option)]
+    pub overlay_loader: Option<String>,
 }
 
 impl Init {
@@ -49,23 +60,21 @@ impl Init {
         let arm9_overlays_output_path = arm9_output_path.join("overlays");
         let arm9_config_path = arm9_output_path.join("config.yaml");
 
-        let mut symbol_maps = SymbolMaps::new();
-
-        let main = Module::analyze_arm9(rom.arm9(), &mut symbol_maps)?;
-        let overlays =
-            rom.arm9_overlays().iter().map(|ov| Module::analyze_overlay(ov, &mut symbol_maps)).collect::<Result<Vec<_>>>()?;
         let autoloads = rom.arm9().autoloads()?;
-        let autoloads = autoloads
-            .iter()
-            .map(|autoload| match autoload.kind() {
-                AutoloadKind::Itcm => Module::analyze_itcm(autoload, &mut symbol_maps),
-                AutoloadKind::Dtcm => Module::analyze_dtcm(autoload, &mut symbol_maps),
-                AutoloadKind::Unknown(_) => bail!("unknown autoload kind"),
-            })
-            .collect::<Result<Vec<_>>>()?;
-
-        let mut program = Program::new(main, overlays, autoloads, symbol_maps);
-        program.analyze_cross_references()?;
+        // There are no symbols files yet, so the loader can't be given by name
+        let overlay_loader =
+            self.overlay_loader.as_deref().map(|loader| OverlayLoader::parse(loader, |_| Ok(None))).transpose()?;
+        let options = AnalysisOptions { conditional_calls: self.conditional_calls, overlay_loader };
+        let program = Program::analyze(rom.arm9(), rom.arm9_overlays(), &autoloads, &options, |kind| {
+            let module_path = match kind {
+                ModuleKind::Arm9 => arm9_output_path.clone(),
+                ModuleKind::Overlay(id) => arm9_overlays_output_path.join(format!("ov{:03}", id)),
+                ModuleKind::Autoload(AutoloadKind::Itcm) => arm9_output_path.join("itcm"),
+                ModuleKind::Autoload(AutoloadKind::Dtcm) => arm9_output_path.join("dtcm"),
+                ModuleKind::Autoload(AutoloadKind::Unknown(_)) => bail!("unknown autoload kind"),
+            };
+            Hints::from_optional_file(Self::hints_path(&module_path))
+        })?;
 
         // Generate configs
         let mut rom_config: RomConfig = serde_yml::from_reader(open_file(&self.rom_config)?)?;
@@ -84,7 +93,7 @@ impl Init {
         )?;
         let autoload_configs =
             self.autoload_configs(&arm9_output_path, &rom_config, program.autoloads(), program.symbol_maps())?;
-        let arm9_config = self.arm9_config(
+        let mut arm9_config = self.arm9_config(
             &arm9_output_path,
             &rom_config,
             program.main(),
@@ -93,6 +102,14 @@ impl Init {
             program.symbol_maps(),
         )?;
 
+        if self.overlay_loader.is_some() {
+            let overlay_loads_path = arm9_output_path.join("overlay_loads.txt");
+            if !self.dry {
+                program.overlay_loads().to_file(&overlay_loads_path)?;
+            }
+            arm9_config.overlay_loads = Some(Self::make_path(overlay_loads_path, &arm9_output_path));
+        }
+
         if !self.dry {
             create_dir_all(&arm9_output_path)?;
             serde_yml::to_writer(create_file(arm9_config_path)?, &arm9_config)?;
@@ -105,6 +122,12 @@ impl Init {
         PathBuf::from(diff_paths(path, &base).unwrap().to_slash_lossy().as_ref())
     }
 
+    /// Returns the path to the hints file in a module's config directory, if the user has created one.
+    fn hints_path(module_path: &Path) -> Option<PathBuf> {
+        let path = module_path.join("hints.txt");
+        path.exists().then_some(path)
+    }
+
     fn arm9_config(
         &self,
         path: &Path,
@@ -121,15 +144,23 @@ impl Init {
         let relocations_path = path.join("relocs.txt");
 
         if !self.dry {
-            Delinks::to_file(&delinks_path, module.sections())?;
+            Delinks::to_file(&delinks_path, module.sections(), &[])?;
             symbol_maps.get(module.kind()).unwrap().to_file(&symbols_path)?;
             module.relocations().to_file(&relocations_path)?;
+            module.relocations().to_file(Relocations::baseline_path(&relocations_path))?;
         }
 
         Ok(Config {
+            version: CONFIG_VERSION,
             rom_config: Self::make_path(&self.rom_config, path),
             build_path: Self::make_path(&self.build_path, path),
             delinks_path: Self::make_path(&self.build_path.join("delinks"), path),
+            conditional_calls: self.conditional_calls,
+            overlay_loader: self.overlay_loader.clone(),
+            overlay_loads: None,
+            types: None,
+            rename_log: None,
+            source_roots: vec![],
             main_module: ConfigModule {
                 name: "main".to_string(),
                 object: Self::make_path(&rom_config.arm9_bin, path),
@@ -137,6 +168,7 @@ impl Init {
                 delinks: Self::make_path(delinks_path, path),
                 symbols: Self::make_path(symbols_path, path),
                 relocations: Self::make_path(relocations_path, path),
+                hints: Self::hints_path(path).map(|hints_path| Self::make_path(hints_path, path)),
             },
             autoloads,
             overlays,
@@ -174,9 +206,10 @@ impl Init {
             let relocs_path = autoload_path.join("relocs.txt");
 
             if !self.dry {
-                Delinks::to_file(&delinks_path, module.sections())?;
+                Delinks::to_file(&delinks_path, module.sections(), &[])?;
                 symbol_maps.get(module.kind()).unwrap().to_file(&symbols_path)?;
                 module.relocations().to_file(&relocs_path)?;
+                module.relocations().to_file(Relocations::baseline_path(&relocs_path))?;
             }
 
             autoloads.push(ConfigAutoload {
@@ -187,6 +220,7 @@ impl Init {
                     delinks: Self::make_path(delinks_path, path),
                     symbols: Self::make_path(symbols_path, path),
                     relocations: Self::make_path(relocs_path, path),
+                    hints: Self::hints_path(&autoload_path).map(|hints_path| Self::make_path(hints_path, path)),
                 },
                 kind,
             })
@@ -222,9 +256,10 @@ impl Init {
             let relocs_path = overlay_config_path.join("relocs.txt");
 
             if !self.dry {
-                Delinks::to_file(&delinks_path, module.sections())?;
+                Delinks::to_file(&delinks_path, module.sections(), &[])?;
                 symbol_maps.get(module.kind()).unwrap().to_file(&symbols_path)?;
                 module.relocations().to_file(&relocs_path)?;
+                module.relocations().to_file(Relocations::baseline_path(&relocs_path))?;
             }
 
             overlays.push(ConfigOverlay {
@@ -235,8 +270,10 @@ impl Init {
                     delinks: Self::make_path(delinks_path, root),
                     symbols: Self::make_path(symbols_path, root),
                     relocations: Self::make_path(relocs_path, root),
+                    hints: Self::hints_path(&overlay_config_path).map(|hints_path| Self::make_path(hints_path, root)),
                 },
                 id,
+                category: None,
             });
         }
 
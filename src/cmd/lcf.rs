@@ -12,12 +12,11 @@ use ds_rom::rom::{raw::AutoloadKind, Rom, RomLoadOptions};
 use crate::{
     analysis::overlay_groups::OverlayGroups,
     config::{
-        config::{Config, ConfigModule},
-        delinks::Delinks,
-        module::ModuleKind,
+        config::Config, delinks::Delinks, module::ModuleKind, relocation::Relocations, section::SectionKind,
+        symbol::SymbolMap, types::Types,
     },
     util::{
-        io::{create_dir_all, create_file_and_dirs, open_file},
+        io::{create_dir_all, create_file_and_dirs},
         path::PathExt,
     },
 };
@@ -41,7 +40,7 @@ pub struct Lcf {
 
 impl Lcf {
     pub fn run(&self) -> Result<()> {
-        let config: Config = serde_yml::from_reader(open_file(&self.config_path)?)?;
+        let config = Config::from_file(&self.config_path)?;
         let config_dir = self.config_path.parent().unwrap();
 
         let rom = Rom::load(
@@ -76,29 +75,18 @@ impl Lcf {
         build_path: &Path,
         delinks_path: &Path,
     ) -> Result<(), anyhow::Error> {
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_dir.join(types)))?;
+
+        let mut modules = vec![(&config.main_module, ModuleKind::Arm9)];
+        modules.extend(config.autoloads.iter().map(|autoload| (&autoload.module, ModuleKind::Autoload(autoload.kind))));
+        modules.extend(config.overlays.iter().map(|overlay| (&overlay.module, ModuleKind::Overlay(overlay.id))));
+
         writeln!(lcf, "SECTIONS {{")?;
-        self.write_module_section(lcf, objects, config_dir, &config.main_module, ModuleKind::Arm9, build_path, delinks_path)?;
-        for autoload in &config.autoloads {
-            self.write_module_section(
-                lcf,
-                objects,
-                config_dir,
-                &autoload.module,
-                ModuleKind::Autoload(autoload.kind),
-                build_path,
-                delinks_path,
-            )?;
-        }
-        for overlay in &config.overlays {
-            self.write_module_section(
-                lcf,
-                objects,
-                config_dir,
-                &overlay.module,
-                ModuleKind::Overlay(overlay.id),
-                build_path,
-                delinks_path,
-            )?;
+        for (module, module_kind) in modules {
+            let mut delinks = Delinks::from_file(config_dir.join(&module.delinks), module_kind)?;
+            delinks.assign_exception_tables(&Relocations::from_file(config_dir.join(&module.relocations))?)?;
+            delinks.exclude_veneers(&SymbolMap::from_file(config_dir.join(&module.symbols), &types)?)?;
+            self.write_module_section(lcf, objects, &delinks, module_kind, build_path, delinks_path)?;
         }
         writeln!(lcf, "}}\n")?;
         Ok(())
@@ -107,7 +95,9 @@ impl Lcf {
     fn write_keep_section_section(&self, lcf: &mut BufWriter<File>) -> Result<()> {
         writeln!(lcf, "KEEP_SECTION {{")?;
         writeln!(lcf, "    .init,")?;
-        writeln!(lcf, "    .ctor")?;
+        writeln!(lcf, "    .ctor,")?;
+        writeln!(lcf, "    .exception,")?;
+        writeln!(lcf, "    .exceptix")?;
         writeln!(lcf, "}}\n")?;
         Ok(())
     }
@@ -177,8 +167,7 @@ impl Lcf {
         &self,
         lcf: &mut BufWriter<File>,
         objects: &mut BufWriter<File>,
-        config_dir: &Path,
-        module: &ConfigModule,
+        delinks: &Delinks,
         module_kind: ModuleKind,
         build_path: &Path,
         delinks_path: &Path,
@@ -192,11 +181,15 @@ impl Lcf {
         };
 
         writeln!(lcf, "    {module_name} : {{")?;
-        let delinks = Delinks::from_file(config_dir.join(&module.delinks), module_kind)?;
         for section in delinks.sections.sorted_by_address() {
             writeln!(lcf, "        . = ALIGN({});", section.alignment())?;
             let section_boundary_name = section.boundary_name();
             writeln!(lcf, "        {memory_name}_{section_boundary_name}_START = .;")?;
+            let is_exception_index = module_kind == ModuleKind::Arm9 && section.kind() == SectionKind::ExceptionIndex;
+            if is_exception_index {
+                // Used by the runtime to look up exception tables
+                writeln!(lcf, "        __exception_table_start__ = .;")?;
+            }
             for file in &delinks.files {
                 if file.sections.by_name(section.name()).is_none() {
                     continue;
@@ -205,6 +198,9 @@ impl Lcf {
                 let (_, file_name) = file_path.rsplit_once('/').unwrap_or(("", &file_path));
                 writeln!(lcf, "        {file_name}.o({})", section.name())?;
             }
+            if is_exception_index {
+                writeln!(lcf, "        __exception_table_end__ = .;")?;
+            }
             writeln!(lcf, "        {memory_name}_{section_boundary_name}_END = .;")?;
         }
         writeln!(lcf, "    }} > {memory_name}\n")?;
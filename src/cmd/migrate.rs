@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::{
+    config::{
+        config::{Config, ConfigModule, CONFIG_VERSION},
+        delinks::Delinks,
+        module::ModuleKind,
+        overlay_loads::OverlayLoads,
+        relocation::Relocations,
+        symbol::SymbolMap,
+        types::Types,
+    },
+    util::io::create_file,
+};
+
+/// Upgrades a project's config and the files it points to from an older dsd to the current format.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "migrate")]
+pub struct Migrate {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+}
+
+impl Migrate {
+    pub fn run(&self) -> Result<()> {
+        let mut config = Config::from_file_for_migration(&self.config_path)?;
+        let config_path = self.config_path.parent().unwrap();
+
+        if config.version == CONFIG_VERSION {
+            log::info!("Config is already at the current format version {CONFIG_VERSION}");
+            return Ok(());
+        }
+
+        while config.version < CONFIG_VERSION {
+            match config.version {
+                0 => Self::migrate_from_unversioned(&config, config_path)?,
+                version => {
+                    log::error!("No migration from format version {version}");
+                    bail!("No migration from format version {version}");
+                }
+            }
+            config.version += 1;
+            log::info!("Migrated to format version {}", config.version);
+        }
+
+        serde_yml::to_writer(create_file(&self.config_path)?, &config)?;
+        Ok(())
+    }
+
+    /// Configs from before the format was versioned. Formats have only gained attributes since then, which the parsers
+    /// treat as optional, so loading every file and writing it back is enough to bring it up to date.
+    fn migrate_from_unversioned(config: &Config, config_path: &Path) -> Result<()> {
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        Self::rewrite_module(&config.main_module, ModuleKind::Arm9, config_path, &types)?;
+        for autoload in &config.autoloads {
+            Self::rewrite_module(&autoload.module, ModuleKind::Autoload(autoload.kind), config_path, &types)?;
+        }
+        for overlay in &config.overlays {
+            Self::rewrite_module(&overlay.module, ModuleKind::Overlay(overlay.id), config_path, &types)?;
+        }
+        if let Some(overlay_loads) = &config.overlay_loads {
+            let path = config_path.join(overlay_loads);
+            OverlayLoads::from_file(&path)?.to_file(&path)?;
+        }
+        Ok(())
+    }
+
+    fn rewrite_module(module: &ConfigModule, module_kind: ModuleKind, config_path: &Path, types: &Types) -> Result<()> {
+        let delinks_path = config_path.join(&module.delinks);
+        let delinks = Delinks::from_file(&delinks_path, module_kind)?;
+        Delinks::to_file(&delinks_path, &delinks.sections, &delinks.files)?;
+
+        let symbols_path = config_path.join(&module.symbols);
+        SymbolMap::from_file(&symbols_path, types)?.to_file(&symbols_path)?;
+
+        // Overrides stay in their own file, so that they're still applied when the relocations are regenerated
+        let relocations_path = config_path.join(&module.relocations);
+        Relocations::from_file_without_overrides(&relocations_path)?.to_file(&relocations_path)?;
+
+        log::info!("Rewrote the files of {module_kind}");
+        Ok(())
+    }
+}
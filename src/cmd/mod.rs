@@ -1,17 +1,35 @@
 mod check;
+mod convert;
+mod ctx;
 mod delink;
+mod demangle;
 mod dis;
 mod import;
 mod init;
 mod lcf;
+mod migrate;
+mod ninja;
 mod objdiff;
+mod overlay_loads;
+mod reanalyze;
 mod rom;
+mod sym;
+mod validate;
 
 pub use check::*;
+pub use convert::*;
+pub use ctx::*;
 pub use delink::*;
+pub use demangle::*;
 pub use dis::*;
 pub use import::*;
 pub use init::*;
 pub use lcf::*;
+pub use migrate::*;
+pub use ninja::*;
 pub use objdiff::*;
+pub use overlay_loads::*;
+pub use reanalyze::*;
 pub use rom::*;
+pub use sym::*;
+pub use validate::*;
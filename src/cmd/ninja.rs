@@ -0,0 +1,321 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use argp::FromArgs;
+
+use crate::{
+    config::{
+        config::{Config, ConfigModule},
+        delinks::Delinks,
+        module::ModuleKind,
+        relocation::{RelocationOverrides, Relocations},
+        symbol::SymbolMap,
+        types::Types,
+    },
+    util::{io::create_file_and_dirs, path::PathExt},
+};
+
+/// Generates a ninja build file for compiling, delinking, linking and building the ROM.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "ninja")]
+pub struct Ninja {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Path to output ninja file. Defaults to `build.ninja` in the current directory.
+    #[argp(option, short = 'o')]
+    pub output_path: Option<PathBuf>,
+
+    /// Command to run the compiler, e.g. `wine mwccarm.exe`.
+    #[argp(option, short = 'C')]
+    pub compiler: String,
+
+    /// Flags to pass to the compiler, as a single string.
+    #[argp(option, short = 'f', default = "String::new()")]
+    pub c_flags: String,
+
+    /// Command to run the linker, e.g. `wine mwldarm.exe`.
+    #[argp(option, short = 'L')]
+    pub linker: String,
+
+    /// Flags to pass to the linker, as a single string.
+    #[argp(option, short = 'l', default = "String::new()")]
+    pub ld_flags: String,
+
+    /// Command to run dsd. Defaults to `dsd`.
+    #[argp(option, short = 'd', default = "\"dsd\".to_string()")]
+    pub dsd: String,
+
+    /// Path to the ROM file to build.
+    #[argp(option, short = 'r')]
+    pub rom: PathBuf,
+
+    /// Path to ARM7 BIOS file, needed if the ROM must be encrypted.
+    #[argp(option, short = '7')]
+    pub arm7_bios: Option<PathBuf>,
+}
+
+/// Paths to build inputs and outputs, relative to the ninja file.
+struct NinjaPaths {
+    config: String,
+    lcf: String,
+    objects: String,
+    elf: String,
+    rom_config: String,
+    rom: String,
+    arm7_bios: Option<String>,
+}
+
+impl Ninja {
+    pub fn run(&self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)?;
+        let config_dir = self.config_path.parent().unwrap();
+
+        let output_path = self.output_path.clone().unwrap_or(PathBuf::from("build.ninja"));
+        let abs_output_dir = output_path.absolute()?.parent().unwrap().to_path_buf();
+
+        let build_path = config_dir.join(&config.build_path);
+        let delinks_path = config_dir.join(&config.delinks_path);
+        let main_object = config_dir.join(&config.main_module.object);
+
+        let paths = NinjaPaths {
+            config: self.relative(&self.config_path, &abs_output_dir)?,
+            lcf: self.relative(build_path.join("linker_script.lcf"), &abs_output_dir)?,
+            objects: self.relative(build_path.join("objects.txt"), &abs_output_dir)?,
+            elf: self.relative(build_path.join("arm9.o"), &abs_output_dir)?,
+            rom_config: self.relative(main_object.parent().unwrap().join("rom_config.yaml"), &abs_output_dir)?,
+            rom: self.relative(&self.rom, &abs_output_dir)?,
+            arm7_bios: self.arm7_bios.as_ref().map(|arm7_bios| self.relative(arm7_bios, &abs_output_dir)).transpose()?,
+        };
+
+        let mut modules = vec![(&config.main_module, ModuleKind::Arm9)];
+        modules.extend(config.autoloads.iter().map(|autoload| (&autoload.module, ModuleKind::Autoload(autoload.kind))));
+        modules.extend(config.overlays.iter().map(|overlay| (&overlay.module, ModuleKind::Overlay(overlay.id))));
+
+        let file = create_file_and_dirs(&output_path)?;
+        let mut ninja = BufWriter::new(file);
+
+        self.write_variables(&mut ninja, &paths)?;
+        self.write_rules(&mut ninja, &paths)?;
+
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_dir.join(types)))?;
+        let mut config_inputs = vec![paths.config.clone()];
+        if let Some(types) = &config.types {
+            config_inputs.push(self.relative(config_dir.join(types), &abs_output_dir)?);
+        }
+        let mut delink_outputs = vec![self.relative(delinks_path.join("delink.yaml"), &abs_output_dir)?];
+        let mut link_inputs = vec![];
+        let mut module_binaries = vec![];
+        let mut watched_dirs = vec![];
+        for (module, module_kind) in &modules {
+            let mut delinks = Delinks::from_file(config_dir.join(&module.delinks), *module_kind)?;
+            delinks.assign_exception_tables(&Relocations::from_file(config_dir.join(&module.relocations))?)?;
+            delinks.exclude_veneers(&SymbolMap::from_file(config_dir.join(&module.symbols), &types)?)?;
+            config_inputs.extend(self.module_config_files(module, config_dir, &abs_output_dir)?);
+            if let Some(dir) = self.module_watched_dir(module, config_dir, &abs_output_dir)? {
+                if !watched_dirs.contains(&dir) {
+                    watched_dirs.push(dir);
+                }
+            }
+            module_binaries.push(self.relative(config_dir.join(&module.object), &abs_output_dir)?);
+
+            for file in &delinks.files {
+                let (file_path, _) = file.split_file_ext();
+                let delinked_object = self.relative(delinks_path.join(format!("{file_path}.o")), &abs_output_dir)?;
+                delink_outputs.push(delinked_object.clone());
+
+                if file.complete {
+                    let built_object = self.relative(build_path.join(format!("{file_path}.o")), &abs_output_dir)?;
+                    let source = self.relative(&file.name, &abs_output_dir)?;
+                    Self::write_build(&mut ninja, &[built_object.clone()], "cc", &[source], &[])?;
+                    link_inputs.push(built_object);
+                } else {
+                    link_inputs.push(delinked_object);
+                }
+            }
+        }
+        writeln!(ninja)?;
+
+        // Regenerate this file when the config changes, or when an overrides file is created in a module's directory
+        let output_file = output_path.file_name().unwrap().to_string_lossy().to_string();
+        Self::write_build(&mut ninja, &[output_file], "configure", &[], &[&config_inputs[..], &watched_dirs[..]].concat())?;
+        writeln!(ninja)?;
+
+        // Delinking writes all objects at once, but only objects with changed contents get their timestamps updated
+        Self::write_build(&mut ninja, &delink_outputs, "delink", &[], &config_inputs)?;
+        Self::write_build(&mut ninja, &[paths.lcf.clone(), paths.objects.clone()], "lcf", &[], &config_inputs)?;
+        writeln!(ninja)?;
+
+        Self::write_build_with_implicit_outputs(
+            &mut ninja,
+            &[paths.elf.clone()],
+            &module_binaries,
+            "link",
+            &[],
+            &[&link_inputs[..], &[paths.lcf.clone(), paths.objects.clone()]].concat(),
+        )?;
+        Self::write_build(
+            &mut ninja,
+            &[paths.rom_config.clone()],
+            "rom_config",
+            &[paths.elf.clone()],
+            &[paths.config.clone()],
+        )?;
+        Self::write_build(&mut ninja, &[paths.rom.clone()], "rom_build", &[paths.rom_config.clone()], &[])?;
+        Self::write_build(
+            &mut ninja,
+            &["check_modules".to_string()],
+            "check_modules",
+            &[],
+            &[&module_binaries[..], &[paths.rom_config.clone()]].concat(),
+        )?;
+        Self::write_build(&mut ninja, &["check".to_string()], "phony", &["check_modules".to_string()], &[])?;
+        writeln!(ninja)?;
+
+        writeln!(ninja, "default {}", Self::escape(&paths.rom))?;
+
+        Ok(())
+    }
+
+    fn module_config_files(&self, module: &ConfigModule, config_dir: &Path, abs_output_dir: &Path) -> Result<Vec<String>> {
+        let mut files = vec![
+            self.relative(config_dir.join(&module.delinks), abs_output_dir)?,
+            self.relative(config_dir.join(&module.symbols), abs_output_dir)?,
+            self.relative(config_dir.join(&module.relocations), abs_output_dir)?,
+        ];
+        if let Some(hints) = &module.hints {
+            files.push(self.relative(config_dir.join(hints), abs_output_dir)?);
+        }
+        let overrides_path = RelocationOverrides::path(&config_dir.join(&module.relocations));
+        if overrides_path.exists() {
+            files.push(self.relative(overrides_path, abs_output_dir)?);
+        }
+        Ok(files)
+    }
+
+    /// Directory where the user may later create a module's relocation overrides file. Creating a file updates the
+    /// modification time of its directory, which makes ninja regenerate the build file with the new file as an input.
+    fn module_watched_dir(&self, module: &ConfigModule, config_dir: &Path, abs_output_dir: &Path) -> Result<Option<String>> {
+        let relocations_path = config_dir.join(&module.relocations);
+        if RelocationOverrides::path(&relocations_path).exists() {
+            return Ok(None);
+        }
+        let dir = self.relative(relocations_path.parent().unwrap(), abs_output_dir)?;
+        Ok(Some(if dir.is_empty() { ".".to_string() } else { dir }))
+    }
+
+    fn relative<P: AsRef<Path>>(&self, path: P, abs_output_dir: &Path) -> Result<String> {
+        Ok(path.as_ref().normalize_diff_paths(abs_output_dir)?.to_string_lossy().to_string())
+    }
+
+    fn write_variables(&self, ninja: &mut BufWriter<File>, paths: &NinjaPaths) -> Result<()> {
+        writeln!(ninja, "# Generated by dsd, do not edit")?;
+        writeln!(ninja)?;
+        writeln!(ninja, "cc = {}", self.compiler)?;
+        writeln!(ninja, "cflags = {}", self.c_flags)?;
+        writeln!(ninja, "ld = {}", self.linker)?;
+        writeln!(ninja, "ldflags = {}", self.ld_flags)?;
+        writeln!(ninja, "dsd = {}", self.dsd)?;
+        writeln!(ninja, "config = {}", Self::escape(&paths.config))?;
+        writeln!(ninja, "lcf = {}", Self::escape(&paths.lcf))?;
+        writeln!(ninja, "objects = {}", Self::escape(&paths.objects))?;
+        writeln!(ninja)?;
+        Ok(())
+    }
+
+    fn write_rules(&self, ninja: &mut BufWriter<File>, paths: &NinjaPaths) -> Result<()> {
+        let arm7_bios = match &paths.arm7_bios {
+            Some(arm7_bios) => format!(" -7 {}", Self::quote_path(arm7_bios)),
+            None => String::new(),
+        };
+
+        let configure = format!(
+            "$dsd ninja -c $config -o $out -C \"$cc\" -f \"$cflags\" -L \"$ld\" -l \"$ldflags\" -d \"$dsd\" -r {}{arm7_bios}",
+            Self::quote_path(&paths.rom)
+        );
+        Self::write_rule(ninja, "configure", &configure, "Regenerating $out")?;
+        writeln!(ninja, "  generator = 1")?;
+        Self::write_rule(ninja, "cc", "$cc $cflags -c $in -o $out", "Compiling $in")?;
+        Self::write_rule(ninja, "delink", "$dsd delink -c $config", "Delinking")?;
+        writeln!(ninja, "  restat = 1")?;
+        Self::write_rule(ninja, "lcf", "$dsd lcf -c $config -l $lcf -o $objects", "Generating linker script")?;
+        writeln!(ninja, "  restat = 1")?;
+        Self::write_rule(ninja, "link", "$ld $ldflags @$objects $lcf -o $out", "Linking $out")?;
+        Self::write_rule(ninja, "rom_config", "$dsd rom config -e $in -c $config", "Configuring ROM")?;
+        Self::write_rule(ninja, "rom_build", &format!("$dsd rom build -c $in -o $out{arm7_bios}"), "Building ROM $out")?;
+        Self::write_rule(ninja, "check_modules", "$dsd check modules -c $config -f", "Checking modules")?;
+        writeln!(ninja)?;
+        Ok(())
+    }
+
+    fn write_rule(ninja: &mut BufWriter<File>, name: &str, command: &str, description: &str) -> Result<()> {
+        writeln!(ninja, "rule {name}")?;
+        writeln!(ninja, "  command = {command}")?;
+        writeln!(ninja, "  description = {description}")?;
+        Ok(())
+    }
+
+    fn write_build(
+        ninja: &mut BufWriter<File>,
+        outputs: &[String],
+        rule: &str,
+        inputs: &[String],
+        implicit_inputs: &[String],
+    ) -> Result<()> {
+        Self::write_build_with_implicit_outputs(ninja, outputs, &[], rule, inputs, implicit_inputs)
+    }
+
+    fn write_build_with_implicit_outputs(
+        ninja: &mut BufWriter<File>,
+        outputs: &[String],
+        implicit_outputs: &[String],
+        rule: &str,
+        inputs: &[String],
+        implicit_inputs: &[String],
+    ) -> Result<()> {
+        write!(ninja, "build")?;
+        for output in outputs {
+            write!(ninja, " {}", Self::escape(output))?;
+        }
+        if !implicit_outputs.is_empty() {
+            write!(ninja, " |")?;
+            for output in implicit_outputs {
+                write!(ninja, " {}", Self::escape(output))?;
+            }
+        }
+        write!(ninja, ": {rule}")?;
+        for input in inputs {
+            write!(ninja, " {}", Self::escape(input))?;
+        }
+        if !implicit_inputs.is_empty() {
+            write!(ninja, " |")?;
+            for input in implicit_inputs {
+                write!(ninja, " {}", Self::escape(input))?;
+            }
+        }
+        writeln!(ninja)?;
+        Ok(())
+    }
+
+    /// Quotes a path for use as an argument in a rule's command, where only `$` has to be escaped for ninja.
+    fn quote_path(path: &str) -> String {
+        format!("\"{}\"", path.replace('$', "$$"))
+    }
+
+    /// Escapes special characters in paths, see https://ninja-build.org/manual.html#ref_lexer
+    fn escape(path: &str) -> String {
+        let mut escaped = String::with_capacity(path.len());
+        for c in path.chars() {
+            if matches!(c, '$' | ' ' | ':') {
+                escaped.push('$');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+}
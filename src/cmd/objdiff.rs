@@ -1,20 +1,22 @@
 use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use argp::FromArgs;
+use ds_rom::rom::raw::AutoloadKind;
 use globset::Glob;
-use objdiff_core::config::ProjectObject;
+use objdiff_core::config::{ProjectObject, ProjectProgressCategory};
 
 use crate::{
+    cmd::Ctx,
     config::{
         config::{Config, ConfigModule},
         delinks::Delinks,
         module::ModuleKind,
+        relocation::Relocations,
+        symbol::SymbolMap,
+        types::Types,
     },
-    util::{
-        io::{create_dir_all, open_file},
-        path::PathExt,
-    },
+    util::{io::create_dir_all, path::PathExt},
 };
 
 const MIN_OBJDIFF_VERSION: &str = "2.3.2";
@@ -54,31 +56,87 @@ pub struct Objdiff {
 
 impl Objdiff {
     pub fn run(&self) -> Result<()> {
-        let config: Config = serde_yml::from_reader(open_file(&self.config_path)?)?;
+        let config = Config::from_file(&self.config_path)?;
         let config_path = self.config_path.parent().unwrap();
 
         let output_path = self.output_path.clone().unwrap_or(PathBuf::from("."));
         let abs_output_path = std::path::absolute(&output_path)?;
 
         let mut units = vec![];
-        units.extend(self.get_units(&config.main_module, ModuleKind::Arm9, config_path, &config, &abs_output_path)?);
+        let mut progress_categories = vec![];
+
+        let main_category = Self::module_kind_category(ModuleKind::Arm9);
+        units.extend(self.get_units(
+            &config.main_module,
+            ModuleKind::Arm9,
+            config_path,
+            &config,
+            &abs_output_path,
+            vec![main_category.id.clone()],
+        )?);
+        progress_categories.push(main_category);
+
         for autoload in &config.autoloads {
+            let module_kind = ModuleKind::Autoload(autoload.kind);
+            let autoload_category = Self::module_kind_category(module_kind);
             units.extend(self.get_units(
                 &autoload.module,
-                ModuleKind::Autoload(autoload.kind),
+                module_kind,
                 config_path,
                 &config,
                 &abs_output_path,
+                vec![autoload_category.id.clone()],
             )?);
+            // Unknown autoloads share one category
+            if !progress_categories.iter().any(|c: &ProjectProgressCategory| c.id == autoload_category.id) {
+                progress_categories.push(autoload_category);
+            }
         }
-        for overlay in &config.overlays {
-            units.extend(self.get_units(
-                &overlay.module,
-                ModuleKind::Overlay(overlay.id),
-                config_path,
-                &config,
-                &abs_output_path,
-            )?);
+
+        if !config.overlays.is_empty() {
+            let overlays_category = Self::module_kind_category(ModuleKind::Overlay(0));
+
+            // Custom categories must not merge with the built-in ones, including those of overlays without a category
+            let mut builtin_ids = progress_categories.iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+            builtin_ids.push(overlays_category.id.clone());
+            builtin_ids
+                .extend(config.overlays.iter().filter(|o| o.category.is_none()).map(|o| Self::overlay_category_id(o.id)));
+
+            let mut overlay_categories: Vec<ProjectProgressCategory> = vec![];
+            for overlay in &config.overlays {
+                let overlay_category = match &overlay.category {
+                    Some(category) => {
+                        if builtin_ids.contains(category) {
+                            log::error!(
+                                "Category '{category}' of overlay {} is already used by a built-in progress category",
+                                overlay.id
+                            );
+                            bail!(
+                                "Category '{category}' of overlay {} is already used by a built-in progress category",
+                                overlay.id
+                            );
+                        }
+                        ProjectProgressCategory { id: category.clone(), name: category.clone() }
+                    }
+                    None => ProjectProgressCategory {
+                        id: Self::overlay_category_id(overlay.id),
+                        name: format!("Overlay {}", overlay.id),
+                    },
+                };
+                units.extend(self.get_units(
+                    &overlay.module,
+                    ModuleKind::Overlay(overlay.id),
+                    config_path,
+                    &config,
+                    &abs_output_path,
+                    vec![overlays_category.id.clone(), overlay_category.id.clone()],
+                )?);
+                if !overlay_categories.iter().any(|c| c.id == overlay_category.id) {
+                    overlay_categories.push(overlay_category);
+                }
+            }
+            progress_categories.push(overlays_category);
+            progress_categories.extend(overlay_categories);
         }
 
         let target_dir = config_path.join(config.build_path).normalize_diff_paths(&abs_output_path)?;
@@ -103,7 +161,7 @@ impl Objdiff {
                 Glob::new("*.hxx")?,
             ]),
             units: Some(units),
-            progress_categories: None,
+            progress_categories: Some(progress_categories),
         };
 
         create_dir_all(&output_path)?;
@@ -122,13 +180,17 @@ impl Objdiff {
         config_path: &Path,
         config: &Config,
         abs_output_path: &Path,
+        progress_categories: Vec<String>,
     ) -> Result<Vec<ProjectObject>> {
-        let delinks = Delinks::from_file(config_path.join(&module.delinks), module_kind)?;
+        let mut delinks = Delinks::from_file(config_path.join(&module.delinks), module_kind)?;
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        delinks.assign_exception_tables(&Relocations::from_file(config_path.join(&module.relocations))?)?;
+        delinks.exclude_veneers(&SymbolMap::from_file(config_path.join(&module.symbols), &types)?)?;
         Ok(delinks
             .files
             .iter()
             .map(|file| {
-                let (file_path, extension) = file.split_file_ext();
+                let (file_path, _) = file.split_file_ext();
 
                 let target_path = config_path
                     .join(&config.delinks_path)
@@ -149,13 +211,8 @@ impl Objdiff {
                 };
 
                 let scratch = if !file.gap() && self.scratch {
-                    let ctx_extension = if extension.is_empty() { ".ctx".to_string() } else { format!("ctx.{extension}") };
-
-                    let ctx_path = config_path
-                        .join(&config.build_path)
-                        .join(file_path)
-                        .with_extension(ctx_extension)
-                        .normalize_diff_paths(abs_output_path)?;
+                    let ctx_path =
+                        Ctx::path(&config_path.join(&config.build_path), file).normalize_diff_paths(abs_output_path)?;
 
                     Some(objdiff_core::config::ScratchConfig {
                         platform: Some("nds_arm9".to_string()),
@@ -185,7 +242,7 @@ impl Objdiff {
                         complete: Some(file.complete),
                         reverse_fn_order: Some(false),
                         source_path,
-                        progress_categories: None,
+                        progress_categories: Some(progress_categories.clone()),
                         auto_generated: Some(file.gap()),
                     }),
                     ..Default::default()
@@ -193,4 +250,20 @@ impl Objdiff {
             })
             .collect::<Result<Vec<_>>>()?)
     }
+
+    fn overlay_category_id(id: u16) -> String {
+        format!("ov{id:03}")
+    }
+
+    /// Progress category for all units in a given kind of module. All overlays share the same category.
+    fn module_kind_category(module_kind: ModuleKind) -> ProjectProgressCategory {
+        let (id, name) = match module_kind {
+            ModuleKind::Arm9 => ("main", "ARM9 main"),
+            ModuleKind::Overlay(_) => ("overlays", "Overlays"),
+            ModuleKind::Autoload(AutoloadKind::Itcm) => ("itcm", "ITCM"),
+            ModuleKind::Autoload(AutoloadKind::Dtcm) => ("dtcm", "DTCM"),
+            ModuleKind::Autoload(AutoloadKind::Unknown(_)) => ("autoload", "Autoload"),
+        };
+        ProjectProgressCategory { id: id.to_string(), name: name.to_string() }
+    }
 }
@@ -0,0 +1,73 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::config::{
+    config::Config,
+    module::ModuleKind,
+    overlay_loads::{OverlayLoad, OverlayLoads},
+};
+
+/// Lists which modules load which overlays.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "overlay-loads")]
+pub struct OverlayLoadsArgs {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Only show loads of and by this overlay.
+    #[argp(option, short = 'o')]
+    pub overlay: Option<u16>,
+}
+
+impl OverlayLoadsArgs {
+    pub fn run(&self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)?;
+        let config_path = self.config_path.parent().unwrap();
+
+        let Some(overlay_loads_path) = &config.overlay_loads else {
+            log::error!("No overlay loads in config, run init or reanalyze with an overlay loader");
+            bail!("No overlay loads in config, run init or reanalyze with an overlay loader");
+        };
+        let overlay_loads = OverlayLoads::from_file(config_path.join(overlay_loads_path))?;
+
+        match self.overlay {
+            Some(id) => Self::print_overlay(&overlay_loads, id),
+            None => Self::print_all(&overlay_loads),
+        }
+
+        Ok(())
+    }
+
+    fn print_all(overlay_loads: &OverlayLoads) {
+        let mut by_module = BTreeMap::<ModuleKind, Vec<u16>>::new();
+        for load in overlay_loads.iter() {
+            let overlays = by_module.entry(load.module).or_default();
+            if !overlays.contains(&load.overlay) {
+                overlays.push(load.overlay);
+            }
+        }
+
+        for (module, mut overlays) in by_module {
+            overlays.sort_unstable();
+            let overlays = overlays.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+            log::info!("{module} loads overlays {overlays}");
+        }
+    }
+
+    fn print_overlay(overlay_loads: &OverlayLoads, id: u16) {
+        let loaded_by = overlay_loads.iter().filter(|load| load.overlay == id).collect::<Vec<_>>();
+        let loads = overlay_loads.iter().filter(|load| load.module == ModuleKind::Overlay(id)).collect::<Vec<_>>();
+
+        log::info!("Overlay {id} is loaded by {} call(s)", loaded_by.len());
+        for OverlayLoad { module, from, .. } in loaded_by {
+            log::info!("  {module} at {from:#010x}");
+        }
+        log::info!("Overlay {id} loads {} overlay(s)", loads.len());
+        for OverlayLoad { from, overlay, .. } in loads {
+            log::info!("  overlay {overlay} at {from:#010x}");
+        }
+    }
+}
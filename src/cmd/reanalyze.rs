@@ -0,0 +1,320 @@
+use std::{
+    collections::BTreeSet,
+    mem,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use argp::FromArgs;
+use ds_rom::rom::{Rom, RomLoadOptions};
+
+use crate::{
+    analysis::overlay_loads::OverlayLoader,
+    config::{
+        config::{Config, ConfigModule},
+        delinks::Delinks,
+        hints::Hints,
+        module::{Module, ModuleKind},
+        program::{AnalysisOptions, Program},
+        relocation::Relocations,
+        section::Section,
+        symbol::{Symbol, SymbolKind, SymbolMap, SymbolMaps},
+        types::Types,
+    },
+};
+
+/// Reruns the analysis on an existing project and merges the results into its symbols and relocations.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "reanalyze")]
+pub struct Reanalyze {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Dry run, only report changes without writing files.
+    #[argp(switch, short = 'd')]
+    pub dry: bool,
+}
+
+#[derive(Default)]
+struct MergeResult {
+    new_functions: Vec<String>,
+    removed_functions: Vec<String>,
+    new_sections: Vec<String>,
+    num_new_relocations: usize,
+    num_deleted_relocations: usize,
+}
+
+impl Reanalyze {
+    pub fn run(&self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)?;
+        let config_path = self.config_path.parent().unwrap();
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+
+        let rom = Rom::load(
+            config_path.join(&config.rom_config),
+            RomLoadOptions { compress: false, encrypt: false, load_files: false, ..Default::default() },
+        )?;
+
+        let autoloads = rom.arm9().autoloads()?;
+        let overlay_loader = match &config.overlay_loader {
+            Some(loader) => Some(OverlayLoader::parse(loader, |name| {
+                let symbol_maps = SymbolMaps::from_config(config_path, &config, &types)?;
+                Self::symbol_address(name, &symbol_maps, &config)
+            })?),
+            None => None,
+        };
+        let options = AnalysisOptions { conditional_calls: config.conditional_calls, overlay_loader };
+        let program = Program::analyze(rom.arm9(), rom.arm9_overlays(), &autoloads, &options, |kind| {
+            let module = Self::config_module(&config, kind)?;
+            Hints::from_optional_file(module.hints.as_ref().map(|hints| config_path.join(hints)))
+        })?;
+
+        let mut modules = vec![(&config.main_module, ModuleKind::Arm9)];
+        modules.extend(config.autoloads.iter().map(|autoload| (&autoload.module, ModuleKind::Autoload(autoload.kind))));
+        modules.extend(config.overlays.iter().map(|overlay| (&overlay.module, ModuleKind::Overlay(overlay.id))));
+
+        for (config_module, module_kind) in modules {
+            let module = program
+                .module_by_kind(module_kind)
+                .with_context(|| format!("Module {module_kind} in config was not found in the ROM"))?;
+            let analyzed_symbols = program.symbol_maps().get(module_kind).context("Symbol map not found for module")?;
+
+            let result = self.merge_module(config_path, config_module, module, analyzed_symbols, &types)?;
+
+            log::info!(
+                "{}: {} new functions, {} removed functions, {} new sections, {} new relocations, {} relocations left deleted",
+                config_module.name,
+                result.new_functions.len(),
+                result.removed_functions.len(),
+                result.new_sections.len(),
+                result.num_new_relocations,
+                result.num_deleted_relocations
+            );
+            for name in &result.new_functions {
+                log::info!("  + {name}");
+            }
+            for name in &result.removed_functions {
+                log::info!("  - {name}");
+            }
+            for name in &result.new_sections {
+                log::info!("  + section {name}");
+            }
+        }
+
+        if let Some(overlay_loads_path) = &config.overlay_loads {
+            log::info!("{} overlay loads", program.overlay_loads().len());
+            if !self.dry {
+                program.overlay_loads().to_file(config_path.join(overlay_loads_path))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Looks up a symbol by name in the project's symbols files. The analysis only knows default names, so names given in
+    /// the config must be resolved here.
+    fn symbol_address(name: &str, symbol_maps: &SymbolMaps, config: &Config) -> Result<Option<u32>> {
+        let mut modules = vec![ModuleKind::Arm9];
+        modules.extend(config.autoloads.iter().map(|autoload| ModuleKind::Autoload(autoload.kind)));
+        modules.extend(config.overlays.iter().map(|overlay| ModuleKind::Overlay(overlay.id)));
+        for module in modules {
+            let Some(symbol_map) = symbol_maps.get(module) else { continue };
+            if let Some((_, symbol)) = symbol_map.by_name(name)? {
+                return Ok(Some(symbol.addr));
+            }
+        }
+        Ok(None)
+    }
+
+    fn config_module(config: &Config, kind: ModuleKind) -> Result<&ConfigModule> {
+        match kind {
+            ModuleKind::Arm9 => Some(&config.main_module),
+            ModuleKind::Overlay(id) => config.overlays.iter().find(|overlay| overlay.id == id).map(|overlay| &overlay.module),
+            ModuleKind::Autoload(kind) => {
+                config.autoloads.iter().find(|autoload| autoload.kind == kind).map(|autoload| &autoload.module)
+            }
+        }
+        .with_context(|| format!("Module {kind} in ROM was not found in config"))
+    }
+
+    fn merge_module(
+        &self,
+        config_path: &Path,
+        config_module: &ConfigModule,
+        module: &Module,
+        analyzed_symbols: &SymbolMap,
+        types: &Types,
+    ) -> Result<MergeResult> {
+        let mut result = MergeResult::default();
+
+        let symbols_path = config_path.join(&config_module.symbols);
+        let symbols = SymbolMap::from_file(&symbols_path, types)?;
+        let symbols = Self::merge_symbols(module, symbols, analyzed_symbols, &mut result);
+
+        let relocations_path = config_path.join(&config_module.relocations);
+        let relocations = Self::merge_relocations(&relocations_path, module, analyzed_symbols, &mut result)?;
+
+        let delinks_path = config_path.join(&config_module.delinks);
+        let delinks = Self::merge_delinks(&delinks_path, config_module, module, &mut result)?;
+
+        if !self.dry {
+            symbols.to_file(&symbols_path)?;
+            relocations.to_file(&relocations_path)?;
+            module.relocations().to_file(Relocations::baseline_path(&relocations_path))?;
+            if !result.new_sections.is_empty() {
+                Delinks::to_file(&delinks_path, &delinks.sections, &delinks.files)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Merges analyzed symbols into the existing ones. Symbols named by the user are kept as they are. Symbols with default
+    /// names take their name from the analysis but keep their kind, size and attributes, since those may have been edited
+    /// by hand, and are removed if the analysis no longer finds anything at their address.
+    fn merge_symbols(module: &Module, existing: SymbolMap, analyzed: &SymbolMap, result: &mut MergeResult) -> SymbolMap {
+        let mut addresses = BTreeSet::new();
+        addresses.extend(existing.iter_by_address(0..u32::MAX).map(|symbol| symbol.addr));
+        addresses.extend(analyzed.iter_by_address(0..u32::MAX).map(|symbol| symbol.addr));
+
+        let mut merged = vec![];
+        for address in addresses {
+            let existing_symbols = existing.for_address(address).into_iter().flatten().map(|(_, s)| s).collect::<Vec<_>>();
+            let mut analyzed_symbols = analyzed.for_address(address).into_iter().flatten().map(|(_, s)| s).collect::<Vec<_>>();
+            let is_analyzed = !analyzed_symbols.is_empty();
+            let had_function = existing_symbols.iter().any(|symbol| matches!(symbol.kind, SymbolKind::Function(_)));
+
+            let (defaults, named): (Vec<&Symbol>, Vec<&Symbol>) =
+                existing_symbols.into_iter().partition(|symbol| Self::is_default_name(module, symbol));
+
+            for symbol in &named {
+                if !is_analyzed && matches!(symbol.kind, SymbolKind::Function(_)) {
+                    log::warn!(
+                        "{}: function {} at {:#010x} was not found by the analysis, keeping it since it was renamed",
+                        module.name(),
+                        symbol.name,
+                        symbol.addr
+                    );
+                }
+                merged.push((*symbol).clone());
+            }
+
+            for symbol in defaults {
+                // Prefer an analyzed symbol of the same kind, in case there are several at this address
+                let same_kind = analyzed_symbols
+                    .iter()
+                    .position(|analyzed| mem::discriminant(&analyzed.kind) == mem::discriminant(&symbol.kind));
+                let Some(index) = same_kind.or((!analyzed_symbols.is_empty()).then_some(0)) else {
+                    if !is_analyzed && matches!(symbol.kind, SymbolKind::Function(_)) {
+                        result.removed_functions.push(symbol.name.clone());
+                    }
+                    continue;
+                };
+                let analyzed_symbol = analyzed_symbols.remove(index);
+                merged.push(Symbol { name: analyzed_symbol.name.clone(), ..symbol.clone() });
+            }
+
+            if !named.is_empty() {
+                continue;
+            }
+            for symbol in analyzed_symbols {
+                if matches!(symbol.kind, SymbolKind::Function(_)) && !had_function {
+                    result.new_functions.push(symbol.name.clone());
+                }
+                merged.push(symbol.clone());
+            }
+        }
+
+        SymbolMap::from_symbols(merged)
+    }
+
+    /// Adds relocations which the analysis newly found. Existing relocations win, since they may have been edited by hand,
+    /// and relocations which are in the baseline but not in the relocations file were deleted by the user, so they're not
+    /// added back. Projects without a baseline only get new relocations in functions which have no relocations yet.
+    fn merge_relocations(
+        relocations_path: &Path,
+        module: &Module,
+        analyzed_symbols: &SymbolMap,
+        result: &mut MergeResult,
+    ) -> Result<Relocations> {
+        let mut relocations = Relocations::from_file_without_overrides(relocations_path)?;
+        let baseline_path = Relocations::baseline_path(relocations_path);
+        let baseline =
+            if baseline_path.exists() { Some(Relocations::from_file_without_overrides(&baseline_path)?) } else { None };
+
+        for relocation in module.relocations().iter() {
+            let from = relocation.from_address();
+            if relocations.get(from).is_some() {
+                continue;
+            }
+            let deleted = match &baseline {
+                Some(baseline) => baseline.get(from).is_some(),
+                None => analyzed_symbols.get_function_containing(from).is_some_and(|(function, symbol)| {
+                    relocations.iter_range(symbol.addr..symbol.addr + function.size).next().is_some()
+                }),
+            };
+            if deleted {
+                result.num_deleted_relocations += 1;
+                continue;
+            }
+            relocations.add(relocation.clone())?;
+            result.num_new_relocations += 1;
+        }
+        Ok(relocations)
+    }
+
+    /// Adds sections which the analysis newly found. Files and the ranges of existing sections are kept as the user wrote
+    /// them, but sections that moved are worth pointing out.
+    fn merge_delinks(
+        delinks_path: &Path,
+        config_module: &ConfigModule,
+        module: &Module,
+        result: &mut MergeResult,
+    ) -> Result<Delinks> {
+        let mut delinks = Delinks::from_file(delinks_path, module.kind())?;
+        for section in module.sections().iter() {
+            match delinks.sections.by_name(section.name()) {
+                Some(existing) if existing.address_range() != section.address_range() => log::warn!(
+                    "{}: section {} is {:#010x}..{:#010x} in delinks.txt but the analysis found {:#010x}..{:#010x}",
+                    config_module.name,
+                    section.name(),
+                    existing.start_address(),
+                    existing.end_address(),
+                    section.start_address(),
+                    section.end_address()
+                ),
+                Some(_) => {}
+                None => {
+                    let new_section = Section::new(
+                        section.name().to_string(),
+                        section.kind(),
+                        section.start_address(),
+                        section.end_address(),
+                        section.alignment(),
+                    )?;
+                    if let Err(error) = delinks.sections.add(new_section) {
+                        log::warn!(
+                            "{}: section {} was found by the analysis but can't be added to delinks.txt: {error}",
+                            config_module.name,
+                            section.name()
+                        );
+                        continue;
+                    }
+                    result.new_sections.push(section.name().to_string());
+                }
+            }
+        }
+        Ok(delinks)
+    }
+
+    fn is_default_name(module: &Module, symbol: &Symbol) -> bool {
+        let addr = symbol.addr;
+        symbol.name == format!("{}{:08x}", module.default_func_prefix, addr)
+            || symbol.name == format!("{}{:08x}", module.default_data_prefix, addr)
+            || symbol.name == format!("_{:08x}", addr)
+            || symbol.name == format!("veneer_{:08x}", addr)
+            || symbol.name.starts_with("@eti_")
+            || symbol.name.starts_with("@etb_")
+    }
+}
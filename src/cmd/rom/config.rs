@@ -1,9 +1,11 @@
 use std::{
+    collections::HashMap,
+    io::Write,
     ops::Range,
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use argp::FromArgs;
 use ds_rom::rom::{raw::AutoloadKind, OverlayConfig, Rom, RomConfig, RomLoadOptions};
 use object::{Object, ObjectSection, ObjectSymbol};
@@ -15,9 +17,15 @@ use crate::{
         config::Config,
         delinks::Delinks,
         module::ModuleKind,
+        relocation::{RelocationKind, Relocations},
         section::{Section, Sections},
+        symbol::SymbolMap,
+        types::Types,
+    },
+    util::{
+        bytes::FromSlice,
+        io::{create_file, read_file},
     },
-    util::io::{create_file, open_file, read_file},
 };
 
 /// Creates a configuration to build a ROM from linked binaries.
@@ -35,7 +43,7 @@ pub struct ConfigRom {
 
 impl ConfigRom {
     pub fn run(&self) -> Result<()> {
-        let config: Config = serde_yml::from_reader(open_file(&self.config)?)?;
+        let config = Config::from_file(&self.config)?;
         let config_path = self.config.parent().unwrap();
 
         let old_rom_paths_path = config_path.join(&config.rom_config);
@@ -57,6 +65,10 @@ impl ConfigRom {
         self.config_autoloads(&object, &config, &rom, &mut rom_paths, &new_rom_paths_dir)?;
         self.config_overlays(&object, &config, &rom, &mut rom_paths, &new_rom_paths_dir)?;
 
+        if config.conditional_calls {
+            self.restore_conditional_calls(&object, &config, &rom)?;
+        }
+
         serde_yml::to_writer(create_file(&new_rom_paths_dir.join("rom_config.yaml"))?, &rom_paths)?;
 
         Ok(())
@@ -246,6 +258,107 @@ impl ConfigRom {
         Ok(())
     }
 
+    /// Restores the condition codes of relocated conditional calls and branches in the linked binaries, since mwld removes
+    /// them when applying the relocations.
+    fn restore_conditional_calls(&self, object: &object::File<'_>, config: &Config, rom: &Rom<'_>) -> Result<()> {
+        let rom_autoloads = rom.arm9().autoloads()?;
+
+        let mut modules = vec![(&config.main_module, "ARM9".to_string(), rom.arm9().code()?, rom.arm9().base_address())];
+        for autoload in &config.autoloads {
+            let rom_autoload = rom_autoloads
+                .iter()
+                .find(|a| a.kind() == autoload.kind)
+                .with_context(|| format!("Failed to find autoload {} in ROM", autoload.kind))?;
+            let module_name = match autoload.kind {
+                AutoloadKind::Itcm => "ITCM",
+                AutoloadKind::Dtcm => "DTCM",
+                AutoloadKind::Unknown(_) => panic!("Unknown autoload kind"),
+            };
+            modules.push((&autoload.module, module_name.to_string(), rom_autoload.code(), rom_autoload.base_address()));
+        }
+        for overlay in &config.overlays {
+            let rom_overlay = rom
+                .arm9_overlays()
+                .iter()
+                .find(|o| o.id() == overlay.id)
+                .with_context(|| format!("Failed to find overlay {} in ROM", overlay.id))?;
+            modules.push((&overlay.module, format!("OV{:03}", overlay.id), rom_overlay.code(), rom_overlay.base_address()));
+        }
+
+        let config_path = self.config.parent().unwrap();
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        for (module, module_name, code, base_address) in modules {
+            let relocations = Relocations::from_file(config_path.join(&module.relocations))?;
+            let symbol_map = SymbolMap::from_file(config_path.join(&module.symbols), &types)?;
+            let linked_section =
+                object.section_by_name(&module_name).with_context(|| format!("{module_name} section not found"))?;
+
+            // Local symbols in different files can have the same name, so only look in this module's section and tell
+            // duplicates apart by their original address
+            let mut linked_symbols: HashMap<&str, Vec<u32>> = HashMap::new();
+            for symbol in object.symbols().filter(|symbol| symbol.section_index() == Some(linked_section.index())) {
+                let Ok(name) = symbol.name() else { continue };
+                linked_symbols.entry(name).or_default().push(symbol.address() as u32);
+            }
+
+            let binary_path = config_path.join(&module.object);
+            let mut binary = read_file(&binary_path)?;
+            let mut num_restored = 0;
+            for relocation in relocations.iter() {
+                if !matches!(relocation.kind(), RelocationKind::ArmCall | RelocationKind::ArmBranch) {
+                    continue;
+                }
+                let from = relocation.from_address();
+                let condition = u32::from_le_slice(&code[(from - base_address) as usize..]) >> 28;
+                if condition == 0xe {
+                    // Always executed
+                    continue;
+                }
+
+                let Some((_, function)) = symbol_map.get_function_containing(from) else {
+                    log::error!("Conditional call at {from:#010x} in {} is not inside a function", module.name);
+                    bail!("Conditional call at {from:#010x} in {} is not inside a function", module.name);
+                };
+                let linked_function_address = match linked_symbols.get(function.name.as_str()).map(|a| a.as_slice()) {
+                    None | Some([]) => {
+                        log::error!("Function {} not found in {module_name} of linked ELF", function.name);
+                        bail!("Function {} not found in {module_name} of linked ELF", function.name);
+                    }
+                    Some(&[address]) => address,
+                    Some(addresses) => {
+                        let Some(&address) = addresses.iter().find(|&&address| address == function.addr) else {
+                            log::error!(
+                                "Function {} is defined more than once in {module_name} of linked ELF, and none of them are at \
+                                its original address {:#010x}",
+                                function.name,
+                                function.addr
+                            );
+                            bail!(
+                                "Function {} is defined more than once in {module_name} of linked ELF, and none of them are at \
+                                its original address {:#010x}",
+                                function.name,
+                                function.addr
+                            );
+                        };
+                        address
+                    }
+                };
+                let linked_address = linked_function_address + (from - function.addr);
+                let offset = (linked_address - linked_section.address() as u32) as usize;
+
+                binary[offset + 3] = (binary[offset + 3] & 0x0f) | ((condition as u8) << 4);
+                num_restored += 1;
+            }
+
+            if num_restored > 0 {
+                log::info!("Restored {num_restored} conditional calls in {}", module.name);
+                create_file(&binary_path)?.write_all(&binary)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn section_ranges<F>(
         &self,
         sections: &Sections,
@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use argp::FromArgs;
+
+use crate::{
+    config::{
+        symbol::{Symbol, SymbolKind},
+        ParseContext,
+    },
+    util::parse::parse_u32,
+};
+
+use super::SymbolFiles;
+
+/// Adds a new symbol.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "add")]
+pub struct SymAdd {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Module to add the symbol to, e.g. main, itcm, dtcm or overlay(3). Required if several modules contain the
+    /// address.
+    #[argp(option, short = 'm')]
+    pub module: Option<String>,
+
+    /// Name of the symbol.
+    #[argp(positional)]
+    pub name: String,
+
+    /// Address of the symbol.
+    #[argp(positional)]
+    pub address: String,
+
+    /// Kind of the symbol, with the same syntax as symbols.txt, e.g. function(arm,size=0x40).
+    #[argp(positional)]
+    pub kind: String,
+}
+
+impl SymAdd {
+    pub fn run(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.contains(char::is_whitespace) {
+            log::error!("Invalid symbol name '{}'", self.name);
+            bail!("Invalid symbol name '{}'", self.name);
+        }
+        let address =
+            parse_u32(&self.address).with_context(|| format!("Failed to parse symbol address '{}'", self.address))?;
+
+        let mut files = SymbolFiles::load(&self.config_path)?;
+        let kind = SymbolKind::parse(&self.kind, &files.types, &ParseContext::command_line())?;
+
+        let module_kind = match SymbolFiles::parse_module(self.module.as_deref())? {
+            Some(module_kind) => module_kind,
+            None => match files.modules_containing(address)?.as_slice() {
+                [] => {
+                    log::error!("Address {address:#010x} is not inside any module");
+                    bail!("Address {address:#010x} is not inside any module");
+                }
+                [module_kind] => *module_kind,
+                modules => {
+                    let modules = modules.iter().map(|module| module.to_string()).collect::<Vec<_>>().join(", ");
+                    log::error!("Address {address:#010x} is in several modules, use --module to select one of: {modules}");
+                    bail!("Address {address:#010x} is in several modules, use --module to select one of: {modules}");
+                }
+            },
+        };
+        files.ensure_valid_location(module_kind, address, &kind)?;
+
+        let symbol = Symbol { name: self.name.clone(), kind, addr: address, ambiguous: false, scope: None, size: None };
+        files.ensure_unique_name(&self.name, symbol.is_local())?;
+
+        let symbol_map = files.symbol_maps.get_mut(module_kind);
+        if let Some((_, existing)) = symbol_map.for_address(address).and_then(|mut symbols| symbols.next()) {
+            log::error!("Symbol '{}' already exists at {address:#010x} in {module_kind}", existing.name);
+            bail!("Symbol '{}' already exists at {address:#010x} in {module_kind}", existing.name);
+        }
+        symbol_map.add(symbol);
+        files.save(module_kind)?;
+
+        log::info!("Added '{}' at {address:#010x} in {module_kind}", self.name);
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::{
+    config::{
+        config::Config,
+        delinks::Delinks,
+        module::ModuleKind,
+        renames::{Rename, RenameLog},
+        symbol::SymbolMaps,
+        types::Types,
+    },
+    util::io::{read_dir, read_to_string, write_file},
+};
+
+/// File extensions of the source and header files to update.
+const SOURCE_EXTENSIONS: &[&str] = &["c", "h", "cpp", "hpp", "cp", "cc", "cxx", "hh", "hxx", "inc"];
+
+/// Replaces the symbols in the rename log with their new names in every source file, then clears the log. Local symbols are
+/// only renamed in the source file of the delink file they're in.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "apply-renames")]
+pub struct SymApplyRenames {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Dry run, only report which files would change.
+    #[argp(switch, short = 'd')]
+    pub dry: bool,
+}
+
+impl SymApplyRenames {
+    pub fn run(&self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)?;
+        let config_path = self.config_path.parent().unwrap();
+
+        let Some(rename_log) = &config.rename_log else {
+            log::error!("No rename_log in config.yaml");
+            bail!("No rename_log in config.yaml");
+        };
+        if config.source_roots.is_empty() {
+            log::error!("No source_roots in config.yaml");
+            bail!("No source_roots in config.yaml");
+        }
+        let rename_log_path = config_path.join(rename_log);
+        let renames = RenameLog::from_file_or_new(&rename_log_path)?;
+        if renames.is_empty() {
+            log::info!("No renames to apply");
+            return Ok(());
+        }
+
+        let scoped_renames = Self::scope_renames(&config, config_path, &Self::combine_renames(&renames))?;
+
+        let mut source_files = vec![];
+        for source_root in &config.source_roots {
+            Self::find_source_files(&config_path.join(source_root), &mut source_files)?;
+        }
+        source_files.sort_unstable();
+
+        let mut num_changed = 0;
+        for path in source_files {
+            let mut file_renames = HashMap::new();
+            for (rename, file) in &scoped_renames {
+                if file.as_ref().is_some_and(|file| !path.ends_with(file)) {
+                    continue;
+                }
+                let to = *file_renames.entry(rename.from.as_str()).or_insert(rename.to.as_str());
+                if to != rename.to {
+                    log::warn!(
+                        "'{}' is renamed to both '{to}' and '{}' in {}, using '{to}'",
+                        rename.from,
+                        rename.to,
+                        path.display()
+                    );
+                }
+            }
+
+            let source = read_to_string(&path)?;
+            let new_source = replace_identifiers(&source, &file_renames);
+            if new_source == source {
+                continue;
+            }
+            if !self.dry {
+                write_file(&path, new_source)?;
+            }
+            log::info!("Updated {}", path.display());
+            num_changed += 1;
+        }
+
+        if self.dry {
+            log::info!("{num_changed} files would be updated by {} renames", renames.len());
+        } else {
+            RenameLog::new().to_file(&rename_log_path)?;
+            log::info!("Updated {num_changed} files with {} renames", renames.len());
+        }
+        Ok(())
+    }
+
+    /// Combines the renames of each symbol into one, e.g. `a` to `b` and then `b` to `c` becomes `a` to `c`. Since every
+    /// rename goes from the original name, renames which swap names are applied at the same time instead of in order.
+    fn combine_renames(renames: &RenameLog) -> Vec<Rename> {
+        let mut combined: Vec<Rename> = vec![];
+        for rename in renames.iter() {
+            let previous = combined.iter_mut().find(|previous| previous.to == rename.from && previous.symbol == rename.symbol);
+            match previous {
+                Some(previous) => previous.to = rename.to.clone(),
+                None => combined.push(rename.clone()),
+            }
+        }
+        combined.retain(|rename| rename.from != rename.to);
+        combined
+    }
+
+    /// Pairs each rename with the delink file it's limited to, which is `None` for renames that apply to every source file.
+    /// Local symbols can only be referred to by their own file, and are left out if they're not in any delink file yet.
+    fn scope_renames(config: &Config, config_path: &Path, renames: &[Rename]) -> Result<Vec<(Rename, Option<String>)>> {
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        let symbol_maps = SymbolMaps::from_config(config_path, config, &types)?;
+
+        let mut modules = vec![(&config.main_module, ModuleKind::Arm9)];
+        modules.extend(config.autoloads.iter().map(|autoload| (&autoload.module, ModuleKind::Autoload(autoload.kind))));
+        modules.extend(config.overlays.iter().map(|overlay| (&overlay.module, ModuleKind::Overlay(overlay.id))));
+
+        let mut scoped = vec![];
+        for rename in renames {
+            let file = match rename.symbol {
+                None => None,
+                Some((module_kind, address)) => {
+                    let is_local = symbol_maps
+                        .get(module_kind)
+                        .and_then(|symbol_map| symbol_map.for_address(address))
+                        .is_some_and(|mut symbols| symbols.any(|(_, symbol)| symbol.name == rename.to && symbol.is_local()));
+                    let module = modules.iter().find(|&&(_, kind)| kind == module_kind).map(|&(module, _)| module);
+                    match module {
+                        Some(module) if is_local => {
+                            let delinks = Delinks::from_file(config_path.join(&module.delinks), module_kind)?;
+                            let file =
+                                delinks.files.iter().filter(|file| !file.gap()).find(|file| {
+                                    file.sections.iter().any(|section| section.address_range().contains(&address))
+                                });
+                            let Some(file) = file else {
+                                log::debug!("Local symbol '{}' is not in any delink file, skipping", rename.to);
+                                continue;
+                            };
+                            Some(file.name.clone())
+                        }
+                        _ => None,
+                    }
+                }
+            };
+            scoped.push((rename.clone(), file));
+        }
+        Ok(scoped)
+    }
+
+    fn find_source_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                Self::find_source_files(&path, files)?;
+            } else if path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext)) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces whole identifiers only, so that renaming `func_0201` leaves `func_02012345` and `my_func_0201` unchanged. All
+/// renames are applied in one pass, so a replaced identifier is never renamed again.
+fn replace_identifiers(text: &str, renames: &HashMap<&str, &str>) -> String {
+    let is_identifier_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(is_identifier_char) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_identifier_char(c)).unwrap_or(rest.len());
+        let identifier = &rest[..end];
+        result.push_str(renames.get(identifier).copied().unwrap_or(identifier));
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(from: &str, to: &str, address: u32) -> Rename {
+        Rename { from: from.to_string(), to: to.to_string(), symbol: Some((ModuleKind::Arm9, address)) }
+    }
+
+    fn apply(text: &str, renames: &[Rename]) -> String {
+        let renames = renames.iter().map(|rename| (rename.from.as_str(), rename.to.as_str())).collect();
+        replace_identifiers(text, &renames)
+    }
+
+    #[test]
+    fn test_whole_identifiers() {
+        let renames = [rename("func_0201", "Init", 0x0201)];
+        assert_eq!(
+            apply("func_0201(); func_02012345(); my_func_0201(); (func_0201)", &renames),
+            "Init(); func_02012345(); my_func_0201(); (Init)"
+        );
+    }
+
+    #[test]
+    fn test_swapped_names() {
+        let mut log = RenameLog::new();
+        log.add(rename("a", "b", 0x1000));
+        log.add(rename("b", "a", 0x2000));
+        let renames = SymApplyRenames::combine_renames(&log);
+        assert_eq!(renames.len(), 2);
+        assert_eq!(apply("a = b;", &renames), "b = a;");
+    }
+
+    #[test]
+    fn test_chained_renames() {
+        let mut log = RenameLog::new();
+        log.add(rename("a", "b", 0x1000));
+        log.add(rename("b", "c", 0x1000));
+        let renames = SymApplyRenames::combine_renames(&log);
+        assert_eq!(renames.len(), 1);
+        assert_eq!((renames[0].from.as_str(), renames[0].to.as_str()), ("a", "c"));
+        assert_eq!(apply("a(b);", &renames), "c(b);");
+    }
+}
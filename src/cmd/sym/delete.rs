@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use argp::FromArgs;
+
+use super::SymbolFiles;
+
+/// Deletes a symbol.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "delete")]
+pub struct SymDelete {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Module of the symbol, e.g. main, itcm, dtcm or overlay(3). Required if the symbol exists in several modules.
+    #[argp(option, short = 'm')]
+    pub module: Option<String>,
+
+    /// Name or address of the symbol.
+    #[argp(positional)]
+    pub symbol: String,
+}
+
+impl SymDelete {
+    pub fn run(&self) -> Result<()> {
+        let mut files = SymbolFiles::load(&self.config_path)?;
+        let module = SymbolFiles::parse_module(self.module.as_deref())?;
+        let (module_kind, index) = files.find(&self.symbol, module)?;
+
+        let symbol = files.symbol_maps.get_mut(module_kind).remove(index);
+        files.save(module_kind)?;
+
+        log::info!("Deleted '{}' at {:#010x} in {module_kind}", symbol.name, symbol.addr);
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+mod add;
+mod apply_renames;
+mod delete;
+mod move_symbol;
+mod rename;
+mod set_type;
+
+pub use add::*;
+pub use apply_renames::*;
+pub use delete::*;
+pub use move_symbol::*;
+pub use rename::*;
+pub use set_type::*;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::{
+    config::{
+        config::{Config, ConfigModule},
+        delinks::Delinks,
+        module::ModuleKind,
+        relocation::RelocationModule,
+        section::Section,
+        symbol::{SymbolIndex, SymbolKind, SymbolMaps},
+        types::Types,
+        ParseContext,
+    },
+    util::parse::parse_u32,
+};
+
+/// Subcommands for editing symbols.txt files.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "sym")]
+pub struct SymArgs {
+    #[argp(subcommand)]
+    command: SymCommand,
+}
+
+impl SymArgs {
+    pub fn run(&self) -> Result<()> {
+        match &self.command {
+            SymCommand::Rename(rename) => rename.run(),
+            SymCommand::SetType(set_type) => set_type.run(),
+            SymCommand::Delete(delete) => delete.run(),
+            SymCommand::Add(add) => add.run(),
+            SymCommand::Move(move_symbol) => move_symbol.run(),
+            SymCommand::ApplyRenames(apply_renames) => apply_renames.run(),
+        }
+    }
+}
+
+#[derive(FromArgs)]
+#[argp(subcommand)]
+enum SymCommand {
+    Rename(SymRename),
+    SetType(SymSetType),
+    Delete(SymDelete),
+    Add(SymAdd),
+    Move(SymMove),
+    ApplyRenames(SymApplyRenames),
+}
+
+/// The symbol maps of every module in a config, loaded for editing.
+struct SymbolFiles {
+    config: Config,
+    config_path: PathBuf,
+    symbol_maps: SymbolMaps,
+    types: Types,
+}
+
+impl SymbolFiles {
+    fn load(config_path: &Path) -> Result<Self> {
+        let config = Config::from_file(config_path)?;
+        let config_path = config_path.parent().unwrap().to_path_buf();
+        let types = Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types)))?;
+        let symbol_maps = SymbolMaps::from_config(&config_path, &config, &types)?;
+        Ok(Self { config, config_path, symbol_maps, types })
+    }
+
+    fn modules(&self) -> Vec<(&ConfigModule, ModuleKind)> {
+        let mut modules = vec![(&self.config.main_module, ModuleKind::Arm9)];
+        modules.extend(self.config.autoloads.iter().map(|autoload| (&autoload.module, ModuleKind::Autoload(autoload.kind))));
+        modules.extend(self.config.overlays.iter().map(|overlay| (&overlay.module, ModuleKind::Overlay(overlay.id))));
+        modules
+    }
+
+    fn module(&self, module_kind: ModuleKind) -> Result<&ConfigModule> {
+        let Some((module, _)) = self.modules().into_iter().find(|&(_, kind)| kind == module_kind) else {
+            log::error!("Module {module_kind} not found in config");
+            bail!("Module {module_kind} not found in config");
+        };
+        Ok(module)
+    }
+
+    /// Parses the `--module` option, using the same syntax as the `module` attribute in relocs.txt.
+    fn parse_module(module: Option<&str>) -> Result<Option<ModuleKind>> {
+        let Some(module) = module else {
+            return Ok(None);
+        };
+        let Some(module_kind) = RelocationModule::parse(module, &ParseContext::command_line())?.first_module() else {
+            log::error!("Expected a module but got '{module}'");
+            bail!("Expected a module but got '{module}'");
+        };
+        Ok(Some(module_kind))
+    }
+
+    /// Finds a symbol by name or address. If the symbol exists in several modules, `module` must select one of them.
+    fn find(&self, target: &str, module: Option<ModuleKind>) -> Result<(ModuleKind, SymbolIndex)> {
+        let address = parse_u32(target).ok();
+
+        let mut matches = vec![];
+        for (_, module_kind) in self.modules() {
+            if module.is_some_and(|module| module != module_kind) {
+                continue;
+            }
+            let Some(symbol_map) = self.symbol_maps.get(module_kind) else { continue };
+            let with_module = |(index, symbol)| (module_kind, index, symbol);
+            match address {
+                Some(address) => matches.extend(symbol_map.for_address(address).into_iter().flatten().map(with_module)),
+                None => matches.extend(symbol_map.for_name(target).into_iter().flatten().map(with_module)),
+            }
+        }
+
+        match matches.as_slice() {
+            [] => {
+                log::error!("No symbol '{target}' found");
+                bail!("No symbol '{target}' found");
+            }
+            [(module_kind, index, _)] => Ok((*module_kind, *index)),
+            _ => {
+                let symbols = matches
+                    .iter()
+                    .map(|(module_kind, _, symbol)| format!("{} at {:#010x} in {module_kind}", symbol.name, symbol.addr))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                log::error!("Symbol '{target}' is ambiguous, use --module to select one of: {symbols}");
+                bail!("Symbol '{target}' is ambiguous, use --module to select one of: {symbols}");
+            }
+        }
+    }
+
+    /// Fails if any module already has a symbol with this name. Local symbols in different files may share a name, so
+    /// this only fails if the existing symbol or the new one is non-local.
+    fn ensure_unique_name(&self, name: &str, local: bool) -> Result<()> {
+        for (_, module_kind) in self.modules() {
+            let Some(symbol_map) = self.symbol_maps.get(module_kind) else { continue };
+            let Some(mut symbols) = symbol_map.for_name(name) else { continue };
+            if let Some((_, symbol)) = symbols.find(|(_, symbol)| !local || !symbol.is_local()) {
+                log::error!("Symbol '{name}' already exists at {:#010x} in {module_kind}", symbol.addr);
+                bail!("Symbol '{name}' already exists at {:#010x} in {module_kind}", symbol.addr);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the modules which have a section containing the address.
+    fn modules_containing(&self, address: u32) -> Result<Vec<ModuleKind>> {
+        let mut modules = vec![];
+        for (module, module_kind) in self.modules() {
+            let delinks = Delinks::from_file(self.config_path.join(&module.delinks), module_kind)?;
+            if delinks.sections.get_by_contained_address(address).is_some() {
+                modules.push(module_kind);
+            }
+        }
+        Ok(modules)
+    }
+
+    /// Fails if the address is outside of the module's sections, or if the symbol kind doesn't fit the section it's in.
+    fn ensure_valid_location(&self, module_kind: ModuleKind, address: u32, kind: &SymbolKind) -> Result<()> {
+        let module = self.module(module_kind)?;
+        let delinks = Delinks::from_file(self.config_path.join(&module.delinks), module_kind)?;
+        let Some((_, section)) = delinks.sections.get_by_contained_address(address) else {
+            log::error!("Address {address:#010x} is not inside any section of {module_kind}");
+            bail!("Address {address:#010x} is not inside any section of {module_kind}");
+        };
+        Self::ensure_kind_fits_section(kind, section)
+    }
+
+    fn ensure_kind_fits_section(kind: &SymbolKind, section: &Section) -> Result<()> {
+        let is_bss = matches!(kind, SymbolKind::Bss(_));
+        if is_bss == section.kind().is_initialized() {
+            log::error!("Symbol kind '{kind}' can't be placed in section '{}' of kind '{}'", section.name(), section.kind());
+            bail!("Symbol kind '{kind}' can't be placed in section '{}' of kind '{}'", section.name(), section.kind());
+        }
+        Ok(())
+    }
+
+    /// Writes the symbol map of a module back to its symbols.txt.
+    fn save(&self, module_kind: ModuleKind) -> Result<()> {
+        let module = self.module(module_kind)?;
+        let Some(symbol_map) = self.symbol_maps.get(module_kind) else {
+            log::error!("Symbol map not found for {module_kind}");
+            bail!("Symbol map not found for {module_kind}");
+        };
+        symbol_map.to_file(self.config_path.join(&module.symbols))
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use argp::FromArgs;
+
+use crate::util::parse::parse_u32;
+
+use super::SymbolFiles;
+
+/// Moves a symbol to another address, and optionally to another module.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "move")]
+pub struct SymMove {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Module of the symbol, e.g. main, itcm, dtcm or overlay(3). Required if the symbol exists in several modules.
+    #[argp(option, short = 'm')]
+    pub module: Option<String>,
+
+    /// Module to move the symbol to. Defaults to the symbol's current module if it contains the new address.
+    #[argp(option, short = 't')]
+    pub to_module: Option<String>,
+
+    /// Name or address of the symbol.
+    #[argp(positional)]
+    pub symbol: String,
+
+    /// New address of the symbol.
+    #[argp(positional)]
+    pub address: String,
+}
+
+impl SymMove {
+    pub fn run(&self) -> Result<()> {
+        let address =
+            parse_u32(&self.address).with_context(|| format!("Failed to parse symbol address '{}'", self.address))?;
+
+        let mut files = SymbolFiles::load(&self.config_path)?;
+        let module = SymbolFiles::parse_module(self.module.as_deref())?;
+        let (module_kind, index) = files.find(&self.symbol, module)?;
+
+        let target_kind = match SymbolFiles::parse_module(self.to_module.as_deref())? {
+            Some(target_kind) => target_kind,
+            None => {
+                let modules = files.modules_containing(address)?;
+                match modules.as_slice() {
+                    [] => {
+                        log::error!("Address {address:#010x} is not inside any module");
+                        bail!("Address {address:#010x} is not inside any module");
+                    }
+                    _ if modules.contains(&module_kind) => module_kind,
+                    [target_kind] => *target_kind,
+                    modules => {
+                        let modules = modules.iter().map(|module| module.to_string()).collect::<Vec<_>>().join(", ");
+                        log::error!(
+                            "Address {address:#010x} is in several modules, use --to-module to select one of: {modules}"
+                        );
+                        bail!("Address {address:#010x} is in several modules, use --to-module to select one of: {modules}");
+                    }
+                }
+            }
+        };
+
+        let mut symbol = files.symbol_maps.get_mut(module_kind).remove(index);
+        files.ensure_valid_location(target_kind, address, &symbol.kind)?;
+        files.ensure_unique_name(&symbol.name, symbol.is_local())?;
+
+        let target_map = files.symbol_maps.get_mut(target_kind);
+        if let Some((_, existing)) = target_map.for_address(address).and_then(|mut symbols| symbols.next()) {
+            log::error!("Symbol '{}' already exists at {address:#010x} in {target_kind}", existing.name);
+            bail!("Symbol '{}' already exists at {address:#010x} in {target_kind}", existing.name);
+        }
+        let old_address = symbol.addr;
+        symbol.addr = address;
+        let (_, symbol) = target_map.add(symbol);
+        let name = symbol.name.clone();
+
+        files.save(module_kind)?;
+        if target_kind != module_kind {
+            files.save(target_kind)?;
+        }
+
+        log::info!("Moved '{name}' from {old_address:#010x} in {module_kind} to {address:#010x} in {target_kind}");
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::config::renames::{Rename, RenameLog};
+
+use super::SymbolFiles;
+
+/// Renames a symbol, and records it in the rename log if `rename_log` is set in config.yaml.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "rename")]
+pub struct SymRename {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Module of the symbol, e.g. main, itcm, dtcm or overlay(3). Required if the symbol exists in several modules.
+    #[argp(option, short = 'm')]
+    pub module: Option<String>,
+
+    /// Current name or address of the symbol.
+    #[argp(positional)]
+    pub symbol: String,
+
+    /// New name of the symbol.
+    #[argp(positional)]
+    pub new_name: String,
+}
+
+impl SymRename {
+    pub fn run(&self) -> Result<()> {
+        if self.new_name.is_empty() || self.new_name.contains(char::is_whitespace) {
+            log::error!("Invalid symbol name '{}'", self.new_name);
+            bail!("Invalid symbol name '{}'", self.new_name);
+        }
+
+        let mut files = SymbolFiles::load(&self.config_path)?;
+        let module = SymbolFiles::parse_module(self.module.as_deref())?;
+        let (module_kind, index) = files.find(&self.symbol, module)?;
+        let local = files.symbol_maps.get_mut(module_kind).get(index).is_local();
+        files.ensure_unique_name(&self.new_name, local)?;
+
+        let symbol_map = files.symbol_maps.get_mut(module_kind);
+        let (old_name, address) = (symbol_map.get(index).name.clone(), symbol_map.get(index).addr);
+        symbol_map.rename(index, &self.new_name)?;
+        files.save(module_kind)?;
+
+        if let Some(rename_log) = &files.config.rename_log {
+            let path = files.config_path.join(rename_log);
+            let mut renames = RenameLog::from_file_or_new(&path)?;
+            renames.add(Rename { from: old_name.clone(), to: self.new_name.clone(), symbol: Some((module_kind, address)) });
+            renames.to_file(&path)?;
+        }
+
+        log::info!("Renamed '{old_name}' to '{}' in {module_kind}", self.new_name);
+        Ok(())
+    }
+}
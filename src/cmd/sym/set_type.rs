@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use argp::FromArgs;
+
+use crate::config::{symbol::SymbolKind, ParseContext};
+
+use super::SymbolFiles;
+
+/// Changes the kind of a symbol.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "set-type")]
+pub struct SymSetType {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+
+    /// Module of the symbol, e.g. main, itcm, dtcm or overlay(3). Required if the symbol exists in several modules.
+    #[argp(option, short = 'm')]
+    pub module: Option<String>,
+
+    /// Name or address of the symbol.
+    #[argp(positional)]
+    pub symbol: String,
+
+    /// New kind of the symbol, with the same syntax as symbols.txt, e.g. data(word[4]).
+    #[argp(positional)]
+    pub kind: String,
+}
+
+impl SymSetType {
+    pub fn run(&self) -> Result<()> {
+        let mut files = SymbolFiles::load(&self.config_path)?;
+        let kind = SymbolKind::parse(&self.kind, &files.types, &ParseContext::command_line())?;
+        let module = SymbolFiles::parse_module(self.module.as_deref())?;
+        let (module_kind, index) = files.find(&self.symbol, module)?;
+
+        let symbol = files.symbol_maps.get_mut(module_kind).get(index);
+        let (name, address, old_kind) = (symbol.name.clone(), symbol.addr, symbol.kind.clone());
+        files.ensure_valid_location(module_kind, address, &kind)?;
+
+        let symbol_map = files.symbol_maps.get_mut(module_kind);
+        if symbol_map.get(index).size.is_some() && kind.known_size(&files.types).is_some() {
+            // The new kind has a size of its own, which would conflict with the old size override
+            log::info!("Removed the size override of '{name}', as {kind} has a size of its own");
+            symbol_map.set_size(index, None);
+        }
+        symbol_map.set_kind(index, kind.clone());
+        files.save(module_kind)?;
+
+        log::info!("Changed kind of '{name}' in {module_kind} from {old_kind} to {kind}");
+        Ok(())
+    }
+}
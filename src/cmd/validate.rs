@@ -0,0 +1,320 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use argp::FromArgs;
+
+use crate::config::{
+    config::{Config, ConfigModule},
+    delinks::Delinks,
+    module::ModuleKind,
+    relocation::{Relocation, RelocationOverride, RelocationOverrides, Relocations},
+    symbol::{Symbol, SymbolKind, SymbolMap},
+    types::Types,
+};
+
+/// Checks the config, delinks, symbols and relocations files of a project and reports every problem found.
+#[derive(FromArgs)]
+#[argp(subcommand, name = "validate")]
+pub struct Validate {
+    /// Path to config.yaml.
+    #[argp(option, short = 'c')]
+    pub config_path: PathBuf,
+}
+
+/// The files of one module. Items are paired with the location they were read from, and the files which failed to load
+/// are left out.
+struct ModuleFiles {
+    kind: ModuleKind,
+    delinks_path: PathBuf,
+    delinks: Option<Delinks>,
+    symbols: Vec<(String, Symbol)>,
+    symbol_map: SymbolMap,
+    relocations: Vec<(String, Relocation)>,
+}
+
+#[derive(Default)]
+struct Problems {
+    problems: Vec<String>,
+}
+
+impl Problems {
+    fn add(&mut self, problem: impl Display) {
+        self.problems.push(problem.to_string());
+    }
+
+    fn add_error(&mut self, error: anyhow::Error) {
+        self.add(format!("{error:#}"));
+    }
+}
+
+impl Validate {
+    pub fn run(&self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)?;
+        let config_path = self.config_path.parent().unwrap();
+
+        let mut problems = Problems::default();
+
+        let types = match Types::from_optional_file(config.types.as_ref().map(|types| config_path.join(types))) {
+            Ok(types) => types,
+            Err(error) => {
+                problems.add_error(error);
+                Types::new()
+            }
+        };
+
+        let mut modules = vec![Self::load_module(&config.main_module, ModuleKind::Arm9, config_path, &types, &mut problems)];
+        for autoload in &config.autoloads {
+            let kind = ModuleKind::Autoload(autoload.kind);
+            modules.push(Self::load_module(&autoload.module, kind, config_path, &types, &mut problems));
+        }
+        for overlay in &config.overlays {
+            let kind = ModuleKind::Overlay(overlay.id);
+            modules.push(Self::load_module(&overlay.module, kind, config_path, &types, &mut problems));
+        }
+
+        for module in &modules {
+            Self::check_delinks(module, &mut problems);
+            Self::check_symbols(module, &modules, &types, &mut problems);
+            Self::check_relocations(module, &modules, &mut problems);
+        }
+
+        if problems.problems.is_empty() {
+            log::info!("No problems found");
+            return Ok(());
+        }
+        for problem in &problems.problems {
+            log::error!("{problem}");
+        }
+        log::error!("Found {} problem(s)", problems.problems.len());
+        bail!("Found {} problem(s)", problems.problems.len());
+    }
+
+    fn load_module(
+        module: &ConfigModule,
+        kind: ModuleKind,
+        config_path: &Path,
+        types: &Types,
+        problems: &mut Problems,
+    ) -> ModuleFiles {
+        let delinks_path = config_path.join(&module.delinks);
+        let delinks = Delinks::from_file(&delinks_path, kind).map_err(|error| problems.add_error(error)).ok();
+
+        let mut symbols = vec![];
+        match SymbolMap::parse_lines(config_path.join(&module.symbols), types) {
+            Ok(lines) => {
+                for line in lines {
+                    match line {
+                        Ok((context, symbol)) => symbols.push((context.to_string(), symbol)),
+                        Err(error) => problems.add_error(error),
+                    }
+                }
+            }
+            Err(error) => problems.add_error(error),
+        }
+        let symbol_map = SymbolMap::from_symbols(symbols.iter().map(|(_, symbol)| symbol.clone()).collect());
+
+        let relocations_path = config_path.join(&module.relocations);
+        let mut relocations = vec![];
+        match Relocations::parse_lines(&relocations_path) {
+            Ok(lines) => {
+                for line in lines {
+                    match line {
+                        Ok((context, relocation)) => relocations.push((context.to_string(), relocation)),
+                        Err(error) => problems.add_error(error),
+                    }
+                }
+            }
+            Err(error) => problems.add_error(error),
+        }
+
+        let overrides_path = RelocationOverrides::path(&relocations_path);
+        if overrides_path.exists() {
+            match RelocationOverrides::from_file(&overrides_path) {
+                Ok(overrides) => Self::apply_overrides(&overrides, &overrides_path, &mut relocations),
+                Err(error) => problems.add_error(error),
+            }
+        }
+
+        ModuleFiles { kind, delinks_path, delinks, symbols, symbol_map, relocations }
+    }
+
+    /// Same as [`RelocationOverrides::apply`], but keeps track of where each relocation came from.
+    fn apply_overrides(overrides: &RelocationOverrides, overrides_path: &Path, relocations: &mut Vec<(String, Relocation)>) {
+        for relocation_override in overrides.iter() {
+            let from = relocation_override.from_address();
+            match relocation_override {
+                RelocationOverride::Add(relocation) => {
+                    relocations.retain(|(_, relocation)| relocation.from_address() != from);
+                    relocations.push((overrides_path.display().to_string(), relocation.clone()));
+                }
+                RelocationOverride::Remove { .. } => {
+                    relocations.retain(|(_, relocation)| relocation.from_address() != from);
+                }
+                RelocationOverride::SetModule { module, .. } => {
+                    for (_, relocation) in relocations.iter_mut().filter(|(_, relocation)| relocation.from_address() == from) {
+                        relocation.set_module(module.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_delinks(module: &ModuleFiles, problems: &mut Problems) {
+        let Some(delinks) = &module.delinks else { return };
+        for file in delinks.files.iter().filter(|file| !file.gap()) {
+            for section in file.sections.sorted_by_address() {
+                let Some(header_section) = delinks.sections.by_name(section.name()) else { continue };
+                if section.start_address() < header_section.start_address()
+                    || section.end_address() > header_section.end_address()
+                {
+                    problems.add(format!(
+                        "{}: file '{}' has {} at {:#010x}..{:#010x}, outside of the section at {:#010x}..{:#010x}",
+                        module.delinks_path.display(),
+                        file.name,
+                        section.name(),
+                        section.start_address(),
+                        section.end_address(),
+                        header_section.start_address(),
+                        header_section.end_address()
+                    ));
+                }
+            }
+        }
+    }
+
+    fn check_symbols(module: &ModuleFiles, modules: &[ModuleFiles], types: &Types, problems: &mut Problems) {
+        let mut names = BTreeMap::<&str, Vec<&(String, Symbol)>>::new();
+        for entry in &module.symbols {
+            let (location, symbol) = entry;
+            names.entry(symbol.name.as_str()).or_default().push(entry);
+
+            let Some(delinks) = &module.delinks else { continue };
+            match delinks.sections.get_by_contained_address(symbol.addr) {
+                Some((_, section)) => {
+                    if matches!(symbol.kind, SymbolKind::Bss(_)) == section.kind().is_initialized() {
+                        problems.add(format!(
+                            "{location}: symbol '{}' of kind '{}' can't be in section '{}' of kind '{}'",
+                            symbol.name,
+                            symbol.kind,
+                            section.name(),
+                            section.kind()
+                        ));
+                    }
+                }
+                None => {
+                    let containing = modules
+                        .iter()
+                        .filter(|other| other.kind != module.kind)
+                        .filter(|other| {
+                            other
+                                .delinks
+                                .as_ref()
+                                .is_some_and(|delinks| delinks.sections.get_by_contained_address(symbol.addr).is_some())
+                        })
+                        .map(|other| other.kind.to_string())
+                        .collect::<Vec<_>>();
+                    if containing.is_empty() {
+                        problems.add(format!(
+                            "{location}: symbol '{}' at {:#010x} is outside of the sections of {}",
+                            symbol.name, symbol.addr, module.kind
+                        ));
+                    } else {
+                        problems.add(format!(
+                            "{location}: symbol '{}' at {:#010x} is not in {}, but it's inside {}",
+                            symbol.name,
+                            symbol.addr,
+                            module.kind,
+                            containing.join(", ")
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (name, symbols) in names {
+            let mut non_local = symbols.iter().filter(|(_, symbol)| !symbol.is_local());
+            if let (Some((location, _)), Some((other_location, _))) = (non_local.next(), non_local.next()) {
+                problems.add(format!("{location}: symbol '{name}' is also defined at {other_location}"));
+            }
+        }
+
+        // Symbols which take up space must not overlap each other
+        let mut sized_symbols = BTreeMap::<u32, Vec<(&str, &Symbol, u32)>>::new();
+        for (location, symbol) in &module.symbols {
+            let size = match &symbol.kind {
+                SymbolKind::Function(function) => Some(function.size),
+                SymbolKind::Veneer(veneer) => Some(veneer.size),
+                SymbolKind::Data(data) => data.size(types),
+                SymbolKind::Bss(bss) => bss.size,
+                SymbolKind::Label(_) | SymbolKind::PoolConstant | SymbolKind::JumpTable(_) => continue,
+            };
+            let size = symbol.size.or(size).unwrap_or(0);
+            if matches!(symbol.kind, SymbolKind::Data(_)) && Self::is_inside_function(&module.symbol_map, symbol.addr, size) {
+                // Inline tables such as switch tables are data symbols inside the function that uses them
+                continue;
+            }
+            sized_symbols.entry(symbol.addr).or_default().push((location.as_str(), symbol, size));
+        }
+        for (&address, symbols) in &sized_symbols {
+            for &(location, symbol, size) in symbols {
+                let end = address + size;
+                let overlapping = sized_symbols
+                    .range(address..end)
+                    .flat_map(|(_, others)| others)
+                    .filter(|&&(_, other, _)| !std::ptr::eq(other, symbol))
+                    // Report symbols at the same address only once
+                    .filter(|&&(_, other, other_size)| {
+                        other.addr > address || (other_size, &other.name) < (size, &symbol.name)
+                    });
+                for &(other_location, other, _) in overlapping {
+                    problems.add(format!(
+                        "{location}: symbol '{}' at {address:#010x}..{end:#010x} overlaps with '{}' at {:#010x} ({})",
+                        symbol.name, other.name, other.addr, other_location
+                    ));
+                }
+            }
+        }
+    }
+
+    fn is_inside_function(symbol_map: &SymbolMap, address: u32, size: u32) -> bool {
+        symbol_map
+            .get_function_containing(address)
+            .is_some_and(|(function, symbol)| address + size <= symbol.addr + function.size)
+    }
+
+    fn check_relocations(module: &ModuleFiles, modules: &[ModuleFiles], problems: &mut Problems) {
+        let mut locations = BTreeMap::<u32, &str>::new();
+        for (location, relocation) in &module.relocations {
+            let from = relocation.from_address();
+            if let Some(other_location) = locations.insert(from, location) {
+                problems.add(format!("{location}: relocation from {from:#010x} is also defined at {other_location}"));
+            }
+
+            let Some(first_module) = relocation.module().first_module() else { continue };
+            let targets = std::iter::once(first_module).chain(relocation.module().other_modules().into_iter().flatten());
+            for target in targets {
+                let Some(target_module) = modules.iter().find(|module| module.kind == target) else {
+                    problems.add(format!(
+                        "{location}: relocation from {from:#010x} points to {target}, which is not in the config"
+                    ));
+                    continue;
+                };
+                let to = relocation.to_address();
+                let symbol_map = &target_module.symbol_map;
+                match (symbol_map.by_address(to), symbol_map.get_function(to)) {
+                    (Ok(Some(_)), _) | (_, Ok(Some(_))) => {}
+                    (Err(error), _) | (_, Err(error)) => {
+                        problems.add(format!("{location}: relocation from {from:#010x} to {to:#010x} in {target}: {error:#}"))
+                    }
+                    (Ok(None), Ok(None)) => problems.add(format!(
+                        "{location}: relocation from {from:#010x} points to {to:#010x} in {target}, which has no symbol there"
+                    )),
+                }
+            }
+        }
+    }
+}
@@ -1,18 +1,89 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use anyhow::{bail, Result};
 use ds_rom::rom::raw::AutoloadKind;
 use serde::{Deserialize, Serialize};
 
+use crate::util::io::read_to_string;
+
+/// Format version of the config and the files it points to, as written by this version of dsd. Bump this whenever a
+/// format changes, and add a migration from the previous version to the `migrate` command.
+pub const CONFIG_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
+    /// Format version, see [`CONFIG_VERSION`]. Configs from before the format was versioned have version 0.
+    #[serde(default)]
+    pub version: u32,
     pub rom_config: PathBuf,
     pub build_path: PathBuf,
     pub delinks_path: PathBuf,
+    /// Relocate conditional ARM calls and branches. mwld removes their condition codes, so `rom config` restores them in
+    /// the linked binaries.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub conditional_calls: bool,
+    /// Overlay loader function such as `FS_LoadOverlay`, by symbol name in `symbols.txt`, address, or `signature:` followed
+    /// by the hex bytes at the start of the function. Calls to it are written to `overlay_loads`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlay_loader: Option<String>,
+    /// Path to overlay loads file, listing which code loads which overlay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlay_loads: Option<PathBuf>,
+    /// Path to types file, declaring struct layouts for data symbols
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub types: Option<PathBuf>,
+    /// Path to rename log, recording symbols renamed by `sym rename` until `sym apply-renames` updates the source files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rename_log: Option<PathBuf>,
+    /// Directories with source and header files to update with `sym apply-renames`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_roots: Vec<PathBuf>,
     pub main_module: ConfigModule,
     pub autoloads: Vec<ConfigAutoload>,
     pub overlays: Vec<ConfigOverlay>,
 }
 
+/// Reads only the version of a config, which works even if the rest of it is in a format this dsd doesn't know.
+#[derive(Deserialize)]
+struct ConfigVersion {
+    #[serde(default)]
+    version: u32,
+}
+
+impl Config {
+    /// Loads a config, and warns if it's from an older version of dsd.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let config = Self::from_file_for_migration(path)?;
+        if config.version < CONFIG_VERSION {
+            log::warn!(
+                "{} has format version {}, run `dsd migrate` to upgrade it to version {CONFIG_VERSION}",
+                path.display(),
+                config.version
+            );
+        }
+        Ok(config)
+    }
+
+    /// Loads a config of the current or any older version.
+    pub fn from_file_for_migration<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = read_to_string(path)?;
+
+        let ConfigVersion { version } = serde_yml::from_str(&text)?;
+        if version > CONFIG_VERSION {
+            log::error!(
+                "{} has format version {version}, which was written by a newer dsd. This dsd supports up to version \
+                {CONFIG_VERSION}, please update it",
+                path.display()
+            );
+            bail!("{} was written by a newer dsd (format version {version})", path.display());
+        }
+
+        Ok(serde_yml::from_str(&text)?)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ConfigModule {
     /// Name of module
@@ -27,11 +98,17 @@ pub struct ConfigModule {
     pub symbols: PathBuf,
     /// Path to relocs file
     pub relocations: PathBuf,
+    /// Path to hints file, to override the heuristics of the analysis
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hints: Option<PathBuf>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct ConfigOverlay {
     pub id: u16,
+    /// Progress category to group this overlay under in objdiff, defaults to one category per overlay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
     #[serde(flatten)]
     pub module: ConfigModule,
 }
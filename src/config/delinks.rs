@@ -4,18 +4,25 @@ use std::{
     fmt::Display,
     fs::File,
     io::{BufRead, BufReader, BufWriter, Lines, Write},
+    ops::Range,
     path::Path,
 };
 
 use anyhow::{bail, Context, Result};
 use ds_rom::rom::raw::AutoloadKind;
 use petgraph::{graph::NodeIndex, Graph};
+use serde::{Deserialize, Serialize, Serializer};
 
-use crate::util::io::{create_file, open_file};
+use crate::{
+    analysis::exception,
+    util::io::{create_file, is_json_file, open_file},
+};
 
 use super::{
     module::ModuleKind,
-    section::{Section, Sections},
+    relocation::Relocations,
+    section::{Section, SectionKind, Sections},
+    symbol::SymbolMap,
     ParseContext,
 };
 
@@ -32,9 +39,44 @@ pub struct DelinkFile {
     gap: bool,
 }
 
+/// Layout of a delinks file in JSON. Gap files are not included, as they are generated when loading.
+#[derive(Serialize)]
+struct DelinksJson<'a> {
+    sections: &'a Sections,
+    files: Vec<&'a DelinkFile>,
+}
+
+#[derive(Deserialize)]
+struct DelinksJsonData {
+    sections: Sections,
+    #[serde(default)]
+    files: Vec<DelinkFileData>,
+}
+
+/// A delink file in JSON. Like in the text format, its sections inherit their kind and alignment from the header, so
+/// a [`DelinkFile`] can only be deserialized along with the rest of the delinks file.
+#[derive(Serialize, Deserialize)]
+struct DelinkFileData {
+    name: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    complete: bool,
+    sections: Vec<DelinkFileSectionData>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DelinkFileSectionData {
+    name: String,
+    start: u32,
+    end: u32,
+}
+
 impl Delinks {
     pub fn from_file<P: AsRef<Path>>(path: P, module_kind: ModuleKind) -> Result<Self> {
         let path = path.as_ref();
+        if is_json_file(path) {
+            return Self::from_json_file(path, module_kind);
+        }
+
         let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
 
         let file = open_file(path)?;
@@ -67,6 +109,34 @@ impl Delinks {
         Ok(delinks)
     }
 
+    fn from_json_file(path: &Path, module_kind: ModuleKind) -> Result<Self> {
+        let DelinksJsonData { sections, files } = serde_json::from_reader(BufReader::new(open_file(path)?))
+            .with_context(|| format!("Failed to parse delinks in '{}'", path.display()))?;
+
+        let files = files
+            .into_iter()
+            .map(|file| {
+                let mut file_sections = Sections::new();
+                for section in file.sections {
+                    let Some(header_section) = sections.by_name(&section.name) else {
+                        bail!(
+                            "{}: section {} in file '{}' does not exist in the header",
+                            path.display(),
+                            section.name,
+                            file.name
+                        );
+                    };
+                    file_sections.add(Section::inherit(header_section, section.start, section.end)?)?;
+                }
+                Ok(DelinkFile::new(file.name, file_sections, file.complete))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut delinks = Delinks { sections, files, module_kind };
+        delinks.generate_gap_files()?;
+        Ok(delinks)
+    }
+
     fn try_parse_delink_file(
         line: &str,
         lines: &mut Lines<BufReader<File>>,
@@ -83,15 +153,20 @@ impl Delinks {
         }
     }
 
-    pub fn to_file<P: AsRef<Path>>(path: P, sections: &Sections) -> Result<()> {
+    /// Writes the header sections and delink files. Gap files are left out, as they're generated when loading the file.
+    pub fn to_file<P: AsRef<Path>>(path: P, sections: &Sections, files: &[DelinkFile]) -> Result<()> {
         let path = path.as_ref();
 
         let file = create_file(path)?;
         let mut writer = BufWriter::new(file);
 
-        write!(writer, "{}", DisplayDelinks { sections, files: &[] })?;
-
-        // TODO: Export delink files here? This function was made for generating a config, and delink files are not generated currently.
+        if is_json_file(path) {
+            let files = files.iter().filter(|file| !file.gap).collect();
+            serde_json::to_writer_pretty(&mut writer, &DelinksJson { sections, files })?;
+            writeln!(writer)?;
+            return Ok(());
+        }
+        write!(writer, "{}", DisplayDelinks { sections, files })?;
 
         Ok(())
     }
@@ -100,6 +175,148 @@ impl Delinks {
         DisplayDelinks { sections: &self.sections, files: &self.files }
     }
 
+    /// Cuts linker-generated veneers out of the gap files, so that they're left out of the delinked objects and the linker
+    /// can generate them again. Veneers in the user's own files are delinked along with the file.
+    pub fn exclude_veneers(&mut self, symbol_map: &SymbolMap) -> Result<()> {
+        let mut veneers =
+            symbol_map.veneers().map(|(veneer, symbol)| symbol.addr..symbol.addr + veneer.size).collect::<Vec<_>>();
+        if veneers.is_empty() {
+            return Ok(());
+        }
+        veneers.sort_by_key(|veneer| veneer.start);
+
+        let mut split_files = vec![];
+        for file in &mut self.files {
+            let mut sections = Sections::new();
+            for section in file.sections.iter() {
+                let contained = veneers
+                    .iter()
+                    .filter(|veneer| veneer.start >= section.start_address() && veneer.end <= section.end_address());
+
+                if !file.gap {
+                    for veneer in contained {
+                        log::warn!(
+                            "File '{}' contains a veneer at {:#010x}, it will be delinked along with the file",
+                            file.name,
+                            veneer.start
+                        );
+                    }
+                    sections.add(Section::inherit(section, section.start_address(), section.end_address())?)?;
+                    continue;
+                }
+
+                let mut pieces = vec![];
+                let mut start = section.start_address();
+                for veneer in contained {
+                    if start < veneer.start {
+                        pieces.push(start..veneer.start);
+                    }
+                    start = veneer.end;
+                }
+                if start < section.end_address() {
+                    pieces.push(start..section.end_address());
+                }
+
+                // The first piece stays in this gap, the rest get their own gap files
+                let mut pieces = pieces.into_iter();
+                if let Some(first) = pieces.next() {
+                    sections.add(Section::inherit(section, first.start, first.end)?)?;
+                }
+                for piece in pieces {
+                    let mut gap = DelinkFile::new_split_gap(&file.name, split_files.len());
+                    gap.sections.add(Section::inherit(section, piece.start, piece.end)?)?;
+                    split_files.push(gap);
+                }
+            }
+            file.sections = sections;
+        }
+
+        self.files.retain(|file| file.sections.len() > 0);
+        self.files.extend(split_files);
+        self.sort_files()?;
+        self.validate_files()?;
+        Ok(())
+    }
+
+    /// Gives each file the `.exceptix` entries and `.exception` tables of its functions, so that they're delinked along
+    /// with the functions. Each index entry points to its function and table, so the relocations tell which file the
+    /// entry belongs to. Files which list `.exceptix` or `.exception` themselves are left as they are.
+    pub fn assign_exception_tables(&mut self, relocations: &Relocations) -> Result<()> {
+        let (Some(index), Some(tables)) = (self.sections.by_name(".exceptix"), self.sections.by_name(".exception")) else {
+            return Ok(());
+        };
+
+        // Function address, index entry and table start of each entry
+        let mut entries = vec![];
+        for entry in index.address_range().step_by(exception::INDEX_ENTRY_SIZE as usize) {
+            let (Some(function), Some(table)) = (relocations.get(entry), relocations.get(entry + 8)) else {
+                log::warn!("Exception index entry at {entry:#010x} has no relocations to its function and table");
+                continue;
+            };
+            entries.push((function.to_address() & !1, entry, table.to_address()));
+        }
+        let mut table_starts = entries.iter().map(|&(_, _, table)| table).collect::<Vec<_>>();
+        table_starts.sort_unstable();
+        table_starts.dedup();
+        let table_range = |start: u32| {
+            let end = table_starts.iter().find(|&&next| next > start).copied().unwrap_or(tables.end_address());
+            start..end
+        };
+
+        let mut assigned = false;
+        for file in self.files.iter_mut().filter(|file| !file.gap) {
+            if file.sections.by_name(".exceptix").is_some() || file.sections.by_name(".exception").is_some() {
+                continue;
+            }
+            let file_entries = entries
+                .iter()
+                .filter(|(function, _, _)| {
+                    file.sections
+                        .iter()
+                        .any(|section| section.kind() == SectionKind::Code && section.address_range().contains(function))
+                })
+                .collect::<Vec<_>>();
+            if file_entries.is_empty() {
+                continue;
+            }
+
+            let file_index =
+                Self::contiguous_range(file_entries.iter().map(|&&(_, entry, _)| entry..entry + exception::INDEX_ENTRY_SIZE));
+            let file_tables = Self::contiguous_range(file_entries.iter().map(|&&(_, _, table)| table_range(table)));
+            let (Some(file_index), Some(file_tables)) = (file_index, file_tables) else {
+                log::warn!(
+                    "The exception tables of file '{}' are not contiguous, add .exception and .exceptix to the file manually",
+                    file.name
+                );
+                continue;
+            };
+            file.sections.add(Section::inherit(tables, file_tables.start, file_tables.end)?)?;
+            file.sections.add(Section::inherit(index, file_index.start, file_index.end)?)?;
+            assigned = true;
+        }
+
+        if assigned {
+            self.files.retain(|file| !file.gap);
+            self.generate_gap_files()?;
+        }
+        Ok(())
+    }
+
+    /// Joins the ranges into one, or returns `None` if there are holes between them.
+    fn contiguous_range(ranges: impl Iterator<Item = Range<u32>>) -> Option<Range<u32>> {
+        let mut ranges = ranges.collect::<Vec<_>>();
+        ranges.sort_unstable_by_key(|range| range.start);
+        ranges.dedup();
+        let mut joined = ranges.first()?.clone();
+        for range in &ranges[1..] {
+            if range.start != joined.end {
+                return None;
+            }
+            joined.end = range.end;
+        }
+        Some(joined)
+    }
+
     fn generate_gap_files(&mut self) -> Result<()> {
         self.sort_files()?;
         self.validate_files()?;
@@ -263,7 +480,7 @@ impl<'a> Display for DisplayDelinks<'a> {
             writeln!(f, "    {section}")?;
         }
         writeln!(f)?;
-        for file in self.files {
+        for file in self.files.iter().filter(|file| !file.gap) {
             writeln!(f, "{file}")?;
         }
         Ok(())
@@ -292,6 +509,10 @@ impl DelinkFile {
         Ok(Self { name, sections: Sections::new(), complete: false, gap: true })
     }
 
+    fn new_split_gap(gap_name: &str, id: usize) -> Self {
+        Self { name: format!("{gap_name}_{id}"), sections: Sections::new(), complete: false, gap: true }
+    }
+
     pub fn parse(
         first_line: &str,
         lines: &mut Lines<BufReader<File>>,
@@ -333,11 +554,37 @@ impl DelinkFile {
     }
 }
 
+impl Serialize for DelinkFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sections = self
+            .sections
+            .sorted_by_address()
+            .into_iter()
+            .map(|section| DelinkFileSectionData {
+                name: section.name().to_string(),
+                start: section.start_address(),
+                end: section.end_address(),
+            })
+            .collect();
+        DelinkFileData { name: self.name.clone(), complete: self.complete, sections }.serialize(serializer)
+    }
+}
+
 impl Display for DelinkFile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{}:", self.name)?;
+        if self.complete {
+            writeln!(f, "    complete")?;
+        }
         for section in self.sections.sorted_by_address() {
-            writeln!(f, "    {section}")?;
+            // Kind and alignment are inherited from the header
+            writeln!(
+                f,
+                "    {:11} start:0x{:08x} end:0x{:08x}",
+                section.name(),
+                section.start_address(),
+                section.end_address()
+            )?;
         }
         Ok(())
     }
@@ -0,0 +1,144 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::util::{io::open_file, parse::parse_u32};
+
+use super::{iter_attributes, symbol::InstructionMode, unknown_attribute, ParseContext};
+
+/// Hints written by the user to override the heuristics of the analysis. Each line in `hints.txt` is a directive followed
+/// by its attributes, and `//` starts a comment:
+///
+/// ```text
+/// function addr:0x02000800 end:0x02000840 mode:thumb  // force function start, end and mode are optional
+/// inline_table addr:0x02000900 size:0x20
+/// jump_table addr:0x02000a00 size:0x18
+/// tail_call addr:0x02000b04                           // branch instruction that leaves the function
+/// not_pointer addr:0x02000c10                         // word that only looks like a pointer
+/// ```
+#[derive(Default, Hash)]
+pub struct Hints {
+    functions: BTreeMap<u32, FunctionHint>,
+    inline_tables: BTreeMap<u32, u32>,
+    jump_tables: BTreeMap<u32, u32>,
+    tail_calls: BTreeSet<u32>,
+    not_pointers: BTreeSet<u32>,
+}
+
+#[derive(Clone, Copy, Hash)]
+pub struct FunctionHint {
+    /// Forced end address of the function
+    pub end: Option<u32>,
+    /// Forced instruction mode of the function
+    pub thumb: Option<bool>,
+}
+
+impl Hints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+
+        let file = open_file(path)?;
+        let reader = BufReader::new(file);
+
+        let mut hints = Self::new();
+        for line in reader.lines() {
+            context.row += 1;
+            hints.parse_line(line?.as_str(), &context)?;
+        }
+        Ok(hints)
+    }
+
+    /// Loads the hints file if there is one, otherwise returns no hints.
+    pub fn from_optional_file<P: AsRef<Path>>(path: Option<P>) -> Result<Self> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::new()),
+        }
+    }
+
+    fn parse_line(&mut self, line: &str, context: &ParseContext) -> Result<()> {
+        let line = line.split_once("//").map(|(line, _comment)| line).unwrap_or(line);
+        let mut words = line.split_whitespace();
+        let Some(directive) = words.next() else { return Ok(()) };
+
+        let mut addr = None;
+        let mut end = None;
+        let mut size = None;
+        let mut thumb = None;
+        for (key, value) in iter_attributes(words) {
+            match key {
+                "addr" => {
+                    addr = Some(parse_u32(value).with_context(|| format!("{context}: failed to parse address '{value}'"))?)
+                }
+                "end" => {
+                    end = Some(parse_u32(value).with_context(|| format!("{context}: failed to parse end address '{value}'"))?)
+                }
+                "size" => size = Some(parse_u32(value).with_context(|| format!("{context}: failed to parse size '{value}'"))?),
+                "mode" => thumb = InstructionMode::parse(value, context)?.into_thumb(),
+                _ => return Err(unknown_attribute(context, "hint attribute 'addr', 'end', 'size' or 'mode'", key)),
+            }
+        }
+        let addr = addr.with_context(|| format!("{context}: missing 'addr' attribute"))?;
+
+        match directive {
+            "function" => {
+                self.functions.insert(addr, FunctionHint { end, thumb });
+            }
+            "inline_table" => {
+                let size = size.with_context(|| format!("{context}: missing 'size' attribute"))?;
+                self.inline_tables.insert(addr, size);
+            }
+            "jump_table" => {
+                let size = size.with_context(|| format!("{context}: missing 'size' attribute"))?;
+                self.jump_tables.insert(addr, size);
+            }
+            "tail_call" => {
+                self.tail_calls.insert(addr);
+            }
+            "not_pointer" => {
+                self.not_pointers.insert(addr);
+            }
+            _ => {
+                return Err(unknown_attribute(
+                    context,
+                    "hint 'function', 'inline_table', 'jump_table', 'tail_call' or 'not_pointer'",
+                    directive,
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn function(&self, address: u32) -> Option<&FunctionHint> {
+        self.functions.get(&address)
+    }
+
+    pub fn is_function_start(&self, address: u32) -> bool {
+        self.functions.contains_key(&address)
+    }
+
+    pub fn inline_table_size(&self, address: u32) -> Option<u32> {
+        self.inline_tables.get(&address).copied()
+    }
+
+    pub fn jump_table_size(&self, address: u32) -> Option<u32> {
+        self.jump_tables.get(&address).copied()
+    }
+
+    pub fn is_tail_call(&self, address: u32) -> bool {
+        self.tail_calls.contains(&address)
+    }
+
+    pub fn is_not_pointer(&self, address: u32) -> bool {
+        self.not_pointers.contains(&address)
+    }
+}
@@ -1,22 +1,76 @@
-use std::{fmt::Display, str::SplitWhitespace};
+use std::{
+    fmt::Display,
+    io::{BufRead, BufReader},
+    path::Path,
+    str::SplitWhitespace,
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::util::io::open_file;
 
 pub mod config;
 pub mod delinks;
+pub mod hints;
 pub mod module;
+pub mod overlay_loads;
 pub mod program;
 pub mod relocation;
+pub mod renames;
 pub mod section;
 pub mod symbol;
+pub mod types;
 
+#[derive(Clone)]
 pub struct ParseContext {
     file_path: String,
     row: usize,
 }
 
+impl ParseContext {
+    /// Context for values passed as command line arguments instead of read from a file.
+    pub fn command_line() -> Self {
+        Self { file_path: "command line".to_string(), row: 0 }
+    }
+}
+
 impl Display for ParseContext {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}", self.file_path, self.row)
+        if self.row == 0 {
+            write!(f, "{}", self.file_path)
+        } else {
+            write!(f, "{}:{}", self.file_path, self.row)
+        }
+    }
+}
+
+/// Parses every line of a text file and returns each item with the location it was read from. Unlike loading the file
+/// normally, parsing continues past lines with errors, so that all problems in the file can be reported at once.
+pub fn parse_lines<T, F>(path: &Path, parse: F) -> Result<Vec<Result<(ParseContext, T)>>>
+where
+    F: Fn(&str, &ParseContext) -> Result<Option<T>>,
+{
+    let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+
+    let file = open_file(path)?;
+    let reader = BufReader::new(file);
+
+    let mut items = vec![];
+    for line in reader.lines() {
+        context.row += 1;
+        match parse(line?.as_str(), &context) {
+            Ok(Some(item)) => items.push(Ok((context.clone(), item))),
+            Ok(None) => {}
+            Err(err) => items.push(Err(err)),
+        }
     }
+    Ok(items)
+}
+
+/// Error for an attribute, kind or directive which this version of dsd doesn't know. Formats only gain these over time, so
+/// an unknown one most likely means that the file was written by a newer dsd.
+pub fn unknown_attribute(context: &ParseContext, expected: &str, key: &str) -> anyhow::Error {
+    anyhow!("{context}: expected {expected} but got '{key}', the file may have been written by a newer dsd")
 }
 
 pub fn iter_attributes<'a>(words: SplitWhitespace<'a>) -> ParseAttributesIterator<'a> {
@@ -1,12 +1,19 @@
-use std::{collections::BTreeMap, fmt::Display};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    ops::Range,
+    path::Path,
+};
 
 use anyhow::{bail, Context, Result};
 use ds_rom::rom::{raw::AutoloadKind, Arm9, Autoload, Overlay};
 
 use crate::{
     analysis::{
+        cache::AnalysisCache,
         ctor::CtorRange,
         data,
+        exception::ExceptionTables,
         functions::{FindFunctionsOptions, Function, ParseFunctionOptions, ParseFunctionResult},
         main::MainFunction,
     },
@@ -14,15 +21,17 @@ use crate::{
 };
 
 use super::{
+    hints::Hints,
     relocation::Relocations,
     section::{Section, Sections},
-    symbol::{SymData, SymbolMap, SymbolMaps},
+    symbol::{SymData, SymbolMap},
 };
 
 pub struct Module<'a> {
     name: String,
     kind: ModuleKind,
     relocations: Relocations,
+    hints: Hints,
     code: &'a [u8],
     base_address: u32,
     bss_size: u32,
@@ -36,17 +45,20 @@ impl<'a> Module<'a> {
         name: String,
         symbol_map: &mut SymbolMap,
         relocations: Relocations,
+        hints: Hints,
         mut sections: Sections,
         code: &'a [u8],
+        cache_path: Option<&Path>,
     ) -> Result<Module<'a>> {
         let base_address = sections.base_address().context("no sections provided")?;
         let end_address = sections.end_address().context("no sections provided")?;
         let bss_size = sections.bss_size();
-        Self::import_functions(symbol_map, &mut sections, base_address, end_address, code)?;
+        Self::import_functions(symbol_map, &mut sections, base_address, end_address, code, &hints, cache_path)?;
         Ok(Self {
             name,
             kind: ModuleKind::Arm9,
             relocations,
+            hints,
             code,
             base_address,
             bss_size,
@@ -56,7 +68,7 @@ impl<'a> Module<'a> {
         })
     }
 
-    pub fn analyze_arm9(arm9: &'a Arm9, symbol_maps: &mut SymbolMaps) -> Result<Self> {
+    pub fn analyze_arm9(arm9: &'a Arm9, symbol_map: &mut SymbolMap, hints: Hints) -> Result<Self> {
         let ctor_range = CtorRange::find_in_arm9(&arm9)?;
         let main_func = MainFunction::find_in_arm9(&arm9)?;
 
@@ -64,6 +76,7 @@ impl<'a> Module<'a> {
             name: "main".to_string(),
             kind: ModuleKind::Arm9,
             relocations: Relocations::new(),
+            hints,
             code: arm9.code()?,
             base_address: arm9.base_address(),
             bss_size: arm9.bss()?.len() as u32,
@@ -71,7 +84,6 @@ impl<'a> Module<'a> {
             default_data_prefix: "data_".to_string(),
             sections: Sections::new(),
         };
-        let symbol_map = symbol_maps.get_mut(module.kind);
 
         module.find_sections_arm9(symbol_map, ctor_range, main_func, &arm9)?;
         module.find_data_from_pools(symbol_map)?;
@@ -87,18 +99,21 @@ impl<'a> Module<'a> {
         name: String,
         symbol_map: &mut SymbolMap,
         relocations: Relocations,
+        hints: Hints,
         mut sections: Sections,
         id: u16,
         code: &'a [u8],
+        cache_path: Option<&Path>,
     ) -> Result<Self> {
         let base_address = sections.base_address().context("no sections provided")?;
         let end_address = sections.end_address().context("no sections provided")?;
         let bss_size = sections.bss_size();
-        Self::import_functions(symbol_map, &mut sections, base_address, end_address, code)?;
+        Self::import_functions(symbol_map, &mut sections, base_address, end_address, code, &hints, cache_path)?;
         Ok(Self {
             name,
             kind: ModuleKind::Overlay(id),
             relocations,
+            hints,
             code,
             base_address,
             bss_size,
@@ -108,11 +123,12 @@ impl<'a> Module<'a> {
         })
     }
 
-    pub fn analyze_overlay(overlay: &'a Overlay, symbol_maps: &mut SymbolMaps) -> Result<Self> {
+    pub fn analyze_overlay(overlay: &'a Overlay, symbol_map: &mut SymbolMap, hints: Hints) -> Result<Self> {
         let mut module = Self {
             name: format!("ov{:03}", overlay.id()),
             kind: ModuleKind::Overlay(overlay.id()),
             relocations: Relocations::new(),
+            hints,
             code: overlay.code(),
             base_address: overlay.base_address(),
             bss_size: overlay.bss_size(),
@@ -120,7 +136,6 @@ impl<'a> Module<'a> {
             default_data_prefix: format!("data_ov{:03}_", overlay.id()),
             sections: Sections::new(),
         };
-        let symbol_map = symbol_maps.get_mut(module.kind);
 
         log::debug!("Analyzing overlay {}", overlay.id());
         module.find_sections_overlay(symbol_map, CtorRange { start: overlay.ctor_start(), end: overlay.ctor_end() })?;
@@ -134,18 +149,21 @@ impl<'a> Module<'a> {
         name: String,
         symbol_map: &mut SymbolMap,
         relocations: Relocations,
+        hints: Hints,
         mut sections: Sections,
         kind: AutoloadKind,
         code: &'a [u8],
+        cache_path: Option<&Path>,
     ) -> Result<Self> {
         let base_address = sections.base_address().context("no sections provided")?;
         let end_address = sections.end_address().context("no sections provided")?;
         let bss_size = sections.bss_size();
-        Self::import_functions(symbol_map, &mut sections, base_address, end_address, code)?;
+        Self::import_functions(symbol_map, &mut sections, base_address, end_address, code, &hints, cache_path)?;
         Ok(Self {
             name,
             kind: ModuleKind::Autoload(kind),
             relocations,
+            hints,
             code,
             base_address,
             bss_size,
@@ -155,11 +173,12 @@ impl<'a> Module<'a> {
         })
     }
 
-    pub fn analyze_itcm(autoload: &'a Autoload, symbol_maps: &mut SymbolMaps) -> Result<Self> {
+    pub fn analyze_itcm(autoload: &'a Autoload, symbol_map: &mut SymbolMap, hints: Hints) -> Result<Self> {
         let mut module = Self {
             name: "itcm".to_string(),
             kind: ModuleKind::Autoload(AutoloadKind::Itcm),
             relocations: Relocations::new(),
+            hints,
             code: autoload.code(),
             base_address: autoload.base_address(),
             bss_size: autoload.bss_size(),
@@ -167,7 +186,6 @@ impl<'a> Module<'a> {
             default_data_prefix: "data_".to_string(),
             sections: Sections::new(),
         };
-        let symbol_map = symbol_maps.get_mut(module.kind);
 
         module.find_sections_itcm(symbol_map)?;
         module.find_data_from_pools(symbol_map)?;
@@ -175,11 +193,12 @@ impl<'a> Module<'a> {
         Ok(module)
     }
 
-    pub fn analyze_dtcm(autoload: &'a Autoload, symbol_maps: &mut SymbolMaps) -> Result<Self> {
+    pub fn analyze_dtcm(autoload: &'a Autoload, symbol_map: &mut SymbolMap, hints: Hints) -> Result<Self> {
         let mut module = Self {
             name: "dtcm".to_string(),
             kind: ModuleKind::Autoload(AutoloadKind::Dtcm),
             relocations: Relocations::new(),
+            hints,
             code: autoload.code(),
             base_address: autoload.base_address(),
             bss_size: autoload.bss_size(),
@@ -187,7 +206,6 @@ impl<'a> Module<'a> {
             default_data_prefix: "data_".to_string(),
             sections: Sections::new(),
         };
-        let symbol_map = symbol_maps.get_mut(module.kind);
 
         module.find_sections_dtcm()?;
         module.find_data_from_sections(symbol_map)?;
@@ -201,8 +219,39 @@ impl<'a> Module<'a> {
         base_address: u32,
         end_address: u32,
         code: &'a [u8],
+        hints: &Hints,
+        cache_path: Option<&Path>,
     ) -> Result<()> {
-        for (sym_function, symbol) in symbol_map.clone_functions() {
+        let cache_key = AnalysisCache::key(code, base_address, end_address, symbol_map, hints);
+        let functions = match cache_path.and_then(|path| AnalysisCache::load(path, cache_key)) {
+            Some(functions) => functions,
+            None => {
+                let functions = Self::parse_functions(symbol_map, base_address, end_address, code, hints)?;
+                if let Some(path) = cache_path {
+                    AnalysisCache::save(path, cache_key, &functions)?;
+                }
+                functions
+            }
+        };
+
+        for function in functions {
+            function.add_local_symbols_to_map(symbol_map)?;
+            sections.add_function(function);
+        }
+        Ok(())
+    }
+
+    fn parse_functions(
+        symbol_map: &SymbolMap,
+        base_address: u32,
+        end_address: u32,
+        code: &'a [u8],
+        hints: &Hints,
+    ) -> Result<Vec<Function>> {
+        let function_starts: BTreeSet<u32> =
+            symbol_map.functions().map(|(sym_function, symbol)| symbol.addr + sym_function.offset).collect();
+        let mut functions = vec![];
+        for (sym_function, symbol) in symbol_map.functions() {
             let offset = symbol.addr - base_address;
             let size = sym_function.size;
             let parse_result = Function::parse_known_function()
@@ -214,15 +263,16 @@ impl<'a> Module<'a> {
                 .options(ParseFunctionOptions { thumb: sym_function.mode.into_thumb() })
                 .module_start_address(base_address)
                 .module_end_address(end_address)
+                .hints(hints)
+                .function_starts(&function_starts)
                 .call()?;
             let function = match parse_result {
                 ParseFunctionResult::Found(function) => function,
                 _ => bail!("function {} could not be analyzed: {:?}", symbol.name, parse_result),
             };
-            function.add_local_symbols_to_map(symbol_map)?;
-            sections.add_function(function);
+            functions.push(function);
         }
-        Ok(())
+        Ok(functions)
     }
 
     fn find_functions(
@@ -238,6 +288,7 @@ impl<'a> Module<'a> {
             .options(options)
             .module_start_address(self.base_address)
             .module_end_address(self.end_address())
+            .hints(&self.hints)
             .call()?;
 
         if functions.len() == 0 {
@@ -271,11 +322,12 @@ impl<'a> Module<'a> {
         }
     }
 
-    /// Adds the .init section to this module. Returns the start and end address of the .init section.
+    /// Adds the .init section to this module. Returns the start and end address of the .init section. If `continuous` is
+    /// true, .init must end at `read_only_end`, which is the start of the exception tables or .ctor.
     fn add_init_section(
         &mut self,
         symbol_map: &mut SymbolMap,
-        ctor: &CtorRange,
+        read_only_end: u32,
         function_range: InitFunctionRange,
         continuous: bool,
     ) -> Result<Option<(u32, u32)>> {
@@ -295,7 +347,7 @@ impl<'a> Module<'a> {
                 )
             })?;
         // Functions in .ctor can sometimes point to .text instead of .init
-        if !continuous || init_end == ctor.start {
+        if !continuous || init_end == read_only_end {
             self.sections.add(Section::with_functions(
                 ".init".to_string(),
                 SectionKind::Code,
@@ -310,6 +362,70 @@ impl<'a> Module<'a> {
         }
     }
 
+    /// Adds the .exception and .exceptix sections to this module, if they exist right before `end`. Each function's entry
+    /// in the exception index and its exception table are given symbols named after the function. Returns the start of the
+    /// exception tables, or `end` if there are none, along with the functions in the index.
+    fn add_exception_sections(&mut self, symbol_map: &mut SymbolMap, end: u32) -> Result<(u32, Vec<Range<u32>>)> {
+        let Some(exception_tables) = ExceptionTables::find(self.code, self.base_address, end) else {
+            return Ok((end, vec![]));
+        };
+        log::debug!(
+            "Found {} exception table(s) in {} at {:#x}..{:#x}",
+            exception_tables.entries.len(),
+            self.kind,
+            exception_tables.tables.start,
+            exception_tables.index.end
+        );
+
+        let tables = &exception_tables.tables;
+        let index = &exception_tables.index;
+        self.sections.add(Section::new(".exception".to_string(), SectionKind::Exception, tables.start, tables.end, 4)?)?;
+        self.sections.add(Section::new(".exceptix".to_string(), SectionKind::ExceptionIndex, index.start, index.end, 4)?)?;
+
+        let infix = self.default_func_prefix.strip_prefix("func_").unwrap_or_default();
+        for (entry, table) in exception_tables.table_ranges() {
+            let function = entry.function_address();
+            symbol_map.add_data(
+                Some(format!("@eti_{infix}{function:08x}")),
+                entry.address,
+                SymData::Word { count: Some(3) },
+            )?;
+            symbol_map.add_data(
+                Some(format!("@etb_{infix}{function:08x}")),
+                table.start,
+                SymData::Byte { count: Some(table.end - table.start) },
+            )?;
+        }
+
+        Ok((tables.start, exception_tables.entries.iter().map(|entry| entry.function_range()).collect()))
+    }
+
+    /// Checks that the functions in the exception index are inside the code sections, which are only known after the
+    /// exception tables have been found.
+    fn check_exception_functions(&self, functions: &[Range<u32>]) -> Result<()> {
+        for function in functions {
+            let in_code = self
+                .sections
+                .get_by_contained_address(function.start)
+                .is_some_and(|(_, section)| section.kind() == SectionKind::Code && function.end <= section.end_address());
+            if !in_code {
+                log::error!(
+                    "Exception index in {} has a function at {:#x}..{:#x} outside of the code sections",
+                    self.kind,
+                    function.start,
+                    function.end
+                );
+                bail!(
+                    "Exception index in {} has a function at {:#x}..{:#x} outside of the code sections",
+                    self.kind,
+                    function.start,
+                    function.end
+                );
+            }
+        }
+        Ok(())
+    }
+
     /// Adds the .text section to this module.
     fn add_text_section(&mut self, functions: BTreeMap<u32, Function>, start: u32, end: u32) -> Result<()> {
         if start < end {
@@ -338,14 +454,15 @@ impl<'a> Module<'a> {
     }
 
     fn find_sections_overlay(&mut self, symbol_map: &mut SymbolMap, ctor: CtorRange) -> Result<()> {
+        let (exception_start, exception_functions) = self.add_exception_sections(symbol_map, ctor.start)?;
         let rodata_end = if let Some(function_range) = self.add_ctor_section(&ctor)? {
-            if let Some((init_start, _)) = self.add_init_section(symbol_map, &ctor, function_range, true)? {
+            if let Some((init_start, _)) = self.add_init_section(symbol_map, exception_start, function_range, true)? {
                 init_start
             } else {
-                ctor.start
+                exception_start
             }
         } else {
-            ctor.start
+            exception_start
         };
 
         let rodata_start = if let Some((text_functions, text_start, text_end)) = self.find_functions(
@@ -357,6 +474,7 @@ impl<'a> Module<'a> {
         } else {
             self.base_address
         };
+        self.check_exception_functions(&exception_functions)?;
 
         self.add_rodata_section(rodata_start, rodata_end)?;
 
@@ -375,17 +493,20 @@ impl<'a> Module<'a> {
         main_func: MainFunction,
         arm9: &Arm9,
     ) -> Result<()> {
+        // .exception and .exceptix
+        let (exception_start, exception_functions) = self.add_exception_sections(symbol_map, ctor.start)?;
+
         // .ctor and .init
         let (read_only_end, rodata_start) = if let Some(function_range) = self.add_ctor_section(&ctor)? {
-            if let Some(init_range) = self.add_init_section(symbol_map, &ctor, function_range, false)? {
+            if let Some(init_range) = self.add_init_section(symbol_map, exception_start, function_range, false)? {
                 (init_range.0, Some(init_range.1))
             } else {
-                (ctor.start, None)
+                (exception_start, None)
             }
         } else {
-            (ctor.start, None)
+            (exception_start, None)
         };
-        let has_init_section = read_only_end != ctor.start;
+        let has_init_section = read_only_end != exception_start;
 
         // Secure area functions (software interrupts)
         let secure_area = &self.code[..0x800];
@@ -406,6 +527,7 @@ impl<'a> Module<'a> {
             .options(ParseFunctionOptions { thumb: None })
             .module_start_address(self.base_address)
             .module_end_address(self.end_address())
+            .hints(&self.hints)
             .call()?
         {
             ParseFunctionResult::Found(function) => function,
@@ -451,10 +573,11 @@ impl<'a> Module<'a> {
         }
         functions.extend(text_functions);
         self.add_text_section(functions, text_start, text_end)?;
+        self.check_exception_functions(&exception_functions)?;
 
         // .rodata
         let rodata_start = rodata_start.unwrap_or(text_end);
-        self.add_rodata_section(rodata_start, ctor.start)?;
+        self.add_rodata_section(rodata_start, exception_start)?;
 
         // .data and .bss
         let data_start = ctor.end.next_multiple_of(32);
@@ -507,7 +630,16 @@ impl<'a> Module<'a> {
                 .name_prefix(&self.default_data_prefix)
                 .module_code(&self.code)
                 .base_address(self.base_address)
+                .hints(&self.hints)
                 .call()?;
+            data::find_local_data_from_pc_relative_adds(
+                function,
+                &self.sections,
+                self.kind,
+                symbol_map,
+                &mut self.relocations,
+                &self.default_data_prefix,
+            )?;
         }
         Ok(())
     }
@@ -515,17 +647,18 @@ impl<'a> Module<'a> {
     fn find_data_from_sections(&mut self, symbol_map: &mut SymbolMap) -> Result<()> {
         for section in self.sections.iter() {
             match section.kind() {
-                SectionKind::Data => {
+                SectionKind::Data | SectionKind::Exception | SectionKind::ExceptionIndex => {
                     let code = section.code(&self.code, self.base_address)?.unwrap();
-                    data::find_local_data_from_section(
-                        &self.sections,
-                        section,
-                        code,
-                        self.kind,
-                        symbol_map,
-                        &mut self.relocations,
-                        &self.default_data_prefix,
-                    )?;
+                    data::find_local_data_from_section()
+                        .sections(&self.sections)
+                        .section(section)
+                        .code(code)
+                        .module_kind(self.kind)
+                        .symbol_map(symbol_map)
+                        .relocations(&mut self.relocations)
+                        .name_prefix(&self.default_data_prefix)
+                        .hints(&self.hints)
+                        .call()?;
                 }
                 SectionKind::Bss | SectionKind::Code => {}
             }
@@ -576,6 +709,10 @@ impl<'a> Module<'a> {
     pub fn kind(&self) -> ModuleKind {
         self.kind
     }
+
+    pub fn hints(&self) -> &Hints {
+        &self.hints
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
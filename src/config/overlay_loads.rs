@@ -0,0 +1,148 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use ds_rom::rom::raw::AutoloadKind;
+
+use crate::util::{
+    io::{create_file, open_file},
+    parse::{parse_u16, parse_u32},
+};
+
+use super::{iter_attributes, module::ModuleKind, relocation::RelocationModule, unknown_attribute, ParseContext};
+
+/// Calls to the overlay loader found by the analysis. Each line in `overlay_loads.txt` is one call, with the module and
+/// address of the call and the ID of the overlay it loads:
+///
+/// ```text
+/// from:0x02001234 module:main overlay:3
+/// from:0x020c5678 module:overlay(3) overlay:12
+/// ```
+#[derive(Default)]
+pub struct OverlayLoads {
+    loads: BTreeSet<OverlayLoad>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OverlayLoad {
+    /// Module containing the call to the overlay loader
+    pub module: ModuleKind,
+    /// Address of the call to the overlay loader
+    pub from: u32,
+    /// Overlay being loaded
+    pub overlay: u16,
+}
+
+impl OverlayLoads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+
+        let file = open_file(path)?;
+        let reader = BufReader::new(file);
+
+        let mut loads = Self::new();
+        for line in reader.lines() {
+            context.row += 1;
+            let Some(load) = OverlayLoad::parse(line?.as_str(), &context)? else {
+                continue;
+            };
+            loads.add(load);
+        }
+        Ok(loads)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = create_file(path)?;
+        let mut writer = BufWriter::new(file);
+
+        for load in &self.loads {
+            writeln!(writer, "{load}")?;
+        }
+        Ok(())
+    }
+
+    pub fn add(&mut self, load: OverlayLoad) {
+        self.loads.insert(load);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &OverlayLoad> {
+        self.loads.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.loads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loads.is_empty()
+    }
+
+    /// Returns true if the given module contains a call which loads the overlay.
+    pub fn loads(&self, module: ModuleKind, overlay: u16) -> bool {
+        self.loads.iter().any(|load| load.module == module && load.overlay == overlay)
+    }
+}
+
+impl OverlayLoad {
+    fn parse(line: &str, context: &ParseContext) -> Result<Option<Self>> {
+        let words = line.split_whitespace();
+
+        let mut from = None;
+        let mut module = None;
+        let mut overlay = None;
+        for (key, value) in iter_attributes(words) {
+            match key {
+                "from" => {
+                    from = Some(
+                        parse_u32(value).with_context(|| format!("{context}: failed to parse \"from\" address '{value}'"))?,
+                    )
+                }
+                "module" => {
+                    module = match RelocationModule::parse(value, context)? {
+                        RelocationModule::None | RelocationModule::Overlays { .. } => {
+                            bail!("{context}: overlay load must be in a single module, but got '{value}'")
+                        }
+                        module => module.first_module(),
+                    }
+                }
+                "overlay" => {
+                    overlay =
+                        Some(parse_u16(value).with_context(|| format!("{context}: failed to parse overlay ID '{value}'"))?)
+                }
+                _ => return Err(unknown_attribute(context, "overlay load attribute 'from', 'module' or 'overlay'", key)),
+            }
+        }
+
+        if from.is_none() && module.is_none() && overlay.is_none() {
+            return Ok(None);
+        }
+        let from = from.with_context(|| format!("{context}: missing 'from' attribute"))?;
+        let module = module.with_context(|| format!("{context}: missing 'module' attribute"))?;
+        let overlay = overlay.with_context(|| format!("{context}: missing 'overlay' attribute"))?;
+
+        Ok(Some(Self { module, from, overlay }))
+    }
+}
+
+impl Display for OverlayLoad {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "from:0x{:08x} module:", self.from)?;
+        match self.module {
+            ModuleKind::Arm9 => write!(f, "main")?,
+            ModuleKind::Overlay(id) => write!(f, "overlay({id})")?,
+            ModuleKind::Autoload(AutoloadKind::Itcm) => write!(f, "itcm")?,
+            ModuleKind::Autoload(AutoloadKind::Dtcm) => write!(f, "dtcm")?,
+            ModuleKind::Autoload(kind) => write!(f, "{kind}")?,
+        }
+        write!(f, " overlay:{}", self.overlay)
+    }
+}
@@ -1,18 +1,37 @@
 use std::ops::Range;
 
 use anyhow::{bail, Result};
-
-use crate::analysis::data::{self, RelocationResult, SymbolCandidate};
+use ds_rom::rom::{raw::AutoloadKind, Arm9, Autoload, Overlay};
+use rayon::prelude::*;
+
+use crate::analysis::{
+    ambiguity,
+    data::{self, RelocationResult, SymbolCandidate},
+    data_size,
+    overlay_groups::OverlayGroups,
+    overlay_loads::{self, OverlayLoader},
+};
 
 use super::{
-    module::Module,
+    hints::Hints,
+    module::{Module, ModuleKind},
+    overlay_loads::OverlayLoads,
     section::SectionKind,
-    symbol::{SymBss, SymData, SymbolMaps},
+    symbol::{SymBss, SymData, SymbolMap, SymbolMaps},
 };
 
+#[derive(Default)]
+pub struct AnalysisOptions {
+    /// Relocate conditional calls, which must be patched after linking. See [`super::config::Config::conditional_calls`].
+    pub conditional_calls: bool,
+    /// Overlay loader function. See [`super::config::Config::overlay_loader`].
+    pub overlay_loader: Option<OverlayLoader>,
+}
+
 pub struct Program<'a> {
     modules: Vec<Module<'a>>,
     symbol_maps: SymbolMaps,
+    overlay_loads: OverlayLoads,
     // Indices in modules vec above
     main: usize,
     overlays: Range<usize>,
@@ -30,13 +49,85 @@ impl<'a> Program<'a> {
         modules.extend(autoloads);
         let autoloads = overlays.end..modules.len();
 
-        Self { modules, symbol_maps, main, overlays, autoloads }
+        Self { modules, symbol_maps, overlay_loads: OverlayLoads::new(), main, overlays, autoloads }
+    }
+
+    /// Analyzes all modules from scratch, including cross references between them. The hints of each module are
+    /// provided by `get_hints`.
+    pub fn analyze<F>(
+        arm9: &'a Arm9,
+        overlays: &'a [Overlay],
+        autoloads: &'a [Autoload],
+        options: &AnalysisOptions,
+        get_hints: F,
+    ) -> Result<Self>
+    where
+        F: Fn(ModuleKind) -> Result<Hints> + Sync,
+    {
+        let mut symbol_maps = SymbolMaps::new();
+        let overlay_groups = OverlayGroups::analyze(arm9.end_address()?, overlays)?;
+
+        let main = Module::analyze_arm9(arm9, symbol_maps.get_mut(ModuleKind::Arm9), get_hints(ModuleKind::Arm9)?)?;
+
+        // Overlays are analyzed independently of each other, so each one gets its own symbol map
+        let overlays = overlays
+            .par_iter()
+            .map(|ov| {
+                let mut symbol_map = SymbolMap::new();
+                let module = Module::analyze_overlay(ov, &mut symbol_map, get_hints(ModuleKind::Overlay(ov.id()))?)?;
+                Ok((module, symbol_map))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let overlays = overlays
+            .into_iter()
+            .map(|(module, symbol_map)| {
+                symbol_maps.insert(module.kind(), symbol_map);
+                module
+            })
+            .collect::<Vec<_>>();
+
+        let autoloads = autoloads
+            .iter()
+            .map(|autoload| {
+                let kind = ModuleKind::Autoload(autoload.kind());
+                let symbol_map = symbol_maps.get_mut(kind);
+                match autoload.kind() {
+                    AutoloadKind::Itcm => Module::analyze_itcm(autoload, symbol_map, get_hints(kind)?),
+                    AutoloadKind::Dtcm => Module::analyze_dtcm(autoload, symbol_map, get_hints(kind)?),
+                    AutoloadKind::Unknown(_) => bail!("unknown autoload kind"),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut program = Self::new(main, overlays, autoloads, symbol_maps);
+        if let Some(loader) = &options.overlay_loader {
+            program.find_overlay_loads(loader)?;
+        }
+        program.analyze_cross_references(&overlay_groups, options)?;
+        data_size::infer_data_sizes(&program.modules, &mut program.symbol_maps)?;
+        Ok(program)
+    }
+
+    /// Finds calls to the overlay loader.
+    pub fn find_overlay_loads(&mut self, loader: &OverlayLoader) -> Result<()> {
+        let address = loader.find(&self.modules)?;
+        self.overlay_loads = overlay_loads::find_overlay_loads(&self.modules, address);
+        log::debug!("Found {} overlay loads", self.overlay_loads.len());
+        Ok(())
     }
 
-    pub fn analyze_cross_references(&mut self) -> Result<()> {
+    pub fn analyze_cross_references(&mut self, overlay_groups: &OverlayGroups, options: &AnalysisOptions) -> Result<()> {
         for module_index in 0..self.modules.len() {
-            let RelocationResult { relocations, external_symbols } =
-                data::analyze_external_references(&self.modules, module_index, &mut self.symbol_maps)?;
+            let mut result = data::analyze_external_references(&self.modules, module_index, &mut self.symbol_maps, options)?;
+            ambiguity::resolve_ambiguous_references(
+                &self.modules,
+                module_index,
+                overlay_groups,
+                &self.overlay_loads,
+                &self.symbol_maps,
+                &mut result,
+            );
+            let RelocationResult { relocations, external_symbols } = result;
 
             self.modules[module_index].relocations_mut().extend(relocations)?;
 
@@ -53,7 +144,7 @@ impl<'a> Program<'a> {
                         let symbol_map = self.symbol_maps.get_mut(self.modules[module_index].kind());
                         match section_kind {
                             SectionKind::Code => {} // Function symbol, already verified to exist
-                            SectionKind::Data => {
+                            SectionKind::Data | SectionKind::Exception | SectionKind::ExceptionIndex => {
                                 symbol_map.add_data(Some(name), symbol.address, SymData::Any)?;
                             }
                             SectionKind::Bss => {
@@ -68,7 +159,7 @@ impl<'a> Program<'a> {
                             let symbol_map = self.symbol_maps.get_mut(self.modules[module_index].kind());
                             match section_kind {
                                 SectionKind::Code => {} // Function symbol, already verified to exist
-                                SectionKind::Data => {
+                                SectionKind::Data | SectionKind::Exception | SectionKind::ExceptionIndex => {
                                     symbol_map.add_ambiguous_data(Some(name), symbol.address, SymData::Any)?;
                                 }
                                 SectionKind::Bss => {
@@ -99,6 +190,10 @@ impl<'a> Program<'a> {
         &self.modules[index]
     }
 
+    pub fn module_by_kind(&self, kind: ModuleKind) -> Option<&Module> {
+        self.modules.iter().find(|module| module.kind() == kind)
+    }
+
     pub fn module_mut(&'a mut self, index: usize) -> &mut Module {
         &mut self.modules[index]
     }
@@ -110,6 +205,10 @@ impl<'a> Program<'a> {
     pub fn symbol_maps(&self) -> &SymbolMaps {
         &self.symbol_maps
     }
+
+    pub fn overlay_loads(&self) -> &OverlayLoads {
+        &self.overlay_loads
+    }
 }
 
 pub struct ExternalModules<'a> {
@@ -4,22 +4,26 @@ use std::{
     io::{BufRead, BufReader, BufWriter, Write},
     iter,
     ops::Range,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use anyhow::{bail, Context, Result};
 use ds_rom::rom::raw::AutoloadKind;
-use object::elf::{R_ARM_ABS32, R_ARM_PC24, R_ARM_THM_PC22, R_ARM_XPC25};
+use object::elf::{
+    R_ARM_ABS16, R_ARM_ABS32, R_ARM_ABS8, R_ARM_ALU_PC_G0, R_ARM_JUMP24, R_ARM_PC24, R_ARM_THM_JUMP11, R_ARM_THM_PC22,
+    R_ARM_THM_PC8, R_ARM_XPC25,
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::util::{
-    io::{create_file, open_file},
+    io::{create_file, is_json_file, open_file},
     parse::{parse_i32, parse_u16, parse_u32},
 };
 
 use super::{
     iter_attributes,
     module::{Module, ModuleKind},
-    ParseContext,
+    parse_lines, unknown_attribute, ParseContext,
 };
 
 pub struct Relocations {
@@ -31,8 +35,32 @@ impl Relocations {
         Self { relocations: BTreeMap::new() }
     }
 
+    /// Loads the relocations file and applies `relocs_overrides.txt` next to it, if there is one.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
+        let mut relocations = Self::from_file_without_overrides(path)?;
+        let overrides_path = RelocationOverrides::path(path);
+        if overrides_path.exists() {
+            RelocationOverrides::from_file(overrides_path)?.apply(&mut relocations);
+        }
+        Ok(relocations)
+    }
+
+    /// Returns the path to the baseline of the given relocations file. The baseline holds the relocations as the analysis
+    /// last found them, so that relocations deleted by the user can be told apart from ones the analysis newly found.
+    pub fn baseline_path(relocations_path: &Path) -> PathBuf {
+        relocations_path.with_file_name("relocs_baseline.txt")
+    }
+
+    /// Loads the relocations file as generated, without applying any overrides.
+    pub fn from_file_without_overrides<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if is_json_file(path) {
+            let mut relocations = Self::new();
+            relocations.extend(Self::read_json_relocations(path)?)?;
+            return Ok(relocations);
+        }
+
         let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
 
         let file = open_file(path)?;
@@ -50,12 +78,36 @@ impl Relocations {
         Ok(Self { relocations })
     }
 
+    fn read_json_relocations(path: &Path) -> Result<Vec<Relocation>> {
+        serde_json::from_reader(BufReader::new(open_file(path)?))
+            .with_context(|| format!("Failed to parse relocations in '{}'", path.display()))
+    }
+
+    /// Parses each relocation in a relocations file along with its location, without applying overrides. See
+    /// [`parse_lines`].
+    pub fn parse_lines<P: AsRef<Path>>(path: P) -> Result<Vec<Result<(ParseContext, Relocation)>>> {
+        let path = path.as_ref();
+        if is_json_file(path) {
+            let context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+            return Ok(Self::read_json_relocations(path)?
+                .into_iter()
+                .map(|relocation| Ok((context.clone(), relocation)))
+                .collect());
+        }
+        parse_lines(path, Relocation::parse)
+    }
+
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let path = path.as_ref();
 
         let file = create_file(path)?;
         let mut writer = BufWriter::new(file);
 
+        if is_json_file(path) {
+            serde_json::to_writer_pretty(&mut writer, &self.relocations.values().collect::<Vec<_>>())?;
+            writeln!(writer)?;
+            return Ok(());
+        }
         for relocation in self.relocations.values() {
             writeln!(writer, "{relocation}")?;
         }
@@ -120,10 +172,142 @@ impl Relocations {
     }
 }
 
-#[derive(PartialEq, Eq)]
+/// Relocation changes written by the user in `relocs_overrides.txt`. Unlike `relocs.txt`, this file is never rewritten by
+/// dsd, so the changes survive reanalysis. Each line is a directive followed by its attributes, and `//` starts a comment:
+///
+/// ```text
+/// add from:0x02000800 kind:load to:0x02100000 module:main
+/// remove from:0x02000804                       // integer which looks like a pointer
+/// set from:0x02000808 module:overlay(3)        // resolve ambiguous relocation
+/// ```
+pub struct RelocationOverrides {
+    overrides: Vec<RelocationOverride>,
+}
+
+pub enum RelocationOverride {
+    Add(Relocation),
+    Remove { from: u32 },
+    SetModule { from: u32, module: RelocationModule },
+}
+
+impl RelocationOverrides {
+    /// Returns the path to the overrides file of the given relocations file.
+    pub fn path(relocations_path: &Path) -> PathBuf {
+        relocations_path.with_file_name("relocs_overrides.txt")
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+
+        let file = open_file(path)?;
+        let reader = BufReader::new(file);
+
+        let mut overrides = vec![];
+        for line in reader.lines() {
+            context.row += 1;
+            let Some(relocation_override) = RelocationOverride::parse(line?.as_str(), &context)? else {
+                continue;
+            };
+            overrides.push(relocation_override);
+        }
+
+        Ok(Self { overrides })
+    }
+
+    pub fn apply(&self, relocations: &mut Relocations) {
+        for relocation_override in &self.overrides {
+            match relocation_override {
+                RelocationOverride::Add(relocation) => {
+                    relocations.relocations.insert(relocation.from, relocation.clone());
+                }
+                RelocationOverride::Remove { from } => {
+                    relocations.relocations.remove(from);
+                }
+                RelocationOverride::SetModule { from, module } => {
+                    if let Some(relocation) = relocations.relocations.get_mut(from) {
+                        relocation.module = module.clone();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RelocationOverride> {
+        self.overrides.iter()
+    }
+}
+
+impl RelocationOverride {
+    fn parse(line: &str, context: &ParseContext) -> Result<Option<Self>> {
+        let line = line.split_once("//").map(|(line, _comment)| line).unwrap_or(line).trim();
+        let Some((directive, attributes)) = line.split_once(char::is_whitespace) else {
+            if line.is_empty() {
+                return Ok(None);
+            }
+            bail!("{context}: override '{line}' has no attributes");
+        };
+
+        match directive {
+            "add" => {
+                let relocation = Relocation::parse(attributes, context)?
+                    .with_context(|| format!("{context}: missing relocation to add"))?;
+                Ok(Some(Self::Add(relocation)))
+            }
+            "remove" | "set" => {
+                let mut from = None;
+                let mut module = None;
+                for (key, value) in iter_attributes(attributes.split_whitespace()) {
+                    match key {
+                        "from" => {
+                            from = Some(
+                                parse_u32(value)
+                                    .with_context(|| format!("{context}: failed to parse \"from\" address '{value}'"))?,
+                            )
+                        }
+                        "module" if directive == "set" => module = Some(RelocationModule::parse(value, context)?),
+                        _ if directive == "set" => {
+                            return Err(unknown_attribute(context, "override attribute 'from' or 'module'", key))
+                        }
+                        _ => return Err(unknown_attribute(context, "override attribute 'from'", key)),
+                    }
+                }
+                let from = from.with_context(|| format!("{context}: missing 'from' attribute"))?;
+                if directive == "remove" {
+                    Ok(Some(Self::Remove { from }))
+                } else {
+                    let module = module.with_context(|| format!("{context}: missing 'module' attribute"))?;
+                    Ok(Some(Self::SetModule { from, module }))
+                }
+            }
+            _ => Err(unknown_attribute(context, "override 'add', 'remove' or 'set'", directive)),
+        }
+    }
+
+    pub fn from_address(&self) -> u32 {
+        match self {
+            Self::Add(relocation) => relocation.from,
+            Self::Remove { from } => *from,
+            Self::SetModule { from, .. } => *from,
+        }
+    }
+}
+
+impl Display for RelocationOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add(relocation) => write!(f, "add {relocation}"),
+            Self::Remove { from } => write!(f, "remove from:0x{from:08x}"),
+            Self::SetModule { from, module } => write!(f, "set from:0x{from:08x} module:{module}"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Relocation {
     from: u32,
     to: u32,
+    #[serde(default)]
     addend: i32,
     kind: RelocationKind,
     module: RelocationModule,
@@ -156,7 +340,13 @@ impl Relocation {
                 }
                 "kind" => kind = Some(RelocationKind::parse(value, context)?),
                 "module" => module = Some(RelocationModule::parse(value, context)?),
-                _ => bail!("{context}: expected relocation attribute 'from', 'to', 'add', 'kind' or 'module' but got '{key}'"),
+                _ => {
+                    return Err(unknown_attribute(
+                        context,
+                        "relocation attribute 'from', 'to', 'add', 'kind' or 'module'",
+                        key,
+                    ))
+                }
             }
         }
 
@@ -183,6 +373,20 @@ impl Relocation {
         }
     }
 
+    pub fn new_branch(from: u32, to: u32, module: RelocationModule, thumb: bool) -> Self {
+        Self { from, to, addend: 0, kind: if thumb { RelocationKind::ThumbBranch } else { RelocationKind::ArmBranch }, module }
+    }
+
+    pub fn new_pc_relative(from: u32, to: u32, module: RelocationModule, thumb: bool) -> Self {
+        Self {
+            from,
+            to,
+            addend: 0,
+            kind: if thumb { RelocationKind::ThumbPcRelative } else { RelocationKind::ArmPcRelative },
+            module,
+        }
+    }
+
     pub fn new_load(from: u32, to: u32, addend: i32, module: RelocationModule) -> Self {
         Self { from, to, addend, kind: RelocationKind::Load, module }
     }
@@ -203,6 +407,10 @@ impl Relocation {
         &self.module
     }
 
+    pub fn set_module(&mut self, module: RelocationModule) {
+        self.module = module;
+    }
+
     pub fn addend(&self) -> i64 {
         self.addend as i64 + self.kind.addend()
     }
@@ -210,17 +418,34 @@ impl Relocation {
 
 impl Display for Relocation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "from:0x{:08x} kind:{} to:0x{:08x} module:{}", self.from, self.kind, self.to, self.module)
+        write!(f, "from:0x{:08x} kind:{} to:0x{:08x}", self.from, self.kind, self.to)?;
+        if self.addend != 0 {
+            write!(f, " add:{}", self.addend)?;
+        }
+        write!(f, " module:{}", self.module)
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RelocationKind {
     ArmCall,
     ThumbCall,
     ArmCallThumb,
     ThumbCallArm,
+    ArmBranch,
+    ThumbBranch,
+    /// `add rX, pc, #imm` in ARM mode
+    #[serde(rename = "arm_pc_rel")]
+    ArmPcRelative,
+    /// `add rX, pc, #imm` in Thumb mode
+    #[serde(rename = "thumb_pc_rel")]
+    ThumbPcRelative,
     Load,
+    /// 16-bit absolute value. Never generated by the analysis, since every module is mapped above `0xffff`
+    Abs16,
+    /// 8-bit absolute value. Never generated by the analysis, since every module is mapped above `0xff`
+    Abs8,
 }
 
 impl RelocationKind {
@@ -230,12 +455,19 @@ impl RelocationKind {
             "thumb_call" => Ok(Self::ThumbCall),
             "arm_call_thumb" => Ok(Self::ArmCallThumb),
             "thumb_call_arm" => Ok(Self::ThumbCallArm),
+            "arm_branch" => Ok(Self::ArmBranch),
+            "thumb_branch" => Ok(Self::ThumbBranch),
+            "arm_pc_rel" => Ok(Self::ArmPcRelative),
+            "thumb_pc_rel" => Ok(Self::ThumbPcRelative),
             "load" => Ok(Self::Load),
-            _ => bail!(
-                "{}: unknown relocation kind '{}', must be one of: arm_call, thumb_call, arm_call_thumb, thumb_call_arm, load",
+            "abs16" => Ok(Self::Abs16),
+            "abs8" => Ok(Self::Abs8),
+            _ => Err(unknown_attribute(
                 context,
-                text
-            ),
+                "relocation kind 'arm_call', 'thumb_call', 'arm_call_thumb', 'thumb_call_arm', 'arm_branch', 'thumb_branch', \
+                'arm_pc_rel', 'thumb_pc_rel', 'load', 'abs16' or 'abs8'",
+                text,
+            )),
         }
     }
 
@@ -245,7 +477,13 @@ impl RelocationKind {
             Self::ThumbCall => object::SymbolKind::Text,
             Self::ArmCallThumb => object::SymbolKind::Text,
             Self::ThumbCallArm => object::SymbolKind::Text,
+            Self::ArmBranch => object::SymbolKind::Text,
+            Self::ThumbBranch => object::SymbolKind::Text,
+            Self::ArmPcRelative => object::SymbolKind::Data,
+            Self::ThumbPcRelative => object::SymbolKind::Data,
             Self::Load => object::SymbolKind::Data,
+            Self::Abs16 => object::SymbolKind::Data,
+            Self::Abs8 => object::SymbolKind::Data,
         }
     }
 
@@ -257,7 +495,13 @@ impl RelocationKind {
             // Bug in mwld thinks that the range of XPC22 is only +-2MB, but it should be +-4MB. Fortunately we can use PC22 as
             // it has the correct range, and the linker resolves BL instructions to BLX automatically anyway.
             Self::ThumbCallArm => R_ARM_THM_PC22,
+            Self::ArmBranch => R_ARM_JUMP24,
+            Self::ThumbBranch => R_ARM_THM_JUMP11,
+            Self::ArmPcRelative => R_ARM_ALU_PC_G0,
+            Self::ThumbPcRelative => R_ARM_THM_PC8,
             Self::Load => R_ARM_ABS32,
+            Self::Abs16 => R_ARM_ABS16,
+            Self::Abs8 => R_ARM_ABS8,
         }
     }
 
@@ -267,7 +511,13 @@ impl RelocationKind {
             Self::ThumbCall => -4,
             Self::ArmCallThumb => -8,
             Self::ThumbCallArm => -4,
+            Self::ArmBranch => -8,
+            Self::ThumbBranch => -4,
+            Self::ArmPcRelative => -8,
+            Self::ThumbPcRelative => -4,
             Self::Load => 0,
+            Self::Abs16 => 0,
+            Self::Abs8 => 0,
         }
     }
 }
@@ -279,12 +529,18 @@ impl Display for RelocationKind {
             Self::ThumbCall => write!(f, "thumb_call"),
             Self::ArmCallThumb => write!(f, "arm_call_thumb"),
             Self::ThumbCallArm => write!(f, "thumb_call_arm"),
+            Self::ArmBranch => write!(f, "arm_branch"),
+            Self::ThumbBranch => write!(f, "thumb_branch"),
+            Self::ArmPcRelative => write!(f, "arm_pc_rel"),
+            Self::ThumbPcRelative => write!(f, "thumb_pc_rel"),
             Self::Load => write!(f, "load"),
+            Self::Abs16 => write!(f, "abs16"),
+            Self::Abs8 => write!(f, "abs8"),
         }
     }
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum RelocationModule {
     None,
     Overlay { id: u16 },
@@ -348,7 +604,7 @@ impl RelocationModule {
         }
     }
 
-    fn parse(text: &str, context: &ParseContext) -> Result<Self> {
+    pub fn parse(text: &str, context: &ParseContext) -> Result<Self> {
         let (value, options) = text.split_once('(').unwrap_or((text, ""));
         let options = options.strip_suffix(')').unwrap_or(options);
 
@@ -394,9 +650,11 @@ impl RelocationModule {
                     bail!("{}: relocations to 'DTCM' have no options, but got '({})'", context, options);
                 }
             }
-            _ => {
-                bail!("{}: unknown relocation to '{}', must be one of: overlays, overlay, main, itcm, dtcm", context, value);
-            }
+            _ => Err(unknown_attribute(
+                context,
+                "relocation module 'none', 'overlays', 'overlay', 'main', 'itcm' or 'dtcm'",
+                value,
+            )),
         }
     }
 
@@ -463,3 +721,18 @@ impl Display for RelocationModule {
         }
     }
 }
+
+/// Relocation modules are written in JSON files the same way as in relocs.txt, e.g. `"overlays(1,2)"`.
+impl Serialize for RelocationModule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for RelocationModule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let context = ParseContext { file_path: "JSON value".to_string(), row: 0 };
+        Self::parse(&text, &context).map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    fmt::Display,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use ds_rom::rom::raw::AutoloadKind;
+
+use crate::util::{
+    io::{create_file, open_file},
+    parse::parse_u32,
+};
+
+use super::{iter_attributes, module::ModuleKind, relocation::RelocationModule, unknown_attribute, ParseContext};
+
+/// Symbols renamed by `sym rename` which have not been applied to the source files yet. Each line in the rename log is
+/// one rename, in the order they were made, along with the module and address of the renamed symbol:
+///
+/// ```text
+/// from:func_02012345 to:Player_Update module:main addr:0x02012345
+/// from:data_020a0000 to:gPlayer module:main addr:0x020a0000
+/// ```
+///
+/// Rename logs from older versions don't have the module and address, so their renames apply to every source file.
+#[derive(Default)]
+pub struct RenameLog {
+    renames: Vec<Rename>,
+}
+
+#[derive(Clone)]
+pub struct Rename {
+    pub from: String,
+    pub to: String,
+    /// Module and address of the renamed symbol
+    pub symbol: Option<(ModuleKind, u32)>,
+}
+
+impl RenameLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+
+        let file = open_file(path)?;
+        let reader = BufReader::new(file);
+
+        let mut renames = Self::new();
+        for line in reader.lines() {
+            context.row += 1;
+            let Some(rename) = Rename::parse(line?.as_str(), &context)? else {
+                continue;
+            };
+            renames.add(rename);
+        }
+        Ok(renames)
+    }
+
+    /// Loads the rename log, or returns an empty log if the file doesn't exist yet.
+    pub fn from_file_or_new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            Self::from_file(path)
+        } else {
+            Ok(Self::new())
+        }
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = create_file(path)?;
+        let mut writer = BufWriter::new(file);
+
+        for rename in &self.renames {
+            writeln!(writer, "{rename}")?;
+        }
+        Ok(())
+    }
+
+    pub fn add(&mut self, rename: Rename) {
+        self.renames.push(rename);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rename> {
+        self.renames.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.renames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+}
+
+impl Rename {
+    fn parse(line: &str, context: &ParseContext) -> Result<Option<Self>> {
+        let words = line.split_whitespace();
+
+        let mut from = None;
+        let mut to = None;
+        let mut module = None;
+        let mut addr = None;
+        for (key, value) in iter_attributes(words) {
+            match key {
+                "from" => from = Some(value.to_string()),
+                "to" => to = Some(value.to_string()),
+                "module" => {
+                    module = match RelocationModule::parse(value, context)? {
+                        RelocationModule::None | RelocationModule::Overlays { .. } => {
+                            bail!("{context}: renamed symbol must be in a single module, but got '{value}'")
+                        }
+                        module => module.first_module(),
+                    }
+                }
+                "addr" => {
+                    addr = Some(parse_u32(value).with_context(|| format!("{context}: failed to parse address '{value}'"))?)
+                }
+                _ => return Err(unknown_attribute(context, "rename attribute 'from', 'to', 'module' or 'addr'", key)),
+            }
+        }
+
+        if from.is_none() && to.is_none() {
+            return Ok(None);
+        }
+        let from = from.with_context(|| format!("{context}: missing 'from' attribute"))?;
+        let to = to.with_context(|| format!("{context}: missing 'to' attribute"))?;
+        let symbol = match (module, addr) {
+            (Some(module), Some(addr)) => Some((module, addr)),
+            (None, None) => None,
+            _ => bail!("{context}: 'module' and 'addr' must be given together"),
+        };
+
+        Ok(Some(Self { from, to, symbol }))
+    }
+}
+
+impl Display for Rename {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "from:{} to:{}", self.from, self.to)?;
+        if let Some((module, addr)) = self.symbol {
+            match module {
+                ModuleKind::Arm9 => write!(f, " module:main")?,
+                ModuleKind::Overlay(id) => write!(f, " module:overlay({id})")?,
+                ModuleKind::Autoload(AutoloadKind::Itcm) => write!(f, " module:itcm")?,
+                ModuleKind::Autoload(AutoloadKind::Dtcm) => write!(f, " module:dtcm")?,
+                ModuleKind::Autoload(kind) => write!(f, " module:{kind}")?,
+            }
+            write!(f, " addr:{addr:#010x}")?;
+        }
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use std::{
 
 use anyhow::{bail, Context, Result};
 use object::{Object, ObjectSymbol};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     analysis::functions::Function,
@@ -16,7 +17,7 @@ use super::{
     iter_attributes,
     module::Module,
     relocation::{Relocation, RelocationKind},
-    ParseContext,
+    unknown_attribute, ParseContext,
 };
 
 pub struct Section {
@@ -90,7 +91,7 @@ impl Section {
                     align =
                         Some(parse_u32(value).with_context(|| format!("{}: failed to parse alignment '{}'", context, value))?)
                 }
-                _ => bail!("{}: expected section attribute 'kind', 'start', 'end' or 'align' but got '{}'", context, key),
+                _ => return Err(unknown_attribute(context, "section attribute 'kind', 'start', 'end' or 'align'", key)),
             }
         }
 
@@ -124,7 +125,7 @@ impl Section {
                     end = Some(parse_u32(value).with_context(|| format!("{context}: failed to parse end address '{value}'"))?)
                 }
                 "align" => bail!("{context}: attribute 'align' should be omitted as it is inherited from this file's header"),
-                _ => bail!("{context}: expected section attribute 'start' or 'end' but got '{key}'"),
+                _ => return Err(unknown_attribute(context, "section attribute 'start' or 'end'", key)),
             }
         }
 
@@ -163,7 +164,9 @@ impl Section {
             let offset = (from - self.start_address) as usize;
 
             // Clear bits in `code` to treat them as the implicit addend
-            let ins = match relocation.kind() {
+            let arm_pc_relative_ins: [u8; 4];
+            let thumb_pc_relative_ins: [u8; 2];
+            let ins: &[u8] = match relocation.kind() {
                 RelocationKind::ArmCall => {
                     // R_ARM_PC24
                     &[0xfe, 0xff, 0xff, 0xeb] // bl #0
@@ -180,12 +183,40 @@ impl Section {
                     // R_ARM_THM_XPC22
                     &[0xff, 0xf7, 0xfe, 0xff] // bl #0
                 }
+                RelocationKind::ArmBranch => {
+                    // R_ARM_JUMP24
+                    &[0xfe, 0xff, 0xff, 0xea] // b #0
+                }
+                RelocationKind::ThumbBranch => {
+                    // R_ARM_THM_JUMP11
+                    &[0xfe, 0xe7] // b #0
+                }
+                RelocationKind::ArmPcRelative => {
+                    // R_ARM_ALU_PC_G0, keep the condition code and destination register
+                    let ins = u32::from_le_slice(&code[offset..]);
+                    arm_pc_relative_ins = ((ins & 0xf000f000) | 0x024f0008).to_le_bytes(); // sub rX, pc, #8
+                    &arm_pc_relative_ins
+                }
+                RelocationKind::ThumbPcRelative => {
+                    // R_ARM_THM_PC8, keep the destination register
+                    let ins = u16::from_le_slice(&code[offset..]);
+                    thumb_pc_relative_ins = ((ins & 0x0700) | 0xa000).to_le_bytes(); // add rX, pc, #0
+                    &thumb_pc_relative_ins
+                }
                 RelocationKind::Load => {
                     // R_ARM_ABS32
                     &[0x00, 0x00, 0x00, 0x00]
                 }
+                RelocationKind::Abs16 => {
+                    // R_ARM_ABS16
+                    &[0x00, 0x00]
+                }
+                RelocationKind::Abs8 => {
+                    // R_ARM_ABS8
+                    &[0x00]
+                }
             };
-            code[offset..offset + 4].copy_from_slice(ins);
+            code[offset..offset + ins.len()].copy_from_slice(ins);
         }
 
         Ok(Some(code))
@@ -271,11 +302,47 @@ impl Display for Section {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+/// The attributes of a section as written in delinks files, used to serialize sections without their functions.
+#[derive(Serialize, Deserialize)]
+struct SectionData {
+    name: String,
+    kind: SectionKind,
+    start: u32,
+    end: u32,
+    align: u32,
+}
+
+impl Serialize for Section {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SectionData {
+            name: self.name.clone(),
+            kind: self.kind,
+            start: self.start_address,
+            end: self.end_address,
+            align: self.alignment,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Section {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let SectionData { name, kind, start, end, align } = SectionData::deserialize(deserializer)?;
+        Section::new(name, kind, start, end, align).map_err(de::Error::custom)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SectionKind {
     Code,
     Data,
     Bss,
+    /// Exception action tables, `.exception`
+    Exception,
+    /// Index of functions with exception tables, `.exceptix`
+    #[serde(rename = "exceptix")]
+    ExceptionIndex,
 }
 
 impl SectionKind {
@@ -284,7 +351,9 @@ impl SectionKind {
             "code" => Ok(Self::Code),
             "data" => Ok(Self::Data),
             "bss" => Ok(Self::Bss),
-            _ => bail!("{}: unknown section kind '{}', must be one of: code, data, bss", context, value),
+            "exception" => Ok(Self::Exception),
+            "exceptix" => Ok(Self::ExceptionIndex),
+            _ => Err(unknown_attribute(context, "section kind 'code', 'data', 'bss', 'exception' or 'exceptix'", value)),
         }
     }
 
@@ -293,6 +362,8 @@ impl SectionKind {
             SectionKind::Code => true,
             SectionKind::Data => true,
             SectionKind::Bss => false,
+            SectionKind::Exception => true,
+            SectionKind::ExceptionIndex => true,
         }
     }
 }
@@ -303,6 +374,8 @@ impl Display for SectionKind {
             Self::Code => write!(f, "code"),
             Self::Data => write!(f, "data"),
             Self::Bss => write!(f, "bss"),
+            Self::Exception => write!(f, "exception"),
+            Self::ExceptionIndex => write!(f, "exceptix"),
         }
     }
 }
@@ -415,6 +488,22 @@ impl Sections {
     }
 }
 
+impl Serialize for Sections {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.sorted_by_address())
+    }
+}
+
+impl<'de> Deserialize<'de> for Sections {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut sections = Sections::new();
+        for section in Vec::<Section>::deserialize(deserializer)? {
+            sections.add(section).map_err(de::Error::custom)?;
+        }
+        Ok(sections)
+    }
+}
+
 pub struct Word {
     pub address: u32,
     pub value: u32,
@@ -1,4 +1,5 @@
 use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{btree_map, hash_map, BTreeMap, HashMap},
     fmt::Display,
@@ -10,15 +11,23 @@ use std::{
 use unarm::LookupSymbol;
 
 use crate::{
-    analysis::{functions::Function, jump_table::JumpTable},
+    analysis::{functions::Function, jump_table::JumpTable, veneer::Veneer},
     util::{
         bytes::FromSlice,
-        io::{create_file, open_file},
+        io::{create_file, is_json_file, open_file},
         parse::parse_u32,
     },
 };
 
-use super::{config::Config, iter_attributes, module::ModuleKind, relocation::Relocations, ParseContext};
+use super::{
+    config::Config,
+    iter_attributes,
+    module::ModuleKind,
+    parse_lines,
+    relocation::Relocations,
+    types::{is_identifier, FieldKind, StructType, TypeName, Types},
+    unknown_attribute, ParseContext,
+};
 
 pub struct SymbolMaps {
     symbol_maps: Vec<SymbolMap>,
@@ -42,16 +51,23 @@ impl SymbolMaps {
         &mut self.symbol_maps[index]
     }
 
-    pub fn from_config<P: AsRef<Path>>(config_path: P, config: &Config) -> Result<Self> {
+    /// Replaces the symbol map of a module, e.g. after analyzing it separately from the other modules.
+    pub fn insert(&mut self, module: ModuleKind, symbol_map: SymbolMap) {
+        *self.get_mut(module) = symbol_map;
+    }
+
+    pub fn from_config<P: AsRef<Path>>(config_path: P, config: &Config, types: &Types) -> Result<Self> {
         let config_path = config_path.as_ref();
 
         let mut symbol_maps = SymbolMaps::new();
-        symbol_maps.get_mut(ModuleKind::Arm9).load(config_path.join(&config.main_module.symbols))?;
+        symbol_maps.get_mut(ModuleKind::Arm9).load(config_path.join(&config.main_module.symbols), types)?;
         for autoload in &config.autoloads {
-            symbol_maps.get_mut(ModuleKind::Autoload(autoload.kind)).load(config_path.join(&autoload.module.symbols))?;
+            symbol_maps
+                .get_mut(ModuleKind::Autoload(autoload.kind))
+                .load(config_path.join(&autoload.module.symbols), types)?;
         }
         for overlay in &config.overlays {
-            symbol_maps.get_mut(ModuleKind::Overlay(overlay.id)).load(config_path.join(&overlay.module.symbols))?;
+            symbol_maps.get_mut(ModuleKind::Overlay(overlay.id)).load(config_path.join(&overlay.module.symbols), types)?;
         }
 
         Ok(symbol_maps)
@@ -80,6 +96,7 @@ impl SymbolMaps {
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct SymbolIndex(usize);
 
+#[derive(Clone)]
 pub struct SymbolMap {
     symbols: Vec<Symbol>,
     symbols_by_address: BTreeMap<u32, Vec<SymbolIndex>>,
@@ -103,14 +120,22 @@ impl SymbolMap {
         Self { symbols, symbols_by_address, symbols_by_name }
     }
 
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+    /// Loads a symbols file. Data symbols may only use the struct types declared in `types`.
+    pub fn from_file<P: AsRef<Path>>(path: P, types: &Types) -> Result<Self> {
         let mut symbol_map = Self::new();
-        symbol_map.load(path)?;
+        symbol_map.load(path, types)?;
         Ok(symbol_map)
     }
 
-    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+    pub fn load<P: AsRef<Path>>(&mut self, path: P, types: &Types) -> Result<()> {
         let path = path.as_ref();
+        if is_json_file(path) {
+            for symbol in Self::read_json_symbols(path, types)? {
+                self.add(symbol);
+            }
+            return Ok(());
+        }
+
         let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
 
         let file = open_file(path)?;
@@ -118,25 +143,54 @@ impl SymbolMap {
 
         for line in reader.lines() {
             context.row += 1;
-            let Some(symbol) = Symbol::parse(line?.as_str(), &context)? else { continue };
+            let Some(symbol) = Symbol::parse(line?.as_str(), types, &context)? else { continue };
             self.add(symbol);
         }
         Ok(())
     }
 
+    fn read_json_symbols(path: &Path, types: &Types) -> Result<Vec<Symbol>> {
+        let symbols: Vec<Symbol> = serde_json::from_reader(BufReader::new(open_file(path)?))
+            .with_context(|| format!("Failed to parse symbols in '{}'", path.display()))?;
+        let context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+        for symbol in &symbols {
+            symbol.validate(types, &context)?;
+        }
+        Ok(symbols)
+    }
+
+    /// Parses each symbol in a symbols file along with its location, see [`parse_lines`].
+    pub fn parse_lines<P: AsRef<Path>>(path: P, types: &Types) -> Result<Vec<Result<(ParseContext, Symbol)>>> {
+        let path = path.as_ref();
+        if is_json_file(path) {
+            let context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+            return Ok(Self::read_json_symbols(path, types)?
+                .into_iter()
+                .map(|symbol| Ok((context.clone(), symbol)))
+                .collect());
+        }
+        parse_lines(path, |line, context| Symbol::parse(line, types, context))
+    }
+
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let path = path.as_ref();
 
         let file = create_file(path)?;
         let mut writer = BufWriter::new(file);
 
-        for indices in self.symbols_by_address.values() {
-            for &index in indices {
-                let symbol = &self.symbols[index.0];
-                if symbol.should_write() {
-                    writeln!(writer, "{symbol}")?;
-                }
-            }
+        let symbols = self
+            .symbols_by_address
+            .values()
+            .flat_map(|indices| indices.iter().map(|index| &self.symbols[index.0]))
+            .filter(|symbol| symbol.should_write());
+
+        if is_json_file(path) {
+            serde_json::to_writer_pretty(&mut writer, &symbols.collect::<Vec<_>>())?;
+            writeln!(writer)?;
+            return Ok(());
+        }
+        for symbol in symbols {
+            writeln!(writer, "{symbol}")?;
         }
 
         Ok(())
@@ -167,7 +221,17 @@ impl SymbolMap {
             return Ok(None);
         };
         let (index, symbol) = symbols.next().unwrap();
-        if let Some((_, other)) = symbols.next() {
+        let Some((other_index, other)) = symbols.next() else {
+            return Ok(Some((index, symbol)));
+        };
+
+        // Local symbols in different files may share a name, but there can only be one non-local symbol with it
+        let mut non_local =
+            [(index, symbol), (other_index, other)].into_iter().chain(symbols).filter(|(_, symbol)| !symbol.is_local());
+        let Some((index, symbol)) = non_local.next() else {
+            bail!("multiple local symbols with name '{}': 0x{:08x}, 0x{:08x}", name, symbol.addr, other.addr);
+        };
+        if let Some((_, other)) = non_local.next() {
             bail!("multiple symbols with name '{}': 0x{:08x}, 0x{:08x}", name, symbol.addr, other.addr);
         }
         Ok(Some((index, symbol)))
@@ -230,6 +294,25 @@ impl SymbolMap {
         }
     }
 
+    pub fn add_veneer(&mut self, addr: u32, veneer: SymVeneer) -> Result<(SymbolIndex, &Symbol)> {
+        let name = format!("veneer_{addr:08x}");
+        self.add_if_new_address(Symbol::new_veneer(name, addr, veneer))
+    }
+
+    pub fn get_veneer(&self, addr: u32) -> Result<Option<(SymVeneer, &Symbol)>> {
+        Ok(self.by_address(addr & !1)?.map_or(None, |(_, s)| match s.kind {
+            SymbolKind::Veneer(veneer) => Some((veneer, s)),
+            _ => None,
+        }))
+    }
+
+    pub fn veneers(&self) -> impl Iterator<Item = (SymVeneer, &Symbol)> {
+        self.symbols.iter().filter_map(|symbol| match symbol.kind {
+            SymbolKind::Veneer(veneer) => Some((veneer, symbol)),
+            _ => None,
+        })
+    }
+
     pub fn clone_functions(&self) -> Vec<(SymFunction, Symbol)> {
         self.functions().map(|(function, symbol)| (function, symbol.clone())).collect()
     }
@@ -296,8 +379,8 @@ impl SymbolMap {
         self.add_if_new_address(Symbol::new_data(name, addr, data, true))
     }
 
-    pub fn get_data(&self, addr: u32) -> Result<Option<(SymData, &Symbol)>> {
-        Ok(self.by_address(addr)?.map_or(None, |(_, s)| match s.kind {
+    pub fn get_data(&self, addr: u32) -> Result<Option<(&SymData, &Symbol)>> {
+        Ok(self.by_address(addr)?.map_or(None, |(_, s)| match &s.kind {
             SymbolKind::Data(data) => Some((data, s)),
             _ => None,
         }))
@@ -314,6 +397,26 @@ impl SymbolMap {
         self.add_if_new_address(Symbol::new_bss(name, addr, data, true))
     }
 
+    pub fn get(&self, index: SymbolIndex) -> &Symbol {
+        &self.symbols[index.0]
+    }
+
+    /// Removes a symbol. Other symbols keep their order, but their indices are invalidated.
+    pub fn remove(&mut self, index: SymbolIndex) -> Symbol {
+        let mut symbols = std::mem::take(&mut self.symbols);
+        let symbol = symbols.remove(index.0);
+        *self = Self::from_symbols(symbols);
+        symbol
+    }
+
+    pub fn set_kind(&mut self, index: SymbolIndex, kind: SymbolKind) {
+        self.symbols[index.0].kind = kind;
+    }
+
+    pub fn set_size(&mut self, index: SymbolIndex, size: Option<u32>) {
+        self.symbols[index.0].size = size;
+    }
+
     pub fn rename_by_address(&mut self, address: u32, new_name: &str) -> Result<()> {
         let symbol_indices = self
             .symbols_by_address
@@ -322,6 +425,10 @@ impl SymbolMap {
         ensure!(symbol_indices.len() == 1, "There must be exactly one symbol at {address:#x} to rename to '{new_name}'");
 
         let symbol_index = symbol_indices[0];
+        self.rename(symbol_index, new_name)
+    }
+
+    pub fn rename(&mut self, symbol_index: SymbolIndex, new_name: &str) -> Result<()> {
         let name = &self.symbols[symbol_index.0].name;
 
         match self.symbols_by_name.entry(name.clone()) {
@@ -414,31 +521,47 @@ impl<'a, I: Iterator<Item = &'a Vec<SymbolIndex>>> Iterator for FunctionSymbolIt
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Symbol {
     pub name: String,
     pub kind: SymbolKind,
     pub addr: u32,
     /// If true, this symbol is involved in an ambiguous external reference to one of many overlays
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub ambiguous: bool,
+    /// Overrides the default scope of the symbol's kind
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<SymScope>,
+    /// Overrides the size given by the symbol's kind, or the distance to the next symbol if the kind has no size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u32>,
 }
 
 impl Symbol {
-    fn parse(line: &str, context: &ParseContext) -> Result<Option<Self>> {
+    fn parse(line: &str, types: &Types, context: &ParseContext) -> Result<Option<Self>> {
         let mut words = line.split_whitespace();
         let Some(name) = words.next() else { return Ok(None) };
 
         let mut kind = None;
         let mut addr = None;
         let mut ambiguous = false;
+        let mut scope = None;
+        let mut size = None;
         for (key, value) in iter_attributes(words) {
             match key {
-                "kind" => kind = Some(SymbolKind::parse(value, context)?),
+                "kind" => kind = Some(SymbolKind::parse(value, types, context)?),
                 "addr" => {
                     addr = Some(parse_u32(value).with_context(|| format!("{context}: failed to parse address '{value}'"))?)
                 }
+                "size" => size = Some(parse_u32(value).with_context(|| format!("{context}: failed to parse size '{value}'"))?),
                 "ambiguous" => ambiguous = true,
-                _ => bail!("{context}: expected symbol attribute 'kind' or 'addr' but got '{key}'"),
+                "local" | "weak" | "global" => {
+                    if scope.is_some() {
+                        bail!("{context}: symbol can only have one of 'local', 'weak' or 'global'");
+                    }
+                    scope = SymScope::parse(key);
+                }
+                _ => return Err(unknown_attribute(context, "symbol attribute 'kind', 'addr' or 'size'", key)),
             }
         }
 
@@ -446,7 +569,36 @@ impl Symbol {
         let kind = kind.with_context(|| format!("{context}: missing 'kind' attribute"))?;
         let addr = addr.with_context(|| format!("{context}: missing 'addr' attribute"))?;
 
-        Ok(Some(Symbol { name, kind, addr, ambiguous }))
+        let symbol = Symbol { name, kind, addr, ambiguous, scope, size };
+        symbol.validate(types, context)?;
+        Ok(Some(symbol))
+    }
+
+    /// Checks what the text and JSON formats can't express on their own: that the name is a single word, that struct
+    /// types are declared and that the `size` attribute doesn't conflict with the size of the kind.
+    fn validate(&self, types: &Types, context: &ParseContext) -> Result<()> {
+        if self.name.is_empty() || self.name.contains(char::is_whitespace) {
+            bail!("{context}: symbol name '{}' must be non-empty and have no whitespace", self.name);
+        }
+        if let SymbolKind::Data(SymData::Struct { name, .. }) = &self.kind {
+            if types.get(name.as_str()).is_none() {
+                bail!(
+                    "{context}: unknown data kind '{name}' of symbol '{}', it's not a struct declared in types.txt",
+                    self.name
+                );
+            }
+        }
+        if let (Some(size), Some(kind_size)) = (self.size, self.kind.known_size(types)) {
+            if size != kind_size {
+                bail!(
+                    "{context}: size:{size:#x} of symbol '{}' conflicts with the size {kind_size:#x} of kind '{}', remove one \
+                    of them",
+                    self.name,
+                    self.kind
+                );
+            }
+        }
+        Ok(())
     }
 
     fn should_write(&self) -> bool {
@@ -463,6 +615,8 @@ impl Symbol {
             }),
             addr: function.start_address() & !1,
             ambiguous: false,
+            scope: None,
+            size: None,
         }
     }
 
@@ -472,6 +626,8 @@ impl Symbol {
             kind: SymbolKind::Label(SymLabel { external: false, mode: InstructionMode::from_thumb(thumb) }),
             addr,
             ambiguous: false,
+            scope: None,
+            size: None,
         }
     }
 
@@ -481,32 +637,64 @@ impl Symbol {
             kind: SymbolKind::Label(SymLabel { external: true, mode: InstructionMode::from_thumb(thumb) }),
             addr,
             ambiguous: false,
+            scope: None,
+            size: None,
         }
     }
 
+    pub fn new_veneer(name: String, addr: u32, veneer: SymVeneer) -> Self {
+        Self { name, kind: SymbolKind::Veneer(veneer), addr, ambiguous: false, scope: None, size: None }
+    }
+
     pub fn new_pool_constant(name: String, addr: u32) -> Self {
-        Self { name, kind: SymbolKind::PoolConstant, addr, ambiguous: false }
+        Self { name, kind: SymbolKind::PoolConstant, addr, ambiguous: false, scope: None, size: None }
     }
 
     pub fn new_jump_table(name: String, addr: u32, size: u32, code: bool) -> Self {
-        Self { name, kind: SymbolKind::JumpTable(SymJumpTable { size, code }), addr, ambiguous: false }
+        Self {
+            name,
+            kind: SymbolKind::JumpTable(SymJumpTable { size, code }),
+            addr,
+            ambiguous: false,
+            scope: None,
+            size: None,
+        }
     }
 
     pub fn new_data(name: String, addr: u32, data: SymData, ambiguous: bool) -> Symbol {
-        Self { name, kind: SymbolKind::Data(data), addr, ambiguous }
+        Self { name, kind: SymbolKind::Data(data), addr, ambiguous, scope: None, size: None }
     }
 
     pub fn new_bss(name: String, addr: u32, data: SymBss, ambiguous: bool) -> Symbol {
-        Self { name, kind: SymbolKind::Bss(data), addr, ambiguous }
+        Self { name, kind: SymbolKind::Bss(data), addr, ambiguous, scope: None, size: None }
+    }
+
+    /// Returns the ELF scope of the symbol and whether it's weak.
+    pub fn obj_scope(&self) -> (object::SymbolScope, bool) {
+        match self.scope {
+            Some(SymScope::Local) => (object::SymbolScope::Compilation, false),
+            Some(SymScope::Weak) => (object::SymbolScope::Dynamic, true),
+            Some(SymScope::Global) => (object::SymbolScope::Dynamic, false),
+            None => (self.kind.into_obj_symbol_scope(), false),
+        }
     }
 
-    pub fn size(&self, max_address: u32) -> u32 {
-        self.kind.size(max_address - self.addr)
+    pub fn is_local(&self) -> bool {
+        match self.scope {
+            Some(scope) => scope == SymScope::Local,
+            None => self.kind.into_obj_symbol_scope() == object::SymbolScope::Compilation,
+        }
+    }
+
+    pub fn size(&self, max_address: u32, types: &Types) -> u32 {
+        self.size.unwrap_or_else(|| self.kind.size(max_address - self.addr, types))
     }
 
     pub fn mapping_symbol_name(&self) -> Option<&str> {
         match self.kind {
-            SymbolKind::Function(SymFunction { mode, .. }) | SymbolKind::Label(SymLabel { mode, .. }) => match mode {
+            SymbolKind::Function(SymFunction { mode, .. })
+            | SymbolKind::Label(SymLabel { mode, .. })
+            | SymbolKind::Veneer(SymVeneer { mode, .. }) => match mode {
                 InstructionMode::Arm => Some("$a"),
                 InstructionMode::Thumb => Some("$t"),
             },
@@ -530,11 +718,51 @@ impl Display for Symbol {
         if self.ambiguous {
             write!(f, " ambiguous")?;
         }
+        if let Some(size) = self.size {
+            write!(f, " size:{size:#x}")?;
+        }
+        if let Some(scope) = self.scope {
+            write!(f, " {scope}")?;
+        }
         Ok(())
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// Visibility of a symbol outside of its translation unit.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymScope {
+    /// Only visible in its own file, like `static` functions and variables. Several files can have local symbols with the
+    /// same name.
+    Local,
+    /// Visible to other files, but can be overridden by a global symbol with the same name
+    Weak,
+    Global,
+}
+
+impl SymScope {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "local" => Some(Self::Local),
+            "weak" => Some(Self::Weak),
+            "global" => Some(Self::Global),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SymScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Weak => write!(f, "weak"),
+            Self::Global => write!(f, "global"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SymbolKind {
     Function(SymFunction),
     Label(SymLabel),
@@ -542,19 +770,22 @@ pub enum SymbolKind {
     JumpTable(SymJumpTable),
     Data(SymData),
     Bss(SymBss),
+    /// Generated by the linker, see [`crate::analysis::veneer::Veneer`]
+    Veneer(SymVeneer),
 }
 
 impl SymbolKind {
-    fn parse(text: &str, context: &ParseContext) -> Result<Self> {
+    pub fn parse(text: &str, types: &Types, context: &ParseContext) -> Result<Self> {
         let (kind, options) = text.split_once('(').unwrap_or((text, ""));
         let options = options.strip_suffix(')').unwrap_or(options);
 
         match kind {
             "function" => Ok(Self::Function(SymFunction::parse(options, context)?)),
-            "data" => Ok(Self::Data(SymData::parse(options, context)?)),
+            "data" => Ok(Self::Data(SymData::parse(options, types, context)?)),
             "bss" => Ok(Self::Bss(SymBss::parse(options, context)?)),
             "label" => Ok(Self::Label(SymLabel::parse(options, context)?)),
-            _ => bail!("{context}: unknown symbol kind '{kind}', must be one of: function, data, bss, label"),
+            "veneer" => Ok(Self::Veneer(SymVeneer::parse(options, context)?)),
+            _ => Err(unknown_attribute(context, "symbol kind 'function', 'data', 'bss', 'label' or 'veneer'", kind)),
         }
     }
 
@@ -566,6 +797,7 @@ impl SymbolKind {
             SymbolKind::JumpTable(_) => false,
             SymbolKind::Data(_) => true,
             SymbolKind::Bss(_) => true,
+            SymbolKind::Veneer(_) => true,
         }
     }
 
@@ -577,6 +809,7 @@ impl SymbolKind {
             Self::JumpTable(_) => object::SymbolKind::Label,
             Self::Data(_) => object::SymbolKind::Data,
             Self::Bss(_) => object::SymbolKind::Data,
+            Self::Veneer(_) => object::SymbolKind::Text,
         }
     }
 
@@ -588,17 +821,30 @@ impl SymbolKind {
             SymbolKind::JumpTable(_) => object::SymbolScope::Compilation,
             SymbolKind::Data(_) => object::SymbolScope::Dynamic,
             SymbolKind::Bss(_) => object::SymbolScope::Dynamic,
+            SymbolKind::Veneer(_) => object::SymbolScope::Compilation,
         }
     }
 
-    pub fn size(&self, max_size: u32) -> u32 {
+    /// Returns the size given by the kind itself, or `None` if it has to be inferred from the next symbol.
+    pub fn known_size(&self, types: &Types) -> Option<u32> {
+        match self {
+            SymbolKind::Function(function) => Some(function.size),
+            SymbolKind::Veneer(veneer) => Some(veneer.size),
+            SymbolKind::Data(data) => data.size(types),
+            SymbolKind::Bss(bss) => bss.size,
+            SymbolKind::Label(_) | SymbolKind::PoolConstant | SymbolKind::JumpTable(_) => None,
+        }
+    }
+
+    pub fn size(&self, max_size: u32, types: &Types) -> u32 {
         match self {
             SymbolKind::Function(function) => function.size,
             SymbolKind::Label { .. } => 0,
             SymbolKind::PoolConstant => 0, // actually 4, but pool constants are just labels
             SymbolKind::JumpTable(_) => 0,
-            SymbolKind::Data(data) => data.size().unwrap_or(max_size) as u32,
+            SymbolKind::Data(data) => data.size(types).unwrap_or(max_size),
             SymbolKind::Bss(bss) => bss.size.unwrap_or(max_size),
+            SymbolKind::Veneer(veneer) => veneer.size,
         }
     }
 }
@@ -610,6 +856,7 @@ impl Display for SymbolKind {
             SymbolKind::Data(data) => write!(f, "data({data})")?,
             SymbolKind::Bss(bss) => write!(f, "bss{bss}")?,
             SymbolKind::Label(label) => write!(f, "label({label})")?,
+            SymbolKind::Veneer(veneer) => write!(f, "veneer({veneer})")?,
             SymbolKind::PoolConstant => {}
             SymbolKind::JumpTable(_) => {}
         }
@@ -617,11 +864,12 @@ impl Display for SymbolKind {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SymFunction {
     pub mode: InstructionMode,
     pub size: u32,
     /// Offset to first instruction
+    #[serde(default)]
     pub offset: u32,
 }
 
@@ -635,7 +883,7 @@ impl SymFunction {
                 match key {
                     "size" => size = Some(parse_u32(value)?),
                     "offset" => offset = parse_u32(value)?,
-                    _ => bail!("{context}: unknown function attribute '{key}', must be one of: size, arm, thumb"),
+                    _ => return Err(unknown_attribute(context, "function attribute 'size' or 'offset'", key)),
                 }
             } else {
                 mode = Some(InstructionMode::parse(option, context)?);
@@ -666,7 +914,60 @@ impl Display for SymFunction {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymVeneer {
+    pub mode: InstructionMode,
+    pub size: u32,
+    /// Destination of the veneer, with the lowest bit set if it's a Thumb function
+    pub target: u32,
+}
+
+impl SymVeneer {
+    fn parse(options: &str, context: &ParseContext) -> Result<Self> {
+        let mut size = None;
+        let mut mode = None;
+        let mut target = None;
+        for option in options.split(',') {
+            if let Some((key, value)) = option.split_once('=') {
+                match key {
+                    "size" => size = Some(parse_u32(value)?),
+                    "target" => target = Some(parse_u32(value)?),
+                    _ => return Err(unknown_attribute(context, "veneer attribute 'size' or 'target'", key)),
+                }
+            } else {
+                mode = Some(InstructionMode::parse(option, context)?);
+            }
+        }
+
+        Ok(Self {
+            mode: mode.with_context(|| format!("{context}: veneer must have an instruction mode"))?,
+            size: size.with_context(|| format!("{context}: veneer must have a size"))?,
+            target: target.with_context(|| format!("{context}: veneer must have a target"))?,
+        })
+    }
+
+    pub fn target_address(&self) -> u32 {
+        self.target & !1
+    }
+
+    pub fn target_thumb(&self) -> bool {
+        (self.target & 1) != 0
+    }
+}
+
+impl From<Veneer> for SymVeneer {
+    fn from(veneer: Veneer) -> Self {
+        Self { mode: InstructionMode::from_thumb(veneer.thumb), size: veneer.size, target: veneer.target }
+    }
+}
+
+impl Display for SymVeneer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},size={:#x},target={:#x}", self.mode, self.size, self.target)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SymLabel {
     /// If true, the label is not used by the function itself, but accessed externally. Such labels are only discovered
     /// during relocation analysis, which is not performed by the dis/delink subcommands. External label symbols are
@@ -687,14 +988,15 @@ impl Display for SymLabel {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum InstructionMode {
     Arm,
     Thumb,
 }
 
 impl InstructionMode {
-    fn parse(text: &str, context: &ParseContext) -> Result<Self> {
+    pub fn parse(text: &str, context: &ParseContext) -> Result<Self> {
         match text {
             "arm" => Ok(Self::Arm),
             "thumb" => Ok(Self::Thumb),
@@ -727,22 +1029,34 @@ impl Display for InstructionMode {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SymJumpTable {
     pub size: u32,
     pub code: bool,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SymData {
     Any,
-    Byte { count: Option<u32> },
-    Short { count: Option<u32> },
-    Word { count: Option<u32> },
+    Byte {
+        count: Option<u32>,
+    },
+    Short {
+        count: Option<u32>,
+    },
+    Word {
+        count: Option<u32>,
+    },
+    /// Struct declared in `types.txt`
+    Struct {
+        name: TypeName,
+        count: Option<u32>,
+    },
 }
 
 impl SymData {
-    fn parse(kind: &str, context: &ParseContext) -> Result<Self> {
+    fn parse(kind: &str, types: &Types, context: &ParseContext) -> Result<Self> {
         if kind.is_empty() {
             bail!("{context}: expected data kind 'any', 'byte' or 'word' but got nothing");
         }
@@ -769,30 +1083,37 @@ impl SymData {
             "short" => Ok(Self::Short { count }),
             "byte" => Ok(Self::Byte { count }),
             "word" => Ok(Self::Word { count }),
-            kind => bail!("{context}: expected data kind 'any', 'byte', 'short' or 'word' but got '{kind}'"),
+            kind if types.get(kind).is_some() => Ok(Self::Struct { name: TypeName::new(kind), count }),
+            kind if !types.is_empty() && is_identifier(kind) => {
+                bail!("{context}: unknown data kind '{kind}', it's not a struct declared in types.txt")
+            }
+            kind => Err(unknown_attribute(context, "data kind 'any', 'byte', 'short' or 'word'", kind)),
         }
     }
 
-    pub fn count(self) -> Option<u32> {
-        match self {
+    pub fn count(&self) -> Option<u32> {
+        match *self {
             Self::Any => None,
             Self::Byte { count } => count,
             Self::Short { count } => count,
             Self::Word { count } => count,
+            Self::Struct { count, .. } => count,
         }
     }
 
-    pub fn element_size(self) -> u32 {
+    /// Returns the size of one element, or `None` if it's a struct which is not declared in `types`.
+    pub fn element_size(&self, types: &Types) -> Option<u32> {
         match self {
-            Self::Any => 1,
-            Self::Byte { .. } => 1,
-            Self::Short { .. } => 2,
-            Self::Word { .. } => 4,
+            Self::Any => Some(1),
+            Self::Byte { .. } => Some(1),
+            Self::Short { .. } => Some(2),
+            Self::Word { .. } => Some(4),
+            Self::Struct { name, .. } => types.size_of(name),
         }
     }
 
-    pub fn size(&self) -> Option<u32> {
-        self.count().map(|count| self.element_size() * count)
+    pub fn size(&self, types: &Types) -> Option<u32> {
+        self.count().zip(self.element_size(types)).map(|(count, element_size)| element_size * count)
     }
 
     pub fn write_assembly<W: io::Write>(
@@ -802,13 +1123,26 @@ impl SymData {
         bytes: &[u8],
         symbols: &SymbolLookup,
     ) -> Result<()> {
-        if let Some(size) = self.size() {
+        if let Some(size) = self.size(symbols.types) {
             if bytes.len() < size as usize {
                 log::error!("Not enough bytes to write raw data directive");
                 bail!("Not enough bytes to write raw data directive");
             }
         }
 
+        let element_size = match self {
+            Self::Struct { name, .. } => match symbols.types.get(name.as_str()) {
+                Some(struct_type) => {
+                    return Self::write_structs(w, symbol.addr, struct_type, bytes, symbols);
+                }
+                None => {
+                    log::warn!("Struct '{name}' of symbol '{}' is not declared in types.txt", symbol.name);
+                    1
+                }
+            },
+            _ => self.element_size(symbols.types).unwrap(),
+        };
+
         let mut offset = 0;
         while offset < bytes.len() {
             let mut data_directive = false;
@@ -840,6 +1174,7 @@ impl SymData {
                         SymData::Byte { .. } => write!(w, "    .byte 0x{:02x}", bytes[0])?,
                         SymData::Short { .. } => write!(w, "    .short {:#x}", bytes[0])?,
                         SymData::Word { .. } => write!(w, "    .word {:#x}", u32::from_le_slice(bytes))?,
+                        SymData::Struct { .. } => write!(w, "    .byte 0x{:02x}", bytes[0])?,
                     }
                     data_directive = true;
                 } else {
@@ -848,9 +1183,10 @@ impl SymData {
                         SymData::Byte { .. } => write!(w, ", 0x{:02x}", bytes[0])?,
                         SymData::Short { .. } => write!(w, ", {:#x}", u16::from_le_slice(bytes))?,
                         SymData::Word { .. } => write!(w, ", {:#x}", u32::from_le_slice(bytes))?,
+                        SymData::Struct { .. } => write!(w, ", 0x{:02x}", bytes[0])?,
                     }
                 }
-                column += self.element_size() as usize;
+                column += element_size as usize;
             }
             if data_directive {
                 writeln!(w)?;
@@ -861,6 +1197,86 @@ impl SymData {
 
         Ok(())
     }
+
+    /// Writes each element of a struct array, with one directive per field.
+    fn write_structs<W: io::Write>(
+        w: &mut W,
+        address: u32,
+        struct_type: &StructType,
+        bytes: &[u8],
+        symbols: &SymbolLookup,
+    ) -> Result<()> {
+        let size = struct_type.size as usize;
+        for (index, element) in bytes.chunks(size).enumerate() {
+            if element.len() < size {
+                // Trailing bytes which don't fit a whole element
+                write!(w, "    .byte ")?;
+                for (i, byte) in element.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ", ")?;
+                    }
+                    write!(w, "0x{byte:02x}")?;
+                }
+                writeln!(w)?;
+                break;
+            }
+            writeln!(w, "    ; [{index}]")?;
+            Self::write_struct_fields(w, address + (index * size) as u32, struct_type, element, symbols, "")?;
+        }
+        Ok(())
+    }
+
+    fn write_struct_fields<W: io::Write>(
+        w: &mut W,
+        address: u32,
+        struct_type: &StructType,
+        bytes: &[u8],
+        symbols: &SymbolLookup,
+        field_prefix: &str,
+    ) -> Result<()> {
+        let mut offset = 0;
+        for field in &struct_type.fields {
+            if offset < field.offset {
+                writeln!(w, "    .space {:#x} ; padding", field.offset - offset)?;
+            }
+            let field_name = format!("{field_prefix}{}", field.name);
+            let field_size = field.kind.size(symbols.types);
+            for i in 0..field.count {
+                let field_offset = field.offset + i * field_size;
+                let field_bytes = &bytes[field_offset as usize..];
+                let field_address = address + field_offset;
+                let field_name = if field.count > 1 { format!("{field_name}[{i}]") } else { field_name.clone() };
+                match &field.kind {
+                    FieldKind::Byte => writeln!(w, "    .byte 0x{:02x} ; {field_name}", field_bytes[0])?,
+                    FieldKind::Short => writeln!(w, "    .short {:#x} ; {field_name}", u16::from_le_slice(field_bytes))?,
+                    FieldKind::Word => writeln!(w, "    .word {:#x} ; {field_name}", u32::from_le_slice(field_bytes))?,
+                    FieldKind::Pointer => {
+                        let pointer = u32::from_le_slice(field_bytes);
+                        if !symbols.write_symbol(w, field_address, pointer, &mut false, "    ")? {
+                            writeln!(w, "    .word {pointer:#x} ; {field_name}")?;
+                        }
+                    }
+                    FieldKind::Struct(name) => {
+                        // Nested structs are always declared
+                        let nested = symbols.types.get(name).unwrap();
+                        Self::write_struct_fields(
+                            w,
+                            field_address,
+                            nested,
+                            &field_bytes[..nested.size as usize],
+                            symbols,
+                            &format!("{field_name}."),
+                        )?;
+                    }
+                }
+            }
+            offset = field.offset + field_size * field.count;
+        }
+        if offset < struct_type.size {
+            writeln!(w, "    .space {:#x} ; padding", struct_type.size - offset)?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for SymData {
@@ -876,11 +1292,14 @@ impl Display for SymData {
             Self::Byte { count: None } => write!(f, "byte[]"),
             Self::Short { count: None } => write!(f, "short[]"),
             Self::Word { count: None } => write!(f, "word[]"),
+            Self::Struct { name, count: Some(1) } => write!(f, "{name}"),
+            Self::Struct { name, count: Some(count) } => write!(f, "{name}[{count}]"),
+            Self::Struct { name, count: None } => write!(f, "{name}[]"),
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SymBss {
     pub size: Option<u32>,
 }
@@ -892,7 +1311,7 @@ impl SymBss {
                 if let Some((key, value)) = option.split_once('=') {
                     match key {
                         "size" => size = Some(parse_u32(value)?),
-                        _ => bail!("{context}: expected 'size=...' but got '{key}={value}'"),
+                        _ => return Err(unknown_attribute(context, "bss attribute 'size'", key)),
                     }
                 } else {
                     bail!("{context}: expected 'key=value' but got '{option}'");
@@ -919,6 +1338,8 @@ pub struct SymbolLookup<'a> {
     /// All symbol maps, including external modules
     pub symbol_maps: &'a SymbolMaps,
     pub relocations: &'a Relocations,
+    /// Struct types for data symbols
+    pub types: &'a Types,
 }
 
 impl<'a> SymbolLookup<'a> {
@@ -1028,7 +1449,7 @@ impl<'a> LookupSymbol for SymbolLookup<'a> {
         }
         if let Some(relocation) = self.relocations.get(source) {
             let module_kind = relocation.module().first_module().unwrap();
-            let external_symbol_map = self.symbol_maps.get(module_kind).unwrap();
+            let external_symbol_map = self.symbol_maps.get(module_kind)?;
 
             let symbol = match external_symbol_map.by_address(destination) {
                 Ok(s) => s.map(|(_, symbol)| symbol),
@@ -0,0 +1,260 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::util::{io::open_file, parse::parse_u32};
+
+use super::ParseContext;
+
+/// Struct layouts declared by the user in `types.txt`, for data symbols such as `kind:data(MyStruct[12])`. The file is a
+/// small subset of C where `//` starts a comment:
+///
+/// ```text
+/// struct Entry {
+///     u16 id;
+///     u16 flags;
+///     void *callback;
+///     u8 name[8];
+///     struct Other other;   // must be declared above
+/// };
+/// ```
+///
+/// Fields are laid out with their natural alignment, like mwcc does.
+#[derive(Default)]
+pub struct Types {
+    structs: BTreeMap<String, StructType>,
+}
+
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<StructField>,
+    pub size: u32,
+    pub alignment: u32,
+}
+
+pub struct StructField {
+    pub name: String,
+    pub kind: FieldKind,
+    pub offset: u32,
+    /// Number of elements if the field is an array, otherwise 1
+    pub count: u32,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum FieldKind {
+    Byte,
+    Short,
+    Word,
+    Pointer,
+    Struct(String),
+}
+
+impl Types {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut context = ParseContext { file_path: path.to_str().unwrap().to_string(), row: 0 };
+
+        let file = open_file(path)?;
+        let reader = BufReader::new(file);
+
+        let mut types = Self::new();
+        let mut current: Option<(String, Vec<(String, FieldKind, u32)>)> = None;
+        for line in reader.lines() {
+            context.row += 1;
+            let line = line?;
+            let line = line.split_once("//").map(|(line, _comment)| line).unwrap_or(line.as_str()).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((_, fields)) = current.as_mut() else {
+                let Some(name) = line.strip_prefix("struct ").and_then(|rest| rest.strip_suffix('{')) else {
+                    bail!("{context}: expected 'struct Name {{' but got '{line}'");
+                };
+                let name = name.trim();
+                if !is_identifier(name) {
+                    bail!("{context}: invalid struct name '{name}'");
+                }
+                if types.structs.contains_key(name) {
+                    bail!("{context}: struct '{name}' is already declared");
+                }
+                current = Some((name.to_string(), vec![]));
+                continue;
+            };
+            if line != "};" {
+                fields.push(Self::parse_field(line, &types, &context)?);
+                continue;
+            }
+
+            let (name, fields) = current.take().unwrap();
+            let struct_type = types.layout(name, fields, &context)?;
+            types.structs.insert(struct_type.name.clone(), struct_type);
+        }
+        if let Some((name, _)) = current {
+            bail!("{context}: struct '{name}' is missing its closing '}};'");
+        }
+
+        Ok(types)
+    }
+
+    /// Loads the types file if there is one, otherwise returns no types.
+    pub fn from_optional_file<P: AsRef<Path>>(path: Option<P>) -> Result<Self> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::new()),
+        }
+    }
+
+    fn parse_field(line: &str, types: &Types, context: &ParseContext) -> Result<(String, FieldKind, u32)> {
+        let Some(line) = line.strip_suffix(';') else {
+            bail!("{context}: expected field to end with ';'");
+        };
+        let line = line.strip_prefix("struct ").unwrap_or(line);
+
+        let (type_name, name) = line
+            .trim()
+            .split_once(char::is_whitespace)
+            .with_context(|| format!("{context}: expected field type and name but got '{line}'"))?;
+        let mut type_name = type_name.trim();
+        let mut name = name.trim();
+        let mut pointer = false;
+        if let Some(rest) = type_name.strip_suffix('*') {
+            type_name = rest;
+            pointer = true;
+        }
+        if let Some(rest) = name.strip_prefix('*') {
+            name = rest.trim();
+            pointer = true;
+        }
+
+        let (name, count) = match name.split_once('[') {
+            Some((name, count)) => {
+                let count = count.strip_suffix(']').with_context(|| format!("{context}: expected ']' after array size"))?;
+                let count =
+                    parse_u32(count.trim()).with_context(|| format!("{context}: failed to parse array size '{count}'"))?;
+                (name.trim(), count)
+            }
+            None => (name, 1),
+        };
+        if !is_identifier(name) {
+            bail!("{context}: invalid field name '{name}'");
+        }
+
+        let kind = if pointer {
+            FieldKind::Pointer
+        } else {
+            match type_name {
+                "u8" | "s8" | "char" | "bool" => FieldKind::Byte,
+                "u16" | "s16" | "short" => FieldKind::Short,
+                "u32" | "s32" | "int" | "long" | "float" => FieldKind::Word,
+                name if types.structs.contains_key(name) => FieldKind::Struct(name.to_string()),
+                name => bail!("{context}: unknown type '{name}', structs must be declared before they are used"),
+            }
+        };
+        Ok((name.to_string(), kind, count))
+    }
+
+    fn layout(&self, name: String, fields: Vec<(String, FieldKind, u32)>, context: &ParseContext) -> Result<StructType> {
+        if fields.is_empty() {
+            bail!("{context}: struct '{name}' has no fields");
+        }
+
+        let mut offset = 0;
+        let mut alignment = 1;
+        let mut struct_fields = Vec::with_capacity(fields.len());
+        for (field_name, kind, count) in fields {
+            let (size, field_alignment) = self.size_and_alignment(&kind);
+            offset = offset.next_multiple_of(field_alignment);
+            alignment = alignment.max(field_alignment);
+            struct_fields.push(StructField { name: field_name, kind, offset, count });
+            offset += size * count;
+        }
+        let size = offset.next_multiple_of(alignment);
+
+        Ok(StructType { name, fields: struct_fields, size, alignment })
+    }
+
+    fn size_and_alignment(&self, kind: &FieldKind) -> (u32, u32) {
+        match kind {
+            FieldKind::Byte => (1, 1),
+            FieldKind::Short => (2, 2),
+            FieldKind::Word | FieldKind::Pointer => (4, 4),
+            FieldKind::Struct(name) => {
+                // Only declared structs can be fields, so it always exists
+                let struct_type = &self.structs[name];
+                (struct_type.size, struct_type.alignment)
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StructType> {
+        self.structs.get(name)
+    }
+
+    /// Returns true if no structs are declared, which is also the case when the project has no `types.txt`.
+    pub fn is_empty(&self) -> bool {
+        self.structs.is_empty()
+    }
+
+    /// Returns the size of one element of the given type, if it's declared.
+    pub fn size_of(&self, name: &TypeName) -> Option<u32> {
+        self.get(name.as_str()).map(|struct_type| struct_type.size)
+    }
+}
+
+impl FieldKind {
+    pub fn size(&self, types: &Types) -> u32 {
+        types.size_and_alignment(self).0
+    }
+}
+
+/// Name of a struct type in `types.txt`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TypeName(String);
+
+impl TypeName {
+    pub fn new(name: &str) -> Self {
+        Self(name.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for TypeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for TypeName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for TypeName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        if !is_identifier(&name) {
+            return Err(de::Error::custom(format!("type name '{name}' is not an identifier")));
+        }
+        Ok(Self(name))
+    }
+}
+
+pub fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -1,6 +1,9 @@
 use anyhow::Result;
 use argp::FromArgs;
-use ds_decomp::cmd::{CheckArgs, Delink, Disassemble, ImportArgs, Init, Lcf, Objdiff, RomArgs};
+use ds_decomp::cmd::{
+    CheckArgs, Convert, Ctx, Delink, Demangle, Disassemble, ImportArgs, Init, Lcf, Migrate, Ninja, Objdiff, OverlayLoadsArgs,
+    Reanalyze, RomArgs, SymArgs, Validate,
+};
 use log::LevelFilter;
 
 /// Command-line toolkit for decompiling DS games.
@@ -25,6 +28,15 @@ enum Command {
     Import(ImportArgs),
     Check(CheckArgs),
     Objdiff(Objdiff),
+    Ninja(Ninja),
+    Reanalyze(Reanalyze),
+    OverlayLoads(OverlayLoadsArgs),
+    Demangle(Demangle),
+    Ctx(Ctx),
+    Sym(SymArgs),
+    Convert(Convert),
+    Validate(Validate),
+    Migrate(Migrate),
 }
 
 impl Command {
@@ -38,6 +50,15 @@ impl Command {
             Command::Import(import) => import.run(),
             Command::Check(check) => check.run(),
             Command::Objdiff(objdiff) => objdiff.run(),
+            Command::Ninja(ninja) => ninja.run(),
+            Command::Reanalyze(reanalyze) => reanalyze.run(),
+            Command::OverlayLoads(overlay_loads) => overlay_loads.run(),
+            Command::Demangle(demangle) => demangle.run(),
+            Command::Ctx(ctx) => ctx.run(),
+            Command::Sym(sym) => sym.run(),
+            Command::Convert(convert) => convert.run(),
+            Command::Validate(validate) => validate.run(),
+            Command::Migrate(migrate) => migrate.run(),
         }
     }
 }
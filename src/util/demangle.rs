@@ -0,0 +1,365 @@
+/// Demangles a symbol name mangled by mwcc, such as `__ct__8CPlayerFv` into `CPlayer::CPlayer(void)`. Returns `None` if
+/// the name is not mangled or uses an unsupported part of the scheme.
+pub fn demangle(name: &str) -> Option<String> {
+    // Special names like `__ct` start with underscores which are not separators
+    let search_start = name.len() - name.trim_start_matches('_').len();
+    let mut search_offset = search_start;
+    while let Some(index) = name[search_offset..].find("__") {
+        let split = search_offset + index;
+        search_offset = split + 1;
+        if split == 0 {
+            continue;
+        }
+        let (base_name, rest) = (&name[..split], &name[split + 2..]);
+        if rest.is_empty() {
+            continue;
+        }
+        if let Some(demangled) = Demangler::new(rest).demangle_symbol(base_name) {
+            return Some(demangled);
+        }
+    }
+    None
+}
+
+struct Demangler<'a> {
+    text: &'a str,
+}
+
+impl<'a> Demangler<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text.chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.text = &self.text[c.len_utf8()..];
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.text = &self.text[1..];
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses the part after `__`, which is an optional class followed by an optional function signature.
+    fn demangle_symbol(&mut self, base_name: &str) -> Option<String> {
+        let class = match self.peek()? {
+            'Q' | '0'..='9' => Some(self.parse_qualified_name()?),
+            _ => None,
+        };
+        let is_const = self.eat('C');
+        let params = if self.eat('F') { Some(self.parse_params(None)?) } else { None };
+        if !self.text.is_empty() || (is_const && params.is_none()) || (class.is_none() && params.is_none()) {
+            return None;
+        }
+
+        let mut result = String::new();
+        if let Some(class) = &class {
+            result.push_str(class);
+            result.push_str("::");
+        }
+        result.push_str(&Self::demangle_special_name(base_name, class.as_deref())?);
+        if let Some(params) = params {
+            result.push('(');
+            result.push_str(&params);
+            result.push(')');
+        }
+        if is_const {
+            result.push_str(" const");
+        }
+        Some(result)
+    }
+
+    fn demangle_special_name(name: &str, class: Option<&str>) -> Option<String> {
+        let class_name = class.map(|class| {
+            let last = class.rsplit("::").next().unwrap_or(class);
+            last.split_once('<').map(|(name, _)| name).unwrap_or(last)
+        });
+        let operator = match name {
+            "__ct" => return class_name.map(|name| name.to_string()),
+            "__dt" => return class_name.map(|name| format!("~{name}")),
+            "__nw" => " new",
+            "__dl" => " delete",
+            "__nwa" => " new[]",
+            "__dla" => " delete[]",
+            "__as" => "=",
+            "__eq" => "==",
+            "__ne" => "!=",
+            "__lt" => "<",
+            "__gt" => ">",
+            "__le" => "<=",
+            "__ge" => ">=",
+            "__pl" => "+",
+            "__mi" => "-",
+            "__ml" => "*",
+            "__dv" => "/",
+            "__md" => "%",
+            "__er" => "^",
+            "__ad" => "&",
+            "__or" => "|",
+            "__co" => "~",
+            "__nt" => "!",
+            "__aa" => "&&",
+            "__oo" => "||",
+            "__ls" => "<<",
+            "__rs" => ">>",
+            "__apl" => "+=",
+            "__ami" => "-=",
+            "__amu" => "*=",
+            "__adv" => "/=",
+            "__amd" => "%=",
+            "__aer" => "^=",
+            "__aad" => "&=",
+            "__aor" => "|=",
+            "__als" => "<<=",
+            "__ars" => ">>=",
+            "__pp" => "++",
+            "__mm" => "--",
+            "__cl" => "()",
+            "__vc" => "[]",
+            "__rf" => "->",
+            "__rm" => "->*",
+            "__cm" => ",",
+            name => {
+                if let Some(conversion) = name.strip_prefix("__op") {
+                    let mut demangler = Demangler::new(conversion);
+                    let ty = demangler.parse_type()?;
+                    if !demangler.text.is_empty() {
+                        return None;
+                    }
+                    return Some(format!("operator {ty}"));
+                }
+                return Some(Self::demangle_template_name(name));
+            }
+        };
+        Some(format!("operator{operator}"))
+    }
+
+    /// Parses a class name with a length prefix, or a `Q` qualified name with several of them.
+    fn parse_qualified_name(&mut self) -> Option<String> {
+        if self.eat('Q') {
+            let count = self.next_char()?.to_digit(10)?;
+            let mut names = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                names.push(self.parse_name()?);
+            }
+            Some(names.join("::"))
+        } else {
+            self.parse_name()
+        }
+    }
+
+    fn parse_name(&mut self) -> Option<String> {
+        let digits = self.text.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        let length: usize = self.text[..digits].parse().ok()?;
+        let end = digits.checked_add(length)?;
+        let name = self.text.get(digits..end)?;
+        self.text = &self.text[end..];
+        Some(Self::demangle_template_name(name))
+    }
+
+    /// Demangles the template arguments of a name like `TVec<f,3>`.
+    fn demangle_template_name(name: &str) -> String {
+        let Some((base, args)) = name.split_once('<') else {
+            return name.to_string();
+        };
+        let Some(args) = args.strip_suffix('>') else {
+            return name.to_string();
+        };
+
+        let mut demangled_args = vec![];
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in args.char_indices() {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                ',' if depth == 0 => {
+                    demangled_args.push(Self::demangle_template_arg(&args[start..i]));
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        demangled_args.push(Self::demangle_template_arg(&args[start..]));
+        format!("{base}<{}>", demangled_args.join(", "))
+    }
+
+    fn demangle_template_arg(arg: &str) -> String {
+        let mut demangler = Demangler::new(arg);
+        match demangler.parse_type() {
+            Some(ty) if demangler.text.is_empty() => ty,
+            _ => arg.to_string(),
+        }
+    }
+
+    /// Parses function parameters until the end of the name, or until `end` for function types.
+    fn parse_params(&mut self, end: Option<char>) -> Option<String> {
+        let mut params = vec![];
+        loop {
+            match (self.peek(), end) {
+                (None, None) => break,
+                (Some(c), Some(end)) if c == end => break,
+                (None, Some(_)) => return None,
+                _ => {}
+            }
+            params.push(self.parse_type()?);
+        }
+        if params.is_empty() {
+            return None;
+        }
+        Some(params.join(", "))
+    }
+
+    fn parse_type(&mut self) -> Option<String> {
+        let c = self.peek()?;
+        let ty = match c {
+            'Q' | '0'..='9' => return self.parse_qualified_name(),
+            'C' | 'V' => {
+                self.next_char();
+                let qualifier = if c == 'C' { "const" } else { "volatile" };
+                let inner = self.parse_type()?;
+                if inner.ends_with('*') || inner.ends_with('&') {
+                    format!("{inner} {qualifier}")
+                } else {
+                    format!("{qualifier} {inner}")
+                }
+            }
+            'U' | 'S' => {
+                self.next_char();
+                let sign = if c == 'U' { "unsigned" } else { "signed" };
+                let inner = self.parse_basic_type()?;
+                format!("{sign} {inner}")
+            }
+            'P' | 'R' => {
+                self.next_char();
+                let indirection = if c == 'P' { "*" } else { "&" };
+                if self.peek() == Some('F') {
+                    let (params, ret) = self.parse_function_type()?;
+                    format!("{ret} ({indirection})({params})")
+                } else {
+                    let inner = self.parse_type()?;
+                    format!("{inner}{indirection}")
+                }
+            }
+            'M' => {
+                self.next_char();
+                let class = self.parse_qualified_name()?;
+                if self.peek() == Some('F') {
+                    let (params, ret) = self.parse_function_type()?;
+                    format!("{ret} ({class}::*)({params})")
+                } else {
+                    let inner = self.parse_type()?;
+                    format!("{inner} {class}::*")
+                }
+            }
+            'A' => {
+                self.next_char();
+                let digits = self.text.chars().take_while(|c| c.is_ascii_digit()).count();
+                let size: u32 = self.text[..digits].parse().ok()?;
+                self.text = &self.text[digits..];
+                if !self.eat('_') {
+                    return None;
+                }
+                let inner = self.parse_type()?;
+                format!("{inner}[{size}]")
+            }
+            'F' => {
+                let (params, ret) = self.parse_function_type()?;
+                format!("{ret} ({params})")
+            }
+            _ => self.parse_basic_type()?,
+        };
+        Some(ty)
+    }
+
+    /// Parses `F<params>_<return type>` and returns the parameters and return type.
+    fn parse_function_type(&mut self) -> Option<(String, String)> {
+        if !self.eat('F') {
+            return None;
+        }
+        let params = self.parse_params(Some('_'))?;
+        if !self.eat('_') {
+            return None;
+        }
+        let ret = self.parse_type()?;
+        Some((params, ret))
+    }
+
+    fn parse_basic_type(&mut self) -> Option<String> {
+        let ty = match self.next_char()? {
+            'v' => "void",
+            'b' => "bool",
+            'c' => "char",
+            'w' => "wchar_t",
+            's' => "short",
+            'i' => "int",
+            'l' => "long",
+            'x' => "long long",
+            'f' => "float",
+            'd' => "double",
+            'r' => "long double",
+            'e' => "...",
+            _ => return None,
+        };
+        Some(ty.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_special_names() {
+        assert_eq!(demangle("__ct__8CPlayerFv").as_deref(), Some("CPlayer::CPlayer(void)"));
+        assert_eq!(demangle("__dt__8CPlayerFv").as_deref(), Some("CPlayer::~CPlayer(void)"));
+        assert_eq!(demangle("__as__8CPlayerFRC8CPlayer").as_deref(), Some("CPlayer::operator=(const CPlayer&)"));
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(demangle("func__Fv").as_deref(), Some("func(void)"));
+        assert_eq!(demangle("Update__8CPlayerFi").as_deref(), Some("CPlayer::Update(int)"));
+        assert_eq!(demangle("GetHealth__8CPlayerCFv").as_deref(), Some("CPlayer::GetHealth(void) const"));
+        assert_eq!(demangle("Set__FUi").as_deref(), Some("Set(unsigned int)"));
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        assert_eq!(demangle("Init__Q24Game7CPlayerFv").as_deref(), Some("Game::CPlayer::Init(void)"));
+    }
+
+    #[test]
+    fn test_templates() {
+        assert_eq!(demangle("Length__9TVec<f,3>CFv").as_deref(), Some("TVec<float, 3>::Length(void) const"));
+    }
+
+    #[test]
+    fn test_const_and_pointer_params() {
+        assert_eq!(demangle("Set__8CPlayerFPCci").as_deref(), Some("CPlayer::Set(const char*, int)"));
+        assert_eq!(demangle("Copy__8CPlayerFRC8CPlayer").as_deref(), Some("CPlayer::Copy(const CPlayer&)"));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(demangle("main"), None);
+        assert_eq!(demangle("func__"), None);
+        assert_eq!(demangle("func__8CPlay"), None);
+        assert_eq!(demangle("func__Fz"), None);
+        assert_eq!(demangle("func__Q"), None);
+        assert_eq!(demangle("Get__8CPlayerC"), None);
+        assert_eq!(demangle("func__18446744073709551615A"), None);
+    }
+}
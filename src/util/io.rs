@@ -75,7 +75,7 @@ pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, FileError> {
             match err.kind() {
                 io::ErrorKind::NotFound => return FileNotFoundSnafu { path }.fail(),
                 io::ErrorKind::OutOfMemory => return FileOutOfMemorySnafu { path }.fail(),
-                _ => todo!(),
+                _ => Err(err)?,
             }
         }
     };
@@ -145,3 +145,9 @@ pub fn create_dir_all<P: AsRef<Path>>(path: P) -> Result<(), FileError> {
     }
     Ok(())
 }
+
+/// Returns true if the path has a `.json` extension. Config files with this extension are read and written as JSON instead
+/// of their text format.
+pub fn is_json_file<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().extension().is_some_and(|extension| extension == "json")
+}
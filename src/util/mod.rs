@@ -1,4 +1,5 @@
 pub mod bytes;
+pub mod demangle;
 pub mod ds;
 pub mod io;
 pub mod parse;
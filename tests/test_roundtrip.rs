@@ -11,7 +11,7 @@ use std::{
 use anyhow::Result;
 use ds_decomp::{
     cmd::{CheckModules, ConfigRom, Delink, Disassemble, Init, Lcf},
-    config::config::Config,
+    config::{config::Config, symbol::SymbolMap, types::Types},
     util::io::{open_file, read_to_string},
 };
 use ds_rom::{
@@ -66,6 +66,7 @@ fn test_roundtrip() -> Result<()> {
         );
 
         assert!(directory_equals(&target_config_dir, &dsd_config_dir)?);
+        check_pre_code_pools(&dsd_config_yaml, &dsd_config)?;
 
         // Disassemble
         let disassemble = Disassemble { config_path: dsd_config_yaml.clone(), asm_path: project_path.join("asm") };
@@ -117,10 +118,46 @@ fn test_roundtrip() -> Result<()> {
     Ok(())
 }
 
+/// Functions with a constant pool before their first instruction must not end at their own first instruction.
+fn check_pre_code_pools(config_path: &Path, config: &Config) -> Result<()> {
+    let config_dir = config_path.parent().unwrap();
+    let types = Types::from_optional_file(config.types.as_ref().map(|types| config_dir.join(types)))?;
+
+    let mut modules = vec![&config.main_module];
+    modules.extend(config.autoloads.iter().map(|autoload| &autoload.module));
+    modules.extend(config.overlays.iter().map(|overlay| &overlay.module));
+
+    let mut num_functions = 0;
+    for module in modules {
+        let symbol_map = SymbolMap::from_file(config_dir.join(&module.symbols), &types)?;
+        for (sym_function, symbol) in symbol_map.functions() {
+            if sym_function.offset == 0 {
+                continue;
+            }
+            assert!(
+                sym_function.size > sym_function.offset,
+                "Function {} in {} was cut short at its first instruction",
+                symbol.name,
+                module.name
+            );
+            num_functions += 1;
+        }
+    }
+    log::info!("Checked {num_functions} functions with pre-code constant pools");
+    Ok(())
+}
+
 fn dsd_init(project_path: &Path, rom_config: &Path) -> Result<PathBuf> {
     let dsd_config_dir = project_path.join("config");
     let build_path = project_path.join("build");
-    let init = Init { rom_config: rom_config.to_path_buf(), output_path: dsd_config_dir.clone(), dry: false, build_path };
+    let init = Init {
+        rom_config: rom_config.to_path_buf(),
+        output_path: dsd_config_dir.clone(),
+        dry: false,
+        build_path,
+        conditional_calls: false,
+        overlay_loader: None,
+    };
     init.run()?;
     Ok(dsd_config_dir)
 }